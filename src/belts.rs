@@ -1,15 +1,26 @@
 use arrayvec::ArrayVec;
 use bevy::prelude::*;
 use bevy_ecs_tilemap::prelude::*;
-
-use crate::buildings::{BuildingType, BuildRequestedEvent};
+use serde::{Deserialize, Serialize};
+
+use crate::buildings::alerts::{AlertKind, BuildingAlert};
+use crate::buildings::status::BuildingStatus;
+use crate::buildings::{
+    building_at, BuildRequestedEvent, Building, BuildingChangedEvent, BuildingTile, BuildingType,
+};
+use crate::direction::MapDirection;
 use crate::map::{BuildingLayer, BuildingTileType};
+use crate::mods::registry::ModName;
+use crate::simulation::Simulation;
+use crate::statistics::{ItemFlow, ItemFlowEvent};
 
 const BELT_CAPACITY: usize = 3;
 
 #[derive(Component)]
 pub struct Belt {
     pub items: ArrayVec<(Entity, f32), BELT_CAPACITY>,
+    // the first item reached the end and there is nothing to pass it to
+    pub dead_end: bool,
 }
 
 const ITEM_SIZE: f32 = 1. / BELT_CAPACITY as f32;
@@ -30,6 +41,18 @@ impl Belt {
     }
 }
 
+impl BuildingAlert for Belt {
+    fn alert(&self) -> Option<AlertKind> {
+        self.dead_end.then_some(AlertKind::DeadEndBelt)
+    }
+}
+
+impl BuildingStatus for Belt {
+    fn status(&self, lines: &mut Vec<String>) {
+        lines.push(format!("items {}/{}", self.items.len(), BELT_CAPACITY));
+    }
+}
+
 #[derive(Component)]
 pub struct Item {
     pub belt: Entity,
@@ -38,66 +61,64 @@ pub struct Item {
 
 pub fn build_belt(
     mut commands: Commands,
-    mut tiles: Query<&mut TileTextureIndex>,
-    mut events: EventReader<BuildRequestedEvent>,
-    mut last_placed: Local<Option<(Entity, TilePos)>>,
-    mut buildings_layer_query: Query<&mut TileStorage, With<BuildingLayer>>,
+    new_buildings: Query<(&BuildingType, &Building), Added<Building>>,
 ) {
-    use BuildingTileType::*;
+    for (building_type, building) in new_buildings.iter() {
+        if let BuildingType::Belt = building_type {
+            for (tile_entity, _, _) in building.layout.tiles.iter() {
+                commands.entity(*tile_entity).insert(Belt {
+                    items: ArrayVec::new(),
+                    dead_end: false,
+                });
+            }
+        }
+    }
+}
 
-    let mut building_layer = buildings_layer_query.single_mut();
+// build requests for a tile already occupied by a belt change the direction of that belt
+pub fn redirect_belts(
+    mut events: EventReader<BuildRequestedEvent>,
+    mut changed_events: EventWriter<BuildingChangedEvent>,
+    mut belt_tiles: Query<(&BuildingTile, &mut TileTextureIndex), With<Belt>>,
+    mut directions: Query<&mut MapDirection, With<Building>>,
+    building_layer_query: Query<&TileStorage, With<BuildingLayer>>,
+) {
+    let building_layer = building_layer_query.single();
 
     for event in events
         .iter()
         .filter(|e| matches!(e.building_type, BuildingType::Belt))
     {
-        if last_placed.map_or(false, |(_, pos)| {
-            pos.x == event.tile_pos.x && pos.y == event.tile_pos.y
-        }) {
+        let Some((building_tile, mut texture)) = building_layer
+            .checked_get(&event.tile_pos)
+            .and_then(|tile| belt_tiles.get_mut(tile).ok())
+        else {
             continue;
-        }
+        };
 
-        match building_layer.get(&event.tile_pos) {
-            Some(tile) => {
-                let is_belt = tiles
-                    .get(tile)
-                    .map_or(false, |t| BuildingTileType::from(*t).is_belt());
+        let belt_type = BuildingTileType::belt(event.direction);
 
-                if !is_belt {
-                    continue;
-                }
+        let current_type = BuildingTileType::from(*texture);
+
+        if current_type != belt_type {
+            *texture = belt_type.into();
+
+            if let Ok(mut direction) = directions.get_mut(building_tile.building) {
+                *direction = event.direction;
             }
-            None => {}
-        }
 
-        let (belt_dir, update_last_belt) = last_placed
-            .and_then(|(_, pos)| belt_dir_between(event.tile_pos, pos))
-            .map(|dir| (dir, true))
-            .unwrap_or((BeltDown, false));
-
-        let placed_belt = commands
-            .spawn(Belt {
-                items: ArrayVec::new(),
-            })
-            .id();
-
-        building_layer.set(&event.tile_pos, placed_belt);
-
-        if let Some((last_e, _)) = *last_placed {
-            if let Some(mut last_tile) = tiles
-                .get_mut(last_e)
-                .ok()
-                .filter(|t| update_last_belt && BuildingTileType::from(*t.as_ref()).is_belt())
-            {
-                last_tile.0 = belt_dir as u32;
+            if let Some(from) = current_type.belt_direction() {
+                changed_events.send(BuildingChangedEvent::Redirected {
+                    tile_pos: event.tile_pos,
+                    from,
+                    to: event.direction,
+                });
             }
         }
-
-        *last_placed = Some((placed_belt, event.tile_pos));
     }
 }
 
-fn belt_dir_between(
+pub fn belt_dir_between(
     TilePos { x: x1, y: y1 }: TilePos,
     TilePos { x: x2, y: y2 }: TilePos,
 ) -> Option<BuildingTileType> {
@@ -120,16 +141,22 @@ fn belt_dir_between(
     None
 }
 
+// everything a belt can hand its items to
+pub type BeltTargets<'w, 's> = Query<'w, 's, (), Or<(With<Belt>, With<BeltInput>)>>;
+
 pub fn move_items_on_belts(
-    mut items: Query<&mut Transform, With<Item>>,
+    mut items: Query<(&mut Item, &mut Transform)>,
     belt_tiles: Query<(Entity, &TilePos, &TileTextureIndex), With<Belt>>,
     mut belts: Query<(&mut Belt, &TileTextureIndex)>,
     mut building_layer_query: Query<
         (&mut TileStorage, &TilemapTileSize, &Transform),
         (With<BuildingLayer>, Without<Item>),
     >,
-    time: Res<Time>,
+    belt_targets: BeltTargets,
+    simulation: Res<Simulation>,
 ) {
+    let delta = simulation.delta().as_secs_f32();
+
     let (mut building_layer, tile_size, building_layer_transform) =
         building_layer_query.single_mut();
 
@@ -145,7 +172,7 @@ pub fn move_items_on_belts(
                 &mut building_layer,
                 &mut belts,
                 &mut items,
-                time.delta_seconds(),
+                delta,
                 tile_size,
                 building_layer_transform,
             );
@@ -155,24 +182,31 @@ pub fn move_items_on_belts(
             continue;
         };
 
+        // nothing takes items from a belt pointing at a building without an input
+        let is_dead_end = !belt_output_pos
+            .and_then(|next_pos| building_layer.checked_get(&next_pos))
+            .is_some_and(|next| belt_targets.contains(next));
+
         let mut max_progress = 1.0f32;
 
         for (item_entity, item_progress) in belt.items.iter_mut() {
-            let next_progress = f32::clamp(
-                *item_progress + time.delta_seconds(),
-                0.,
-                max_progress.max(0.),
-            );
+            let next_progress = f32::clamp(*item_progress + delta, 0., max_progress.max(0.));
             *item_progress = next_progress;
             max_progress = next_progress - ITEM_SIZE;
 
-            if let Ok(mut transform) = items.get_mut(*item_entity) {
+            if let Ok((_, mut transform)) = items.get_mut(*item_entity) {
                 let world_pos = tile_to_world_pos(*belt_pos, tile_size, building_layer_transform);
                 let offset = building_type.progress_offset(*item_progress);
 
                 transform.translation = (world_pos + offset).extend(10.);
             }
         }
+
+        let is_stuck = is_dead_end && belt.items.first().is_some_and(|(_, p)| *p >= 1.);
+
+        if belt.dead_end != is_stuck {
+            belt.dead_end = is_stuck;
+        }
     }
 }
 
@@ -181,7 +215,7 @@ fn try_move_item_between_belts(
     next_belt_pos: TilePos,
     building_layer: &TileStorage,
     belts: &mut Query<(&mut Belt, &TileTextureIndex)>,
-    items: &mut Query<&mut Transform, With<Item>>,
+    items: &mut Query<(&mut Item, &mut Transform)>,
     delta: f32,
     tile_size: &TilemapTileSize,
     building_layer_transform: &Transform,
@@ -215,7 +249,9 @@ fn try_move_item_between_belts(
 
         let (first_item_entity, _) = belt.items.pop_at(0).unwrap();
 
-        if let Ok(mut transform) = items.get_mut(first_item_entity) {
+        if let Ok((mut item, mut transform)) = items.get_mut(first_item_entity) {
+            item.belt = next_belt_entity;
+
             let world_pos = tile_to_world_pos(next_belt_pos, tile_size, building_layer_transform);
             let offset = next_belt_type.progress_offset(next_belt_progress);
 
@@ -224,20 +260,88 @@ fn try_move_item_between_belts(
     }
 }
 
-#[derive(PartialEq, Eq, Clone, Copy)]
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Hash, Serialize, Deserialize)]
 pub enum ItemType {
     Coal,
+    Mod(ModName),
+}
+
+impl ItemType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ItemType::Coal => "coal",
+            ItemType::Mod(name) => name.as_str(),
+        }
+    }
+
+    pub fn icon(&self) -> &'static str {
+        match self {
+            // mods don't bring their own art
+            ItemType::Coal | ItemType::Mod(_) => "items.png",
+        }
+    }
+}
+
+pub struct UnknownItemType;
+
+impl std::str::FromStr for ItemType {
+    type Err = UnknownItemType;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "coal" => Ok(ItemType::Coal),
+            _ => ModName::existing(s)
+                .map(ItemType::Mod)
+                .ok_or(UnknownItemType),
+        }
+    }
 }
 
-const MAX_INVENTORY_SIZE: usize = 8 * 8;
+pub const MAX_INVENTORY_SIZE: usize = 8 * 8;
 const STACK_SIZE: usize = 5;
 
-#[derive(Component)]
+#[derive(Component, Clone)]
 pub struct Inventory {
     pub slots: ArrayVec<Option<(ItemType, usize)>, MAX_INVENTORY_SIZE>,
+    // the last item offered by a belt didn't fit
+    pub rejected: bool,
 }
 
 impl Inventory {
+    pub fn with_slots(slots: usize) -> Self {
+        Inventory {
+            slots: (0..slots.min(MAX_INVENTORY_SIZE)).map(|_| None).collect(),
+            rejected: false,
+        }
+    }
+
+    // takes up to `amount` items from the stack in the slot, returns how many it got
+    pub fn take(&mut self, slot: usize, amount: usize) -> usize {
+        let Some(Some((item_type, stored))) = self.slots.get(slot).copied() else {
+            return 0;
+        };
+
+        let taken = amount.min(stored);
+        self.slots[slot] = Some((item_type, stored - taken)).filter(|(_, left)| *left > 0);
+        taken
+    }
+
+    // how many of `amount` items would fit, without inserting them
+    pub fn room_for(&self, amount: usize, item_type: ItemType) -> usize {
+        let mut inventory = self.clone();
+        (0..amount)
+            .take_while(|_| inventory.insert(1, item_type))
+            .count()
+    }
+
+    pub fn clear_slot(&mut self, slot: usize) -> Option<(ItemType, usize)> {
+        self.slots.get_mut(slot).and_then(Option::take)
+    }
+
+    pub fn clear(&mut self) {
+        self.slots.iter_mut().for_each(|slot| *slot = None);
+    }
+
     pub fn insert(&mut self, amount: usize, item_type: ItemType) -> bool {
         let Some(slot) = self
             .slots
@@ -255,6 +359,22 @@ impl Inventory {
     }
 }
 
+impl BuildingAlert for Inventory {
+    fn alert(&self) -> Option<AlertKind> {
+        self.rejected.then_some(AlertKind::InventoryFull)
+    }
+}
+
+impl BuildingStatus for Inventory {
+    fn status(&self, lines: &mut Vec<String>) {
+        let used = self.slots.iter().flatten().count();
+        let items: usize = self.slots.iter().flatten().map(|(_, amount)| amount).sum();
+
+        lines.push(format!("slots {}/{}", used, self.slots.len()));
+        lines.push(format!("items {}", items));
+    }
+}
+
 #[derive(Component)]
 pub struct BeltInput {
     pub inventory: Entity,
@@ -267,6 +387,7 @@ pub fn input_from_belts(
     mut inventories: Query<&mut Inventory>,
     inputs: Query<&BeltInput>,
     building_layer_query: Query<&TileStorage, With<BuildingLayer>>,
+    mut item_flow: EventWriter<ItemFlowEvent>,
 ) {
     let building_layer = building_layer_query.single();
 
@@ -298,9 +419,171 @@ pub fn input_from_belts(
                 continue;
             };
 
-            if inventory.insert(1, item.item_type) {
+            let inserted = inventory.insert(1, item.item_type);
+
+            if inventory.rejected == inserted {
+                inventory.rejected = !inserted;
+            }
+
+            if inserted {
                 let (entity, _) = belt.items.pop_at(0).unwrap();
                 commands.entity(entity).despawn();
+
+                item_flow.send(ItemFlowEvent {
+                    item_type: item.item_type,
+                    flow: ItemFlow::Consumed,
+                    amount: 1,
+                });
+            }
+        }
+    }
+}
+
+// items go with their belt when it is demolished, replaced or cleared away
+pub fn despawn_lost_items(
+    mut commands: Commands,
+    items: Query<(Entity, &Item)>,
+    belts: Query<(), With<Belt>>,
+) {
+    for (entity, item) in items.iter() {
+        if !belts.contains(item.belt) {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+// items created out of nothing, onto the belt or into the inventory of the building at the tile
+#[derive(Event, Clone, Copy, Debug)]
+pub struct SpawnItemEvent {
+    pub item_type: ItemType,
+    pub tile_pos: TilePos,
+    pub amount: usize,
+}
+
+pub fn spawn_items(
+    mut commands: Commands,
+    mut events: EventReader<SpawnItemEvent>,
+    mut belts: Query<(&mut Belt, &TileTextureIndex)>,
+    building_tiles: Query<&BuildingTile>,
+    mut inventories: Query<&mut Inventory>,
+    building_layer_query: Query<(&TileStorage, &TilemapTileSize, &Transform), With<BuildingLayer>>,
+) {
+    let (building_layer, tile_size, building_layer_transform) = building_layer_query.single();
+
+    for event in events.iter() {
+        let Some(tile) = building_layer.checked_get(&event.tile_pos) else {
+            continue;
+        };
+
+        let mut spawned = 0;
+
+        if let Ok((mut belt, belt_tile)) = belts.get_mut(tile) {
+            let world_pos = tile_to_world_pos(event.tile_pos, tile_size, building_layer_transform);
+
+            // queued up behind the last item, starting at the end of an empty belt
+            while spawned < event.amount {
+                let pos = belt.items.last().map_or(1., |(_, p)| p - ITEM_SIZE);
+                let offset = BuildingTileType::from(*belt_tile).progress_offset(pos);
+
+                let placed = pos >= 0.
+                    && belt.place_new(pos, || {
+                        commands
+                            .spawn(Item {
+                                belt: tile,
+                                item_type: event.item_type,
+                            })
+                            .insert(TransformBundle::from_transform(
+                                Transform::from_translation((world_pos + offset).extend(10.)),
+                            ))
+                            .id()
+                    });
+
+                if !placed {
+                    break;
+                }
+                spawned += 1;
+            }
+        } else if let Some(mut inventory) = building_tiles
+            .get(tile)
+            .ok()
+            .and_then(|building_tile| inventories.get_mut(building_tile.building).ok())
+        {
+            while spawned < event.amount && inventory.insert(1, event.item_type) {
+                spawned += 1;
+            }
+        }
+
+        if spawned < event.amount {
+            warn!(
+                "spawned {} of {} {} at {}, {}",
+                spawned,
+                event.amount,
+                event.item_type.as_str(),
+                event.tile_pos.x,
+                event.tile_pos.y
+            );
+        }
+    }
+}
+
+// changes by hand to the inventory of the building at the tile, from its window or an undo
+#[derive(Event, Clone, Debug)]
+pub enum InventoryEvent {
+    // into the hand of the player, who already holds them by the time this lands
+    Take {
+        tile_pos: TilePos,
+        slot: usize,
+        amount: usize,
+    },
+    ClearSlot {
+        tile_pos: TilePos,
+        slot: usize,
+    },
+    Clear {
+        tile_pos: TilePos,
+    },
+    // the contents of a demolished building, put back when it is rebuilt
+    Restore {
+        tile_pos: TilePos,
+        slots: Vec<Option<(ItemType, usize)>>,
+    },
+}
+
+pub fn change_inventories(
+    mut events: EventReader<InventoryEvent>,
+    building_tiles: Query<&BuildingTile>,
+    mut inventories: Query<&mut Inventory>,
+    building_layer_query: Query<&TileStorage, With<BuildingLayer>>,
+) {
+    let building_layer = building_layer_query.single();
+
+    for event in events.iter() {
+        let (InventoryEvent::Take { tile_pos, .. }
+        | InventoryEvent::ClearSlot { tile_pos, .. }
+        | InventoryEvent::Clear { tile_pos }
+        | InventoryEvent::Restore { tile_pos, .. }) = event;
+
+        let Some(mut inventory) = building_at(tile_pos, building_layer, &building_tiles)
+            .and_then(|building| inventories.get_mut(building).ok())
+        else {
+            continue;
+        };
+
+        match event {
+            InventoryEvent::Take { slot, amount, .. } => {
+                inventory.take(*slot, *amount);
+            }
+            InventoryEvent::ClearSlot { slot, .. } => {
+                inventory.clear_slot(*slot);
+            }
+            InventoryEvent::Clear { .. } => inventory.clear(),
+            // a building that wasn't rebuilt leaves the items of whatever stands there alone
+            InventoryEvent::Restore { slots, .. } => {
+                if inventory.slots.iter().all(Option::is_none) {
+                    for (slot, restored) in inventory.slots.iter_mut().zip(slots) {
+                        *slot = *restored;
+                    }
+                }
             }
         }
     }
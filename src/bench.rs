@@ -0,0 +1,318 @@
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use bevy::ecs::system::BoxedSystem;
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::*;
+
+use crate::belts::{input_from_belts, move_items_on_belts, Belt, Item};
+use crate::buildings::guide::{update_build_guide, update_demo_guide};
+use crate::buildings::mine::{mine_produce, Mine};
+use crate::buildings::templates::BuildingRegistry;
+use crate::buildings::{BuildRequestedEvent, BuildTool, BuildingType, Tool};
+use crate::direction::MapDirection;
+use crate::input::{AreaSelection, GameCursor};
+use crate::map::{BuildGuideLayer, TILEMAP_SIZE};
+use crate::simulation::{Simulation, SimulationPlugin};
+use crate::statistics::ItemFlowEvent;
+use crate::ui::MapInteraction;
+
+pub const BENCHMARK_TICKS: u32 = 3600;
+
+// synthetic factories, built headlessly and as large as the map allows
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Scenario {
+    // one belt winding over most of the map, fed by a row of mines at the top
+    Serpentine,
+    // hundreds of mines, each feeding its own chest over a single belt
+    Mines,
+    // the serpentine while the cursor sweeps over it with the build tool and the bulldozer
+    BuildGuide,
+}
+
+impl Scenario {
+    pub const ALL: [Scenario; 3] = [Scenario::Serpentine, Scenario::Mines, Scenario::BuildGuide];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Scenario::Serpentine => "serpentine",
+            Scenario::Mines => "mines",
+            Scenario::BuildGuide => "guide",
+        }
+    }
+
+    fn requests(&self) -> Vec<BuildRequestedEvent> {
+        match self {
+            Scenario::Serpentine | Scenario::BuildGuide => serpentine(),
+            Scenario::Mines => mines(),
+        }
+    }
+
+    // the systems measured, tick systems in the order of the SimulationTick schedule
+    fn systems(&self) -> Vec<TimedSystem> {
+        match self {
+            Scenario::Serpentine | Scenario::Mines => vec![
+                TimedSystem::new("mine_produce", mine_produce),
+                TimedSystem::new("move_items_on_belts", move_items_on_belts),
+                TimedSystem::new("input_from_belts", input_from_belts),
+            ],
+            Scenario::BuildGuide => vec![
+                TimedSystem::new("update_build_guide", update_build_guide),
+                TimedSystem::new("update_demo_guide", update_demo_guide),
+            ],
+        }
+    }
+}
+
+pub struct UnknownScenario;
+
+impl FromStr for Scenario {
+    type Err = UnknownScenario;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Scenario::ALL
+            .into_iter()
+            .find(|scenario| scenario.as_str() == s)
+            .ok_or(UnknownScenario)
+    }
+}
+
+fn belt(x: u32, y: u32, direction: MapDirection) -> BuildRequestedEvent {
+    BuildRequestedEvent {
+        building_type: BuildingType::Belt,
+        direction,
+        tile_pos: TilePos::new(x, y),
+    }
+}
+
+fn building(building_type: BuildingType, x: u32, y: u32) -> BuildRequestedEvent {
+    BuildRequestedEvent {
+        building_type,
+        direction: MapDirection::Left,
+        tile_pos: TilePos::new(x, y),
+    }
+}
+
+// rows of belts going right and left in turns, from the mines at the top down into a chest at
+// the bottom left corner
+fn serpentine() -> Vec<BuildRequestedEvent> {
+    const ROWS: u32 = TILEMAP_SIZE.y - 4;
+    let last = TILEMAP_SIZE.x - 1;
+
+    let mut requests = vec![building(BuildingType::Chest, 0, 0)];
+
+    for y in 0..ROWS {
+        let (direction, row_end) = match y % 2 {
+            0 => (MapDirection::Left, 1),
+            _ => (MapDirection::Right, last),
+        };
+
+        for x in 1..=last {
+            let direction = match x == row_end && y > 0 {
+                true => MapDirection::Down,
+                false => direction,
+            };
+
+            requests.push(belt(x, y, direction));
+        }
+    }
+
+    // the tile left of each mine is another mine, so they all output down onto the top row
+    for x in (1..last).step_by(2) {
+        requests.push(building(BuildingType::Mine, x, ROWS));
+    }
+
+    requests
+}
+
+// chest, belt and mine side by side, repeated over the whole map
+fn mines() -> Vec<BuildRequestedEvent> {
+    let mut requests = Vec::new();
+
+    for y in (0..TILEMAP_SIZE.y).step_by(2) {
+        for x in (0..TILEMAP_SIZE.x).step_by(4) {
+            requests.push(building(BuildingType::Chest, x, y));
+            requests.push(belt(x + 1, y, MapDirection::Left));
+            requests.push(building(BuildingType::Mine, x + 2, y));
+        }
+    }
+
+    requests
+}
+
+struct TimedSystem {
+    name: &'static str,
+    system: BoxedSystem,
+    runs: Vec<Duration>,
+}
+
+impl TimedSystem {
+    fn new<M>(name: &'static str, system: impl IntoSystem<(), (), M>) -> Self {
+        TimedSystem {
+            name,
+            system: Box::new(IntoSystem::into_system(system)),
+            runs: Vec::new(),
+        }
+    }
+
+    // commands are applied right away and count towards the system
+    fn run(&mut self, world: &mut World) {
+        let start = Instant::now();
+        self.system.run((), world);
+        self.system.apply_deferred(world);
+        self.runs.push(start.elapsed());
+    }
+
+    fn timing(&self) -> SystemTiming {
+        let total: Duration = self.runs.iter().sum();
+
+        SystemTiming {
+            name: self.name,
+            total,
+            mean: total / self.runs.len().max(1) as u32,
+            max: self.runs.iter().max().copied().unwrap_or_default(),
+        }
+    }
+}
+
+pub struct SystemTiming {
+    pub name: &'static str,
+    pub total: Duration,
+    pub mean: Duration,
+    pub max: Duration,
+}
+
+pub struct BenchmarkReport {
+    pub scenario: Scenario,
+    pub ticks: u32,
+    pub belts: usize,
+    pub mines: usize,
+    pub items: usize,
+    pub elapsed: Duration,
+    pub systems: Vec<SystemTiming>,
+}
+
+impl fmt::Display for BenchmarkReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{}: {} belts, {} mines, {} ticks in {:.2?}, {} items left on the map",
+            self.scenario.as_str(),
+            self.belts,
+            self.mines,
+            self.ticks,
+            self.elapsed,
+            self.items
+        )?;
+        writeln!(
+            f,
+            "  {:<24}{:>12}{:>12}{:>12}",
+            "system", "total", "mean", "max"
+        )?;
+
+        for timing in &self.systems {
+            writeln!(
+                f,
+                "  {:<24}{:>12}{:>12}{:>12}",
+                timing.name,
+                format!("{:.2?}", timing.total),
+                format!("{:.2?}", timing.mean),
+                format!("{:.2?}", timing.max)
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+// the factory is built through the simulation, like a player would, and left paused
+fn build_factory(scenario: Scenario, registry: BuildingRegistry) -> App {
+    let mut app = App::new();
+
+    app.add_plugins((MinimalPlugins, SimulationPlugin))
+        .insert_resource(registry);
+
+    app.update();
+    app.world.resource_mut::<Simulation>().paused = true;
+
+    for request in scenario.requests() {
+        app.world.send_event(request);
+    }
+
+    app.update();
+    app
+}
+
+// the cursor visits every tile in turn, the bulldozer drags from the corner to the cursor
+fn sweep_cursor(world: &mut World, iteration: u32) {
+    let tile = iteration % (TILEMAP_SIZE.x * TILEMAP_SIZE.y);
+    let tile_pos = TilePos::new(tile % TILEMAP_SIZE.x, tile / TILEMAP_SIZE.x);
+
+    world.resource_mut::<GameCursor>().tile_pos = Some(tile_pos);
+}
+
+pub fn run_benchmark(
+    scenario: Scenario,
+    registry: BuildingRegistry,
+    ticks: u32,
+) -> BenchmarkReport {
+    let mut app = build_factory(scenario, registry);
+    let world = &mut app.world;
+
+    if let Scenario::BuildGuide = scenario {
+        world.init_resource::<GameCursor>();
+        world.insert_resource(AreaSelection {
+            start: Some(TilePos::new(0, 0)),
+            ..default()
+        });
+        world.insert_resource(MapInteraction(true));
+        world.insert_resource(Tool::None);
+        world.spawn((TileStorage::empty(TILEMAP_SIZE), BuildGuideLayer));
+    }
+
+    let mut systems = scenario.systems();
+
+    for system in systems.iter_mut() {
+        system.system.initialize(world);
+    }
+
+    let start = Instant::now();
+
+    for iteration in 0..ticks {
+        match scenario {
+            Scenario::BuildGuide => {
+                sweep_cursor(world, iteration);
+
+                *world.resource_mut::<Tool>() = Tool::Build(BuildTool {
+                    building: BuildingType::Mine,
+                    direction: MapDirection::Left,
+                });
+                systems[0].run(world);
+
+                *world.resource_mut::<Tool>() = Tool::Buldozer;
+                systems[1].run(world);
+            }
+            _ => {
+                for system in systems.iter_mut() {
+                    system.run(world);
+                }
+
+                // there are no frames to clear old events
+                world.resource_mut::<Events<ItemFlowEvent>>().update();
+            }
+        }
+    }
+
+    let elapsed = start.elapsed();
+
+    BenchmarkReport {
+        scenario,
+        ticks,
+        belts: world.query::<&Belt>().iter(world).count(),
+        mines: world.query::<&Mine>().iter(world).count(),
+        items: world.query::<&Item>().iter(world).count(),
+        elapsed,
+        systems: systems.iter().map(TimedSystem::timing).collect(),
+    }
+}
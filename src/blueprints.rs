@@ -0,0 +1,349 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::build_mode::BuildMode;
+use crate::buildings::templates::BuildingRegistry;
+use crate::buildings::{
+    building_at, BuildRequestedEvent, Building, BuildingTile, BuildingType, Tool,
+};
+use crate::direction::MapDirection;
+use crate::input::{Action, ActionInput};
+use crate::map::{BuildingLayer, BuildingTileType, TileArea, TILEMAP_SIZE};
+
+pub mod encoding;
+
+const LIBRARY_PATH: &str = "blueprints";
+
+pub struct BlueprintPlugin;
+
+impl Plugin for BlueprintPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<BlueprintLibrary>()
+            .add_event::<CopyAreaEvent>()
+            .add_systems(Startup, load_blueprint_library)
+            .add_systems(
+                Update,
+                (
+                    copy_area,
+                    handle_blueprint_library,
+                    handle_blueprint_clipboard,
+                )
+                    .run_if(in_state(BuildMode::Enabled)),
+            );
+    }
+}
+
+#[derive(Event)]
+pub struct CopyAreaEvent {
+    pub area: TileArea,
+}
+
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Blueprint {
+    pub name: String,
+    pub entries: Vec<BlueprintEntry>,
+}
+
+// position and size of the building footprint, relative to the bottom left corner of the blueprint
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlueprintEntry {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub building_type: BuildingType,
+    pub direction: MapDirection,
+    pub tile_type: Option<BuildingTileType>,
+}
+
+impl BlueprintEntry {
+    fn direction(&self) -> MapDirection {
+        self.tile_type
+            .and_then(|t| t.belt_direction())
+            .unwrap_or(self.direction)
+    }
+}
+
+impl Blueprint {
+    // buildings that would stick out of the map are left out
+    pub fn requests_at(&self, origin: TilePos) -> impl Iterator<Item = BuildRequestedEvent> + '_ {
+        self.entries.iter().filter_map(move |entry| {
+            let x = origin.x.checked_add(entry.x)?;
+            let y = origin.y.checked_add(entry.y)?;
+
+            if x.checked_add(entry.width)? > TILEMAP_SIZE.x
+                || y.checked_add(entry.height)? > TILEMAP_SIZE.y
+            {
+                return None;
+            }
+
+            Some(BuildRequestedEvent {
+                building_type: entry.building_type,
+                direction: entry.direction(),
+                tile_pos: TilePos::new(x, y),
+            })
+        })
+    }
+
+    // turns left like the build tool, templates are anchored at the bottom left corner
+    // so the footprint is rotated and its new corner becomes the origin
+    pub fn rotate(&mut self) {
+        let rotated: Vec<_> = self
+            .entries
+            .iter()
+            .map(|entry| {
+                let x = -(entry.y as i64 + entry.height as i64 - 1);
+                let y = entry.x as i64;
+                (x, y)
+            })
+            .collect();
+
+        let min_x = rotated.iter().map(|(x, _)| *x).min().unwrap_or(0);
+        let min_y = rotated.iter().map(|(_, y)| *y).min().unwrap_or(0);
+
+        for (entry, (x, y)) in self.entries.iter_mut().zip(rotated) {
+            entry.x = (x - min_x) as u32;
+            entry.y = (y - min_y) as u32;
+            std::mem::swap(&mut entry.width, &mut entry.height);
+            entry.direction.turn_left();
+
+            if let Some(mut direction) = entry.tile_type.and_then(|t| t.belt_direction()) {
+                direction.turn_left();
+                entry.tile_type = Some(BuildingTileType::belt(direction));
+            }
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct BlueprintLibrary {
+    pub blueprints: Vec<Blueprint>,
+    selected: usize,
+}
+
+impl BlueprintLibrary {
+    fn path(name: &str) -> PathBuf {
+        Path::new(LIBRARY_PATH).join(name).with_extension("ron")
+    }
+
+    pub fn save(&mut self, mut blueprint: Blueprint) -> anyhow::Result<()> {
+        let number = (1..)
+            .find(|n| {
+                !self
+                    .blueprints
+                    .iter()
+                    .any(|b| b.name == format!("blueprint-{n}"))
+            })
+            .unwrap();
+        blueprint.name = format!("blueprint-{number}");
+
+        let contents = ron::ser::to_string_pretty(&blueprint, Default::default())?;
+        fs::create_dir_all(LIBRARY_PATH)?;
+        fs::write(Self::path(&blueprint.name), contents)?;
+
+        self.blueprints.push(blueprint);
+        Ok(())
+    }
+
+    pub fn load() -> anyhow::Result<Self> {
+        let mut blueprints = Vec::new();
+
+        let Ok(dir) = fs::read_dir(LIBRARY_PATH) else {
+            return Ok(BlueprintLibrary::default());
+        };
+
+        for entry in dir {
+            let path = entry?.path();
+
+            if path.extension().is_some_and(|ext| ext == "ron") {
+                let blueprint: Blueprint = ron::from_str(&fs::read_to_string(&path)?)
+                    .map_err(|e| anyhow::anyhow!("{}: {e}", path.display()))?;
+                blueprints.push(blueprint);
+            }
+        }
+
+        blueprints.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(BlueprintLibrary {
+            blueprints,
+            selected: 0,
+        })
+    }
+
+    fn next(&mut self) -> Option<&Blueprint> {
+        if self.blueprints.is_empty() {
+            return None;
+        }
+
+        let blueprint = &self.blueprints[self.selected % self.blueprints.len()];
+        self.selected = (self.selected + 1) % self.blueprints.len();
+        Some(blueprint)
+    }
+}
+
+pub fn load_blueprint_library(mut library: ResMut<BlueprintLibrary>) {
+    match BlueprintLibrary::load() {
+        Ok(loaded) => *library = loaded,
+        Err(e) => error!("failed to load blueprints: {e}"),
+    }
+}
+
+pub fn copy_area(
+    mut copy_events: EventReader<CopyAreaEvent>,
+    mut selected_tool: ResMut<Tool>,
+    buildings: Query<(&Building, &BuildingType, &TilePos, &MapDirection)>,
+    building_tiles: Query<&BuildingTile>,
+    tile_textures: Query<&TileTextureIndex>,
+    building_layer: Query<&TileStorage, With<BuildingLayer>>,
+) {
+    let Ok(building_layer) = building_layer.get_single() else {
+        return;
+    };
+
+    for event in copy_events.iter() {
+        let mut copied: Vec<Entity> = event
+            .area
+            .iter()
+            .filter_map(|tile_pos| building_at(&tile_pos, building_layer, &building_tiles))
+            .collect();
+        copied.sort();
+        copied.dedup();
+
+        // origins in map coordinates
+        let mut footprints = Vec::new();
+
+        for (building, building_type, origin, direction) in
+            copied.iter().filter_map(|e| buildings.get(*e).ok())
+        {
+            let mut min = *origin;
+            let mut max = *origin;
+
+            for (_, tile_pos, _) in building.layout.tiles.iter() {
+                min = TilePos::new(min.x.min(tile_pos.x), min.y.min(tile_pos.y));
+                max = TilePos::new(max.x.max(tile_pos.x), max.y.max(tile_pos.y));
+            }
+
+            // belts can be redirected after they are built, so keep their current tile
+            let tile_type = building
+                .layout
+                .tiles
+                .first()
+                .and_then(|(e, _, _)| tile_textures.get(*e).ok())
+                .map(|texture| BuildingTileType::from(*texture))
+                .filter(|t| t.is_belt());
+
+            footprints.push((
+                *origin,
+                BlueprintEntry {
+                    x: 0,
+                    y: 0,
+                    width: max.x - min.x + 1,
+                    height: max.y - min.y + 1,
+                    building_type: *building_type,
+                    direction: *direction,
+                    tile_type,
+                },
+            ));
+        }
+
+        let (Some(min_x), Some(min_y)) = (
+            footprints.iter().map(|(origin, _)| origin.x).min(),
+            footprints.iter().map(|(origin, _)| origin.y).min(),
+        ) else {
+            continue;
+        };
+
+        let entries = footprints
+            .into_iter()
+            .map(|(origin, entry)| BlueprintEntry {
+                x: origin.x - min_x,
+                y: origin.y - min_y,
+                ..entry
+            })
+            .collect();
+
+        *selected_tool = Tool::Blueprint(Blueprint {
+            name: String::from("blueprint"),
+            entries,
+        });
+    }
+}
+
+pub fn handle_blueprint_library(
+    input: ActionInput,
+    mut selected_tool: ResMut<Tool>,
+    mut library: ResMut<BlueprintLibrary>,
+) {
+    if input.just_pressed(Action::SaveBlueprint)
+        && let Tool::Blueprint(blueprint) = &*selected_tool
+        && let Err(e) = library.save(blueprint.clone())
+    {
+        error!("failed to save blueprint: {e}");
+    }
+
+    if input.just_pressed(Action::LoadBlueprint)
+        && let Some(blueprint) = library.next()
+    {
+        *selected_tool = Tool::Blueprint(blueprint.clone());
+    }
+}
+
+// blueprints are shared as text strings through the clipboard
+pub fn handle_blueprint_clipboard(
+    input: ActionInput,
+    registry: Res<BuildingRegistry>,
+    mut selected_tool: ResMut<Tool>,
+    mut clipboard: Local<Option<arboard::Clipboard>>,
+) {
+    let export = input.just_pressed(Action::ExportBlueprint);
+    let import = input.just_pressed(Action::ImportBlueprint);
+
+    if !export && !import {
+        return;
+    }
+
+    // keep the clipboard alive, on some platforms its contents are dropped with it
+    if clipboard.is_none() {
+        match arboard::Clipboard::new() {
+            Ok(c) => *clipboard = Some(c),
+            Err(e) => error!("clipboard is not available: {e}"),
+        }
+    }
+
+    if export && let Tool::Blueprint(blueprint) = &*selected_tool {
+        match encoding::encode(blueprint) {
+            Ok(blueprint_string) => {
+                info!(
+                    "exported blueprint {}: {}",
+                    blueprint.name, blueprint_string
+                );
+
+                if let Some(clipboard) = clipboard.as_mut()
+                    && let Err(e) = clipboard.set_text(blueprint_string)
+                {
+                    error!("failed to copy blueprint: {e}");
+                }
+            }
+            Err(e) => error!("failed to export blueprint: {e}"),
+        }
+    }
+
+    if import && let Some(clipboard) = clipboard.as_mut() {
+        let blueprint_string = match clipboard.get_text() {
+            Ok(text) => text,
+            Err(e) => {
+                error!("failed to read clipboard: {e}");
+                return;
+            }
+        };
+
+        match encoding::decode(&blueprint_string, &registry) {
+            Ok(blueprint) => *selected_tool = Tool::Blueprint(blueprint),
+            Err(e) => error!("failed to import blueprint: {e}"),
+        }
+    }
+}
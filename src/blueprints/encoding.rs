@@ -0,0 +1,181 @@
+use std::fmt;
+use std::io::{Read, Write};
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+
+use super::{Blueprint, BlueprintEntry};
+use crate::buildings::templates::BuildingRegistry;
+use crate::buildings::BuildingType;
+use crate::direction::MapDirection;
+use crate::map::{BuildingTileType, TILEMAP_SIZE};
+
+// first character of every blueprint string, bump when the format changes
+pub const BLUEPRINT_VERSION: char = '1';
+// a pasted string inflates to at most this much, anything bigger isn't a blueprint
+pub const MAX_BLUEPRINT_BYTES: u64 = 1 << 20;
+
+#[derive(Debug)]
+pub enum BlueprintDecodeError {
+    Empty,
+    UnsupportedVersion(char),
+    Corrupt(String),
+    NoBuildings,
+    UnknownBuilding(String),
+    UnregisteredBuilding(BuildingType),
+}
+
+impl fmt::Display for BlueprintDecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => write!(f, "blueprint string is empty"),
+            Self::UnsupportedVersion(version) => write!(
+                f,
+                "unsupported blueprint version '{version}', expected '{BLUEPRINT_VERSION}'"
+            ),
+            Self::Corrupt(reason) => write!(f, "blueprint string is corrupt: {reason}"),
+            Self::NoBuildings => write!(f, "blueprint contains no buildings"),
+            Self::UnknownBuilding(name) => write!(f, "unknown building '{name}' in blueprint"),
+            Self::UnregisteredBuilding(building_type) => write!(
+                f,
+                "building '{}' in blueprint is not loaded",
+                building_type.as_str()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for BlueprintDecodeError {}
+
+// buildings are stored by name, so unknown ones can be reported instead of failing to parse
+#[derive(Serialize, Deserialize)]
+struct EncodedBlueprint {
+    name: String,
+    entries: Vec<EncodedEntry>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct EncodedEntry {
+    building: String,
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+    direction: MapDirection,
+    tile_type: Option<BuildingTileType>,
+}
+
+pub fn encode(blueprint: &Blueprint) -> anyhow::Result<String> {
+    let encoded = EncodedBlueprint {
+        name: blueprint.name.clone(),
+        entries: blueprint
+            .entries
+            .iter()
+            .map(|entry| EncodedEntry {
+                building: entry.building_type.as_str().to_string(),
+                x: entry.x,
+                y: entry.y,
+                width: entry.width,
+                height: entry.height,
+                direction: entry.direction,
+                tile_type: entry.tile_type,
+            })
+            .collect(),
+    };
+
+    let mut encoder = DeflateEncoder::new(Vec::new(), Compression::best());
+    encoder.write_all(ron::to_string(&encoded)?.as_bytes())?;
+    let compressed = encoder.finish()?;
+
+    Ok(format!("{BLUEPRINT_VERSION}{}", BASE64.encode(compressed)))
+}
+
+pub fn decode(
+    blueprint_string: &str,
+    registry: &BuildingRegistry,
+) -> Result<Blueprint, BlueprintDecodeError> {
+    let blueprint_string = blueprint_string.trim();
+
+    let mut chars = blueprint_string.chars();
+    let version = chars.next().ok_or(BlueprintDecodeError::Empty)?;
+
+    if version != BLUEPRINT_VERSION {
+        return Err(BlueprintDecodeError::UnsupportedVersion(version));
+    }
+
+    let compressed = BASE64
+        .decode(chars.as_str())
+        .map_err(|e| BlueprintDecodeError::Corrupt(e.to_string()))?;
+
+    // one byte more than allowed tells a full blueprint from one that was cut off
+    let mut contents = String::new();
+    DeflateDecoder::new(compressed.as_slice())
+        .take(MAX_BLUEPRINT_BYTES + 1)
+        .read_to_string(&mut contents)
+        .map_err(|e| BlueprintDecodeError::Corrupt(e.to_string()))?;
+
+    if contents.len() as u64 > MAX_BLUEPRINT_BYTES {
+        return Err(BlueprintDecodeError::Corrupt(format!(
+            "blueprint is larger than {MAX_BLUEPRINT_BYTES} bytes"
+        )));
+    }
+
+    let encoded: EncodedBlueprint =
+        ron::from_str(&contents).map_err(|e| BlueprintDecodeError::Corrupt(e.to_string()))?;
+
+    if encoded.entries.is_empty() {
+        return Err(BlueprintDecodeError::NoBuildings);
+    }
+
+    let mut entries = Vec::with_capacity(encoded.entries.len());
+
+    for entry in encoded.entries {
+        let building_type: BuildingType = entry
+            .building
+            .parse()
+            .map_err(|_| BlueprintDecodeError::UnknownBuilding(entry.building.clone()))?;
+
+        if !registry.contains(building_type) {
+            return Err(BlueprintDecodeError::UnregisteredBuilding(building_type));
+        }
+
+        if entry.width == 0 || entry.height == 0 {
+            return Err(BlueprintDecodeError::Corrupt(format!(
+                "{} has an empty footprint",
+                entry.building
+            )));
+        }
+
+        let fits = |start: u32, size: u32, map_size: u32| {
+            start.checked_add(size).is_some_and(|end| end <= map_size)
+        };
+
+        if !fits(entry.x, entry.width, TILEMAP_SIZE.x)
+            || !fits(entry.y, entry.height, TILEMAP_SIZE.y)
+        {
+            return Err(BlueprintDecodeError::Corrupt(format!(
+                "{} at {}, {} lies outside the map",
+                entry.building, entry.x, entry.y
+            )));
+        }
+
+        entries.push(BlueprintEntry {
+            x: entry.x,
+            y: entry.y,
+            width: entry.width,
+            height: entry.height,
+            building_type,
+            direction: entry.direction,
+            tile_type: entry.tile_type,
+        });
+    }
+
+    Ok(Blueprint {
+        name: encoded.name,
+        entries,
+    })
+}
@@ -1,21 +1,28 @@
 use arrayvec::ArrayVec;
 use bevy::prelude::*;
 use bevy_ecs_tilemap::prelude::*;
+use serde::{Deserialize, Serialize};
 
-use self::templates::{BuildingTemplate, BuildingRegistry, PlacedBuildingTemplate};
+use self::templates::{BuildingRegistry, PlacedBuildingTemplate};
+use crate::belts::Inventory;
+use crate::blueprints::Blueprint;
 use crate::direction::MapDirection;
-use crate::map::{BuildingLayer, BuildingTileType};
+use crate::map::{BuildingLayer, BuildingTileType, Terrain, TileArea};
+use crate::mods::registry::ModName;
 
+pub mod alerts;
 pub mod chest;
 pub mod guide;
 pub mod mine;
+pub mod status;
 pub mod templates;
 
-#[derive(Component, Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[derive(Component, Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum BuildingType {
     Belt,
     Mine,
     Chest,
+    Mod(ModName),
 }
 
 #[derive(Component)]
@@ -23,26 +30,22 @@ pub struct Building {
     pub layout: BuildingLayout,
 }
 
-impl BuildingLayout {
-    pub fn contains(&self, tile: &TilePos) -> bool {
-        self.tiles.iter().any(|(_, pos, _)| pos == tile)
-    }
-}
-
 #[derive(Component)]
 pub struct BuildingTile {
     pub building: Entity,
 }
 
-#[derive(Resource, Default, Clone, PartialEq, Eq)]
+#[derive(Resource, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Tool {
     #[default]
     None,
     Build(BuildTool),
     Buldozer,
+    Copy,
+    Blueprint(Blueprint),
 }
 
-#[derive(Clone, PartialEq, Eq)]
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct BuildTool {
     pub building: BuildingType,
     pub direction: MapDirection,
@@ -62,6 +65,7 @@ impl Tool {
     pub fn rotate(&mut self) {
         match self {
             Tool::Build(BuildTool { direction, .. }) => direction.turn_left(),
+            Tool::Blueprint(blueprint) => blueprint.rotate(),
             _ => {}
         }
     }
@@ -82,18 +86,18 @@ pub struct BuildingLayout {
 }
 
 #[derive(Bundle)]
-struct BuildingBundle {
-    building_type: BuildingType,
-    origin: TilePos,
-    template: Handle<BuildingTemplate>,
-    direction: MapDirection,
+pub struct BuildingBundle {
+    pub building_type: BuildingType,
+    pub origin: TilePos,
+    pub direction: MapDirection,
 }
 
 pub fn build_building(
     mut commands: Commands,
     mut request_events: EventReader<BuildRequestedEvent>,
-    template_handles: Res<BuildingRegistry>,
-    templates: Res<Assets<BuildingTemplate>>,
+    mut changed_events: EventWriter<BuildingChangedEvent>,
+    templates: Res<BuildingRegistry>,
+    terrain: Res<Terrain>,
     mut building_layer: Query<&mut TileStorage, With<BuildingLayer>>,
 ) {
     if request_events.is_empty() {
@@ -102,19 +106,36 @@ pub fn build_building(
 
     let building_layer = building_layer.single_mut();
 
+    // tiles of buildings requested this frame, they are not in the building layer yet
+    let mut reserved = Vec::new();
+
     for event in request_events.iter() {
-        let template_handle = template_handles.get(event.building_type);
+        // a mod building from a mod that isn't loaded, or whose base has no template yet
+        if !templates.contains(event.building_type) {
+            warn!("unknown building {:?}", event.building_type);
+            continue;
+        }
 
         let template = templates
-            .get(&template_handle)
-            .unwrap()
+            .get(event.building_type)
             .place(event.tile_pos, event.direction);
 
-        if is_posible_to_build(&template, &building_layer) {
+        let is_reserved = template
+            .instructions()
+            .any(|(tile_pos, _)| reserved.contains(&tile_pos));
+
+        if !is_reserved && is_posible_to_build(&template, &building_layer, &terrain) {
+            reserved.extend(template.instructions().map(|(tile_pos, _)| tile_pos));
+
             commands.spawn(BuildingBundle {
                 building_type: event.building_type,
                 origin: event.tile_pos,
-                template: template_handle,
+                direction: event.direction,
+            });
+
+            changed_events.send(BuildingChangedEvent::Built {
+                building_type: event.building_type,
+                origin: event.tile_pos,
                 direction: event.direction,
             });
         }
@@ -124,20 +145,24 @@ pub fn build_building(
 pub fn construct_building(
     mut commands: Commands,
     mut building_layer: Query<(Entity, &mut TileStorage), With<BuildingLayer>>,
+    // the type is also marked as changed when its template is reloaded
     changed_buildings: Query<
         (
+            Entity,
             &TilePos,
             &MapDirection,
-            &Handle<BuildingTemplate>,
+            &BuildingType,
             Option<&Building>,
         ),
-        Changed<Handle<BuildingTemplate>>,
+        Changed<BuildingType>,
     >,
-    templates: Res<Assets<BuildingTemplate>>,
+    templates: Res<BuildingRegistry>,
 ) {
     let (building_layer_entity, mut building_layer) = building_layer.single_mut();
 
-    for (origin_pos, direction, template_handle, building) in changed_buildings.iter() {
+    for (building_entity, origin_pos, direction, building_type, building) in
+        changed_buildings.iter()
+    {
         // despawn tiles of previous building if it exists
         if let Some(Building { layout }) = building {
             for (entity, tile_pos, _) in &layout.tiles {
@@ -146,12 +171,8 @@ pub fn construct_building(
             }
         }
 
-        let template = templates
-            .get(template_handle)
-            .unwrap()
-            .place(*origin_pos, *direction);
+        let template = templates.get(*building_type).place(*origin_pos, *direction);
 
-        let building_entity = commands.spawn_empty().id();
         let mut tiles = ArrayVec::new();
 
         for (tile_pos, tile_type) in template.instructions() {
@@ -182,38 +203,149 @@ pub struct DemolishEvent {
     pub tile_pos: TilePos,
 }
 
+// sent for every change made to the buildings on the map
+#[derive(Event, Clone)]
+pub enum BuildingChangedEvent {
+    Built {
+        building_type: BuildingType,
+        origin: TilePos,
+        direction: MapDirection,
+    },
+    Demolished {
+        building_type: BuildingType,
+        origin: TilePos,
+        direction: MapDirection,
+        inventory: Option<Box<Inventory>>,
+    },
+    Redirected {
+        tile_pos: TilePos,
+        from: MapDirection,
+        to: MapDirection,
+    },
+}
+
+#[derive(Event)]
+pub struct DemolishAreaEvent {
+    pub area: TileArea,
+    pub filter: DemolishFilter,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DemolishFilter {
+    #[default]
+    All,
+    BeltsOnly,
+    BuildingsOnly,
+}
+
+impl DemolishFilter {
+    pub fn matches(&self, building_type: BuildingType) -> bool {
+        match self {
+            DemolishFilter::All => true,
+            DemolishFilter::BeltsOnly => building_type == BuildingType::Belt,
+            DemolishFilter::BuildingsOnly => building_type != BuildingType::Belt,
+        }
+    }
+}
+
+#[derive(Event)]
+pub struct SelectEvent {
+    pub tile_pos: TilePos,
+}
+
+pub fn building_at(
+    tile_pos: &TilePos,
+    building_layer: &TileStorage,
+    building_tiles: &Query<&BuildingTile>,
+) -> Option<Entity> {
+    building_layer
+        .checked_get(tile_pos)
+        .and_then(|tile| building_tiles.get(tile).ok())
+        .map(|building_tile| building_tile.building)
+}
+
 pub fn demolish_building(
     mut commands: Commands,
     mut events: EventReader<DemolishEvent>,
-    building_query: Query<(Entity, &Building)>,
+    mut area_events: EventReader<DemolishAreaEvent>,
+    mut changed_events: EventWriter<BuildingChangedEvent>,
+    building_query: Query<(
+        &Building,
+        &BuildingType,
+        &TilePos,
+        &MapDirection,
+        Option<&Inventory>,
+    )>,
     building_tile_query: Query<&BuildingTile>,
     mut building_layer_query: Query<&mut TileStorage, With<BuildingLayer>>,
 ) {
     let mut building_layer = building_layer_query.single_mut();
 
-    for event in events.iter() {
-        if let Some(tile_entity) = building_layer.get(&event.tile_pos) {
+    let tiles = events
+        .iter()
+        .map(|e| (e.tile_pos, DemolishFilter::All))
+        .chain(
+            area_events
+                .iter()
+                .flat_map(|e| e.area.iter().map(|tile_pos| (tile_pos, e.filter))),
+        )
+        .collect::<Vec<_>>();
+
+    for (tile_pos, filter) in tiles {
+        if let Some(tile_entity) = building_layer.checked_get(&tile_pos) {
             // TODO maybe handle disconnected entities
-            if let Ok((building_entity, building)) = building_tile_query
+            match building_tile_query
                 .get(tile_entity)
-                .and_then(|bt| building_query.get(bt.building))
+                .and_then(|bt| Ok((bt.building, building_query.get(bt.building)?)))
             {
-                for (e, tile_pos, _) in building.layout.tiles.iter() {
-                    commands.entity(*e).despawn_recursive();
-                    building_layer.checked_remove(tile_pos);
+                Ok((building_entity, (building, building_type, origin, direction, inventory))) => {
+                    if !filter.matches(*building_type) {
+                        continue;
+                    }
+
+                    for (e, tile_pos, _) in building.layout.tiles.iter() {
+                        commands.entity(*e).despawn_recursive();
+                        building_layer.checked_remove(tile_pos);
+                    }
+                    commands.entity(building_entity).despawn();
+
+                    changed_events.send(BuildingChangedEvent::Demolished {
+                        building_type: *building_type,
+                        origin: *origin,
+                        direction: *direction,
+                        inventory: inventory.cloned().map(Box::new),
+                    });
+                }
+                Err(_) if filter == DemolishFilter::All => {
+                    building_layer.checked_remove(&tile_pos);
                 }
-                commands.entity(building_entity).despawn();
-            } else {
-                building_layer.checked_remove(&event.tile_pos);
+                Err(_) => {}
             }
         }
     }
 }
 
-fn is_posible_to_build(template: &PlacedBuildingTemplate, building_layer: &TileStorage) -> bool {
-    template
-        .instructions()
-        .all(|(tile_pos, _)| building_layer.get(&tile_pos).is_none())
+fn is_posible_to_build(
+    template: &PlacedBuildingTemplate,
+    building_layer: &TileStorage,
+    terrain: &Terrain,
+) -> bool {
+    template.instructions().all(|(tile_pos, _)| {
+        tile_pos.within_map_bounds(&building_layer.size)
+            && building_layer.get(&tile_pos).is_none()
+            && terrain.is_buildable(tile_pos)
+    })
+}
+
+impl BuildingType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BuildingType::Belt => "belt",
+            BuildingType::Mine => "mine",
+            BuildingType::Chest => "chest",
+            BuildingType::Mod(name) => name.as_str(),
+        }
+    }
 }
 
 pub struct UnknownBuildingType;
@@ -227,7 +359,7 @@ impl std::str::FromStr for BuildingType {
             "belt" => Belt,
             "mine" => Mine,
             "chest" => Chest,
-            _ => return Err(UnknownBuildingType),
+            _ => Mod(ModName::existing(s).ok_or(UnknownBuildingType)?),
         };
 
         Ok(building_type)
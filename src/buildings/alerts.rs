@@ -0,0 +1,59 @@
+use bevy::prelude::*;
+
+use super::BuildingTile;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum AlertKind {
+    OutputBlocked,
+    InventoryFull,
+    DeadEndBelt,
+}
+
+impl AlertKind {
+    pub fn message(&self) -> &'static str {
+        match self {
+            AlertKind::OutputBlocked => "output blocked",
+            AlertKind::InventoryFull => "inventory full",
+            AlertKind::DeadEndBelt => "belt leads nowhere",
+        }
+    }
+}
+
+// components of a building or its tiles that can report the building as stalled
+pub trait BuildingAlert: Component {
+    fn alert(&self) -> Option<AlertKind>;
+}
+
+#[derive(Resource, Default, PartialEq)]
+pub struct ActiveAlerts {
+    pub alerts: Vec<(Entity, AlertKind)>,
+}
+
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CollectAlerts;
+
+#[derive(Resource, Default)]
+pub struct CollectedAlerts(Vec<(Entity, AlertKind)>);
+
+pub fn collect_alerts<T: BuildingAlert>(
+    mut collected: ResMut<CollectedAlerts>,
+    components: Query<(Entity, &T, Option<&BuildingTile>)>,
+) {
+    for (entity, component, building_tile) in components.iter() {
+        if let Some(kind) = component.alert() {
+            let building = building_tile.map_or(entity, |t| t.building);
+            collected.0.push((building, kind));
+        }
+    }
+}
+
+// alerts are collected every frame, the active list is only touched when it differs
+pub fn publish_alerts(mut collected: ResMut<CollectedAlerts>, mut active: ResMut<ActiveAlerts>) {
+    let mut alerts = std::mem::take(&mut collected.0);
+    alerts.sort();
+    alerts.dedup();
+
+    if active.alerts != alerts {
+        active.alerts = alerts;
+    }
+}
@@ -1,26 +1,24 @@
 use bevy::prelude::*;
-use bevy_ecs_tilemap::prelude::*;
 
-use crate::belts::{BeltInput, Inventory};
+use crate::belts::{BeltInput, Inventory, MAX_INVENTORY_SIZE};
 use crate::buildings::{Building, BuildingType};
-use crate::map::BuildingLayer;
+use crate::mods::registry::ModRegistry;
 
 pub fn build_chest(
     mut commands: Commands,
-    new_chests: Query<(Entity, &BuildingType, &TilePos), Added<Building>>,
-    building_layer: Query<&TileStorage, With<BuildingLayer>>,
+    new_chests: Query<(Entity, &BuildingType, &Building), Added<Building>>,
+    mods: Res<ModRegistry>,
 ) {
-    let building_layer = building_layer.single();
+    for (entity, building_type, building) in new_chests.iter() {
+        if let Some(BuildingType::Chest) = mods.base(*building_type) {
+            commands
+                .entity(entity)
+                .insert(Inventory::with_slots(MAX_INVENTORY_SIZE));
 
-    for (entity, building_type, tile_pos) in new_chests.iter() {
-        if let BuildingType::Chest = building_type {
-            if let Some(tile_entity) = building_layer.get(tile_pos) {
+            for (tile_entity, _, _) in building.layout.tiles.iter() {
                 commands
-                    .entity(tile_entity)
-                    .insert(BeltInput { inventory: entity })
-                    .insert(Inventory {
-                        slots: (&[None; 1] as &[_]).try_into().unwrap(),
-                    });
+                    .entity(*tile_entity)
+                    .insert(BeltInput { inventory: entity });
             }
         }
     }
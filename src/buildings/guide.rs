@@ -1,12 +1,14 @@
+use bevy::ecs::system::SystemParam;
 use bevy::prelude::*;
 use bevy_ecs_tilemap::prelude::*;
 
-use super::templates::{BuildingTemplate, BuildingRegistry};
+use super::templates::BuildingRegistry;
 use super::{
-    is_posible_to_build, BuildRequestedEvent, Building, BuildingType, DemolishEvent, Tool, BuildTool,
+    building_at, is_posible_to_build, BuildRequestedEvent, Building, BuildingTile, BuildingType,
+    DemolishAreaEvent, DemolishEvent, Tool, BuildTool,
 };
-use crate::input::GameCursor;
-use crate::map::{BuildingTileType, BuildGuideLayer};
+use crate::input::{AreaSelection, GameCursor};
+use crate::map::{BuildingLayer, BuildingTileType, BuildGuideLayer, Terrain, TileArea};
 use crate::ui::MapInteraction;
 
 #[derive(Component)]
@@ -19,12 +21,13 @@ pub fn update_build_guide(
     selected_tool: Res<Tool>,
     mouse_pos: Res<GameCursor>,
     map_interaction: Res<MapInteraction>,
-    buildings: Res<BuildingRegistry>,
-    templates: Res<Assets<BuildingTemplate>>,
+    templates: Res<BuildingRegistry>,
+    terrain: Res<Terrain>,
     mut guide_tiles: Query<&mut TileStorage, With<BuildGuideLayer>>,
     guide_tilemap: Query<Entity, With<BuildGuideLayer>>,
+    building_layer: Query<&TileStorage, (With<BuildingLayer>, Without<BuildGuideLayer>)>,
 ) {
-    let Ok(mut guide_tiles) = guide_tiles.get_single_mut() else {
+    let (Ok(mut guide_tiles), Ok(building_layer)) = (guide_tiles.get_single_mut(), building_layer.get_single()) else {
         warn!("no building layer");
         return;
     };
@@ -39,18 +42,15 @@ pub fn update_build_guide(
         && let Some(tile_pos) = mouse_pos.tile_pos
         && map_interaction.is_allowed()
     {
-        let template_handle = buildings.get(building);
-
         let template = templates
-            .get(&template_handle)
-            .unwrap()
+            .get(building)
             .place(tile_pos, direction);
 
-        let is_belt_edit = || building == BuildingType::Belt && guide_tiles.checked_get(&tile_pos)
+        let is_belt_edit = || building == BuildingType::Belt && building_layer.checked_get(&tile_pos)
             .and_then(|te| tiles.get(te).ok())
             .map_or(false, |tile| BuildingTileType::from(*tile).is_belt());
 
-        let guide_color = match is_posible_to_build(&template, &guide_tiles) {
+        let guide_color = match is_posible_to_build(&template, building_layer, &terrain) {
             true => Color::rgba(0., 1., 0., 0.75),
             false if is_belt_edit() => Color::rgba(1., 1., 0., 0.75),
             false => Color::rgba(1., 0., 0., 0.75),
@@ -72,19 +72,93 @@ pub fn update_build_guide(
             }
         });       
     }
+
+    if let Tool::Blueprint(blueprint) = &*selected_tool
+        && let Some(cursor_pos) = mouse_pos.tile_pos
+        && map_interaction.is_allowed()
+    {
+        let guide_tilemap_entity = guide_tilemap.single();
+
+        for request in blueprint.requests_at(cursor_pos) {
+            let template = templates
+                .get(request.building_type)
+                .place(request.tile_pos, request.direction);
+
+            let guide_color = match is_posible_to_build(&template, building_layer, &terrain) {
+                true => Color::rgba(0., 1., 0., 0.75),
+                false => Color::rgba(1., 0., 0., 0.75),
+            };
+
+            commands.entity(guide_tilemap_entity).with_children(|parent| {
+                for (tile_pos, building_type) in template.instructions() {
+                    let tile = parent.spawn(TileBundle {
+                        position: tile_pos,
+                        tilemap_id: TilemapId(guide_tilemap_entity),
+                        texture_index: building_type.into(),
+                        color: guide_color.into(),
+                        ..default()
+                    }).insert(BuildGuide).id();
+
+                    guide_tiles.checked_set(&tile_pos, tile);
+                }
+            });
+        }
+    }
+}
+
+// the area the bulldozer or the copy tool is about to select
+#[derive(SystemParam)]
+pub struct AreaTool<'w> {
+    mouse_pos: Res<'w, GameCursor>,
+    selection: Res<'w, AreaSelection>,
+    selected_tool: Res<'w, Tool>,
+    map_interaction: Res<'w, MapInteraction>,
+}
+
+impl AreaTool<'_> {
+    fn area(&self) -> Option<TileArea> {
+        if let Tool::Buldozer | Tool::Copy = *self.selected_tool
+            && let Some(tile_pos) = self.mouse_pos.tile_pos
+            && (self.map_interaction.is_allowed() || self.selection.start.is_some())
+        {
+            Some(self.selection.area(tile_pos))
+        } else {
+            None
+        }
+    }
+
+    fn is_copy(&self) -> bool {
+        matches!(*self.selected_tool, Tool::Copy)
+    }
+}
+
+// the building layer next to the guide layer drawn on top of it
+#[derive(SystemParam)]
+pub struct GuideLayers<'w, 's> {
+    building_layer:
+        Query<'w, 's, &'static TileStorage, (With<BuildingLayer>, Without<BuildGuideLayer>)>,
+    guide_tiles: Query<'w, 's, (Entity, &'static mut TileStorage), With<BuildGuideLayer>>,
+}
+
+impl GuideLayers<'_, '_> {
+    fn get_mut(&mut self) -> Option<(&TileStorage, Entity, Mut<'_, TileStorage>)> {
+        let building_layer = self.building_layer.get_single().ok()?;
+        let (guide_entity, guide_tiles) = self.guide_tiles.get_single_mut().ok()?;
+
+        Some((building_layer, guide_entity, guide_tiles))
+    }
 }
 
 pub fn update_demo_guide(
     mut commands: Commands,
-    mouse_pos: Res<GameCursor>,
-    buildings: Query<&Building>,
-    selected_tool: Res<Tool>,
-    map_interaction: Res<MapInteraction>,
+    area_tool: AreaTool,
+    buildings: Query<(&Building, &BuildingType)>,
+    building_tile_query: Query<&BuildingTile>,
     mut building_tiles: Query<&mut TileColor>,
-    mut guide_tiles: Query<(Entity, &mut TileStorage), With<BuildGuideLayer>>,
+    mut layers: GuideLayers,
     mut highlighted_buildings: Local<Vec<Entity>>,
 ) {
-    let Ok((guide_entity, mut guide_tiles)) = guide_tiles.get_single_mut() else {
+    let Some((building_layer, guide_entity, mut guide_tiles)) = layers.get_mut() else {
         warn!("no building layer");
         return;
     };
@@ -96,44 +170,65 @@ pub fn update_demo_guide(
         }
     }
 
-    if let Tool::Buldozer = *selected_tool
-        && let Some(tile_pos) = mouse_pos.tile_pos
-        && map_interaction.is_allowed()
-    {
+    if let Some(area) = area_tool.area() {
+        let is_copy = area_tool.is_copy();
+
         commands.entity(guide_entity).with_children(|parent| {
-            let entity = parent.spawn(TileBundle {
-                tilemap_id: TilemapId(parent.parent_entity()),
-                texture_index: BuildingTileType::Explosion.into(),
-                position: tile_pos,
-                ..default()
-            }).insert(BuildGuide).id();
-            
-            guide_tiles.checked_set(&tile_pos, entity);
-
-            let Some(building) = buildings.iter().find(|b| b.layout.contains(&tile_pos)) else {
-                return;
-            };
+            for tile_pos in area.iter() {
+                let entity = parent.spawn(TileBundle {
+                    tilemap_id: TilemapId(parent.parent_entity()),
+                    texture_index: BuildingTileType::Explosion.into(),
+                    position: tile_pos,
+                    color: match is_copy {
+                        true => Color::rgba(0., 0.5, 1., 0.75).into(),
+                        false => default(),
+                    },
+                    ..default()
+                }).insert(BuildGuide).id();
+
+                guide_tiles.checked_set(&tile_pos, entity);
+            }
+        });
+
+        if is_copy {
+            return;
+        }
+
+        let mut demolished: Vec<Entity> = area
+            .iter()
+            .filter_map(|tile_pos| building_at(&tile_pos, building_layer, &building_tile_query))
+            .collect();
+        demolished.sort();
+        demolished.dedup();
+
+        // highlight tiles of buildings about to be demolished
+        for (building, building_type) in demolished.iter().filter_map(|e| buildings.get(*e).ok()) {
+            if !area_tool.selection.filter.matches(*building_type) {
+                continue;
+            }
 
-            // highlight tiles of a building about to be demolished
             for (e, _ , _) in building.layout.tiles.iter() {
                 if let Ok(mut tile) = building_tiles.get_mut(*e) {
                     highlighted_buildings.push(*e);
                     *tile = Color::RED.into();
                 }
             }
-
-        });
+        }
     }
 }
 
 pub fn should_update_build_guide(
     mouse_pos: Res<GameCursor>,
+    selection: Res<AreaSelection>,
     selected_tool: Res<Tool>,
     build_events: EventReader<BuildRequestedEvent>,
     demolish_events: EventReader<DemolishEvent>,
+    demolish_area_events: EventReader<DemolishAreaEvent>,
 ) -> bool {
     mouse_pos.is_changed()
+        || selection.is_changed()
         || selected_tool.is_changed()
         || !build_events.is_empty()
         || !demolish_events.is_empty()
+        || !demolish_area_events.is_empty()
 }
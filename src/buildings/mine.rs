@@ -1,28 +1,65 @@
+use std::hash::{Hash, Hasher};
 use std::time::Duration;
 
 use bevy::prelude::*;
 use bevy_ecs_tilemap::prelude::*;
 
+use super::alerts::{AlertKind, BuildingAlert};
+use super::status::BuildingStatus;
 use super::Building;
 use crate::belts::{Belt, Item, ItemType};
 use crate::buildings::BuildingType;
 use crate::map::BuildingLayer;
+use crate::mods::registry::ModRegistry;
+use crate::simulation::Simulation;
+use crate::statistics::{ItemFlow, ItemFlowEvent};
 
-#[derive(Component)]
+#[derive(Component, Debug)]
 pub struct Mine {
+    item_type: ItemType,
     timer: Timer,
     output: TilePos,
+    blocked: bool,
+}
+
+// the whole state of the mine, for comparing factories
+impl Hash for Mine {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.item_type.hash(state);
+        self.timer.elapsed().hash(state);
+        self.output.hash(state);
+        self.blocked.hash(state);
+    }
+}
+
+impl BuildingAlert for Mine {
+    fn alert(&self) -> Option<AlertKind> {
+        self.blocked.then_some(AlertKind::OutputBlocked)
+    }
+}
+
+impl BuildingStatus for Mine {
+    fn status(&self, lines: &mut Vec<String>) {
+        lines.push(format!("progress {:.0}%", self.timer.percent() * 100.));
+
+        if self.blocked {
+            lines.push("output blocked".to_string());
+        }
+    }
 }
 
 pub fn build_mine(
     mut commands: Commands,
     new_buildings: Query<(Entity, &BuildingType, &TilePos), Added<Building>>,
+    mods: Res<ModRegistry>,
 ) {
     for (entity, building_type, tile_pos) in new_buildings.iter() {
-        if let BuildingType::Mine = building_type {
+        if let Some(BuildingType::Mine) = mods.base(*building_type) {
             commands.entity(entity).insert(Mine {
+                item_type: mods.mined_item(*building_type),
                 timer: Timer::new(Duration::from_secs(1), TimerMode::Repeating),
                 output: *tile_pos,
+                blocked: false,
             });
         }
     }
@@ -33,30 +70,36 @@ pub fn mine_produce(
     mut mines: Query<&mut Mine>,
     mut belts: Query<(Entity, &mut Belt)>,
     tilemap_query: Query<&TileStorage, With<BuildingLayer>>,
-    time: Res<Time>,
-    asset_server: ResMut<AssetServer>,
+    simulation: Res<Simulation>,
+    mut item_flow: EventWriter<ItemFlowEvent>,
 ) {
     let building_layer = tilemap_query.single();
 
     for mut mine in mines.iter_mut() {
-        if mine.timer.tick(time.delta()).just_finished() {
+        if mine.timer.tick(simulation.delta()).just_finished() {
             let ouputs = output_positions(mine.output).flat_map(|pos| building_layer.get(&pos));
 
+            mine.blocked = true;
+
             for belt_entity in ouputs {
                 if let Ok((belt_entity, mut belt)) = belts.get_mut(belt_entity) {
                     if belt.place_new(0.33, || {
                         commands
-                            .spawn(SpriteBundle {
-                                transform: Transform::from_xyz(0., 0., -9999.),
-                                texture: asset_server.load("items.png"),
-                                ..default()
-                            })
-                            .insert(Item {
+                            .spawn(Item {
                                 belt: belt_entity,
-                                item_type: ItemType::Coal,
+                                item_type: mine.item_type,
                             })
+                            .insert(TransformBundle::from_transform(Transform::from_xyz(
+                                0., 0., -9999.,
+                            )))
                             .id()
                     }) {
+                        mine.blocked = false;
+                        item_flow.send(ItemFlowEvent {
+                            item_type: mine.item_type,
+                            flow: ItemFlow::Produced,
+                            amount: 1,
+                        });
                         break;
                     }
                 }
@@ -0,0 +1,42 @@
+use bevy::prelude::*;
+
+use super::Building;
+
+// components of a building or its tiles that can describe their current state
+pub trait BuildingStatus: Component {
+    fn status(&self, lines: &mut Vec<String>);
+}
+
+#[derive(Resource, Default)]
+pub struct InspectedBuilding {
+    pub building: Option<Entity>,
+    pub status: Vec<String>,
+}
+
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CollectBuildingStatus;
+
+pub fn collect_building_status<T: BuildingStatus>(
+    mut inspected: ResMut<InspectedBuilding>,
+    buildings: Query<&Building>,
+    components: Query<&T>,
+) {
+    let Some(building_entity) = inspected.building else {
+        return;
+    };
+
+    let tiles = buildings
+        .get(building_entity)
+        .into_iter()
+        .flat_map(|b| b.layout.tiles.iter().map(|(e, _, _)| *e));
+
+    let mut lines = Vec::new();
+
+    for entity in std::iter::once(building_entity).chain(tiles) {
+        if let Ok(component) = components.get(entity) {
+            component.status(&mut lines);
+        }
+    }
+
+    inspected.status.extend(lines);
+}
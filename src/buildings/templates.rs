@@ -1,3 +1,6 @@
+use std::fs;
+use std::path::Path;
+
 use arrayvec::ArrayVec;
 use bevy::prelude::*;
 use bevy::reflect::{TypePath, TypeUuid};
@@ -13,7 +16,7 @@ pub mod loader;
 
 type Instructions<T> = ArrayVec<(TilePos, T), MAX_BUILDING_SIZE>;
 
-#[derive(Debug, TypeUuid, TypePath)]
+#[derive(Debug, Clone, TypeUuid, TypePath)]
 #[uuid = "a5bf35d0-f823-4a41-8e54-dd1bd4ed0acd"]
 pub struct BuildingTemplate {
     pub building_type: BuildingType,
@@ -106,7 +109,7 @@ impl PlacedBuildingTemplate<'_> {
                 let pos = TilePos::new(self.origin.x + tile_pos.x, self.origin.y + tile_pos.y);
                 (pos, *tile_type)
             })
-    }   
+    }
 
     // pub fn io(&self) -> impl Iterator<Item = (TilePos, IoTileType)> + '_ {
     //     self.template.io[self.direction]
@@ -120,28 +123,61 @@ impl PlacedBuildingTemplate<'_> {
 
 #[derive(Resource, Default)]
 pub struct BuildingRegistry {
-    templates: PreHashMap<BuildingType, Handle<BuildingTemplate>>,
-    loading_handles: Vec<HandleUntyped>,
+    templates: PreHashMap<BuildingType, BuildingTemplate>,
 }
 
 impl BuildingRegistry {
-    fn register(&mut self, building_type: BuildingType, template: Handle<BuildingTemplate>) {
-        self.templates.insert(Hashed::new(building_type), template);
+    pub fn register(&mut self, template: BuildingTemplate) {
+        self.templates
+            .insert(Hashed::new(template.building_type), template);
+    }
+
+    // reads the templates straight from the disk, for simulations without the asset server
+    pub fn load_dir(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let mut registry = BuildingRegistry::default();
+
+        for entry in fs::read_dir(path)? {
+            let path = entry?.path();
+
+            if path
+                .to_str()
+                .is_some_and(|p| p.ends_with(loader::TEMPLATE_EXTENSION))
+            {
+                registry.register(loader::load_template(&path, &fs::read(&path)?)?);
+            }
+        }
+
+        Ok(registry)
+    }
+
+    pub fn contains(&self, building: BuildingType) -> bool {
+        self.templates.contains_key(&Hashed::new(building))
     }
 
-    pub fn get(&self, building: BuildingType) -> Handle<BuildingTemplate> {
+    pub fn get(&self, building: BuildingType) -> &BuildingTemplate {
         let Some(template) = self.templates.get(&Hashed::new(building)) else {
-            panic!("building {:?} is not registered, registered buildings {:?}", building, self.templates);
+            let registered: Vec<_> = self.templates.keys().map(|k| **k).collect();
+            panic!(
+                "building {:?} is not registered, registered buildings {:?}",
+                building, registered
+            );
         };
 
-        template.clone()
+        template
     }
 }
 
-pub fn load_building_templates(assets: Res<AssetServer>, mut templates: ResMut<BuildingRegistry>) {
+// handles of the template assets, kept so the templates stay loaded and can be hot reloaded
+#[derive(Resource, Default)]
+pub struct BuildingTemplateHandles(Vec<HandleUntyped>);
+
+pub fn load_building_templates(
+    assets: Res<AssetServer>,
+    mut template_handles: ResMut<BuildingTemplateHandles>,
+) {
     match assets.load_folder("buildings") {
         Ok(handles) => {
-            templates.loading_handles.extend(handles);
+            template_handles.0.extend(handles);
         }
         Err(e) => warn!("couldn't load building templates: {}", e),
     }
@@ -151,29 +187,26 @@ pub fn register_building_templates(
     templates: Res<Assets<BuildingTemplate>>,
     mut asset_events: EventReader<AssetEvent<BuildingTemplate>>,
     mut building_templates: ResMut<BuildingRegistry>,
-    mut buildings: Query<&mut Handle<BuildingTemplate>>,
+    mut buildings: Query<&mut BuildingType>,
 ) {
     for event in asset_events.iter() {
-        match event {
-            AssetEvent::Created { handle } => {
-                if let Some(index) = building_templates
-                    .loading_handles
-                    .iter()
-                    .position(|h| h.id() == handle.id())
-                {
-                    let template = templates.get(handle).unwrap();
-                    let handle = building_templates.loading_handles.swap_remove(index);
-                    building_templates.register(template.building_type, handle.typed());
-                }
-            }
-            AssetEvent::Modified { handle } => {
-                for building_handle in buildings.iter_mut() {
-                    if &*building_handle == handle {
-                        building_handle.into_inner();
-                    }
+        let (AssetEvent::Created { handle } | AssetEvent::Modified { handle }) = event else {
+            continue;
+        };
+
+        let Some(template) = templates.get(handle) else {
+            continue;
+        };
+
+        building_templates.register(template.clone());
+
+        // existing buildings are constructed again from the changed template
+        if let AssetEvent::Modified { .. } = event {
+            for mut building_type in buildings.iter_mut() {
+                if *building_type == template.building_type {
+                    building_type.set_changed();
                 }
             }
-            _ => {}
         }
     }
 }
@@ -6,8 +6,30 @@ use bevy::asset::LoadedAsset;
 
 use super::BuildingTemplate;
 
+pub const TEMPLATE_EXTENSION: &str = "building.tmx";
+
 pub struct BuildingTemplateLoader;
 
+// the building type comes from the file name, e.g. mine.building.tmx
+pub fn load_template(path: &Path, bytes: &[u8]) -> anyhow::Result<BuildingTemplate> {
+    let mut loader = tiled::Loader::with_cache_and_reader(
+        tiled::DefaultResourceCache::new(),
+        BytesResourceReader::new(bytes),
+    );
+    let tilemap = loader
+        .load_tmx_map(path)
+        .map_err(|e| anyhow::anyhow!("Could not load TMX map: {e}"))?;
+
+    let building_type = path
+        .file_name()
+        .and_then(|s| s.to_str())
+        .and_then(|s| s.strip_suffix(".building.tmx"))
+        .and_then(|s| s.parse().ok())
+        .ok_or_else(|| anyhow::anyhow!("unknown building {}", path.display()))?;
+
+    BuildingTemplate::from_tilemap(building_type, tilemap)
+}
+
 impl bevy::asset::AssetLoader for BuildingTemplateLoader {
     fn load<'a>(
         &'a self,
@@ -15,24 +37,7 @@ impl bevy::asset::AssetLoader for BuildingTemplateLoader {
         load_context: &'a mut bevy::asset::LoadContext,
     ) -> bevy::asset::BoxedFuture<'a, Result<(), anyhow::Error>> {
         Box::pin(async move {
-            let path = load_context.path();
-
-            let mut loader = tiled::Loader::with_cache_and_reader(
-                tiled::DefaultResourceCache::new(),
-                BytesResourceReader::new(bytes),
-            );
-            let tilemap = loader
-                .load_tmx_map(path)
-                .map_err(|e| anyhow::anyhow!("Could not load TMX map: {e}"))?;
-
-            let building_type = path
-                .file_name()
-                .and_then(|s| s.to_str())
-                .and_then(|s| s.strip_suffix(".building.tmx"))
-                .and_then(|s| s.parse().ok())
-                .ok_or_else(|| anyhow::anyhow!("unknown building {}", path.display()))?;
-
-            let template = BuildingTemplate::from_tilemap(building_type, tilemap)?;
+            let template = load_template(load_context.path(), bytes)?;
 
             load_context.set_default_asset(LoadedAsset::new(template));
             Ok(())
@@ -40,7 +45,7 @@ impl bevy::asset::AssetLoader for BuildingTemplateLoader {
     }
 
     fn extensions(&self) -> &[&str] {
-        static EXTENSIONS: &[&str] = &["building.tmx"];
+        static EXTENSIONS: &[&str] = &[TEMPLATE_EXTENSION];
         EXTENSIONS
     }
 }
@@ -1,5 +1,27 @@
+use bevy::ecs::system::SystemParam;
 use bevy::input::mouse::MouseWheel;
 use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+
+use crate::game::GameState;
+use crate::input::{Action, ActionInput};
+use crate::map::{BuildingLayer, TILEMAP_SIZE, TILE_SIZE};
+use crate::settings::Settings;
+
+const MIN_ZOOM: f32 = 0.25;
+pub const MAX_ZOOM: f32 = 4.;
+// how quickly the zoom catches up with the target, per second
+const ZOOM_SMOOTHING: f32 = 12.;
+
+pub struct CameraPlugin;
+
+impl Plugin for CameraPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Zoom>()
+            .add_systems(Startup, spawn_camera)
+            .add_systems(Update, camera_movement.run_if(in_state(GameState::InGame)));
+    }
+}
 
 #[derive(Component)]
 pub struct MainCamera;
@@ -7,55 +29,142 @@ pub struct MainCamera;
 #[derive(Resource, Default)]
 pub struct Zoom(pub f32);
 
+pub fn spawn_camera(mut commands: Commands) {
+    commands.spawn(Camera2dBundle::default()).insert(MainCamera);
+}
+
+// the mouse wheel moves a target the camera zooms towards
+#[derive(SystemParam)]
+pub struct ZoomInput<'w, 's> {
+    scroll_evr: EventReader<'w, 's, MouseWheel>,
+    zoom: ResMut<'w, Zoom>,
+    target_zoom: Local<'s, Option<f32>>,
+}
+
+impl ZoomInput<'_, '_> {
+    fn target(&mut self, scale: f32) -> f32 {
+        let target = self.target_zoom.get_or_insert(scale);
+
+        for e in self.scroll_evr.iter() {
+            match e.y {
+                y if y < 0. => {
+                    *target *= 2.;
+                }
+                y if y > 0. => {
+                    *target *= 0.5;
+                }
+                _ => {}
+            }
+        }
+
+        *target = target.clamp(MIN_ZOOM, MAX_ZOOM);
+        *target
+    }
+}
+
+#[derive(SystemParam)]
+pub struct CameraCursor<'w, 's> {
+    window: Query<'w, 's, &'static Window, With<PrimaryWindow>>,
+    last_drag_pos: Local<'s, Option<Vec2>>,
+}
+
+impl CameraCursor<'_, '_> {
+    // cursor position relative to the window centre, y pointing up like the world
+    fn offset(&self) -> Option<Vec2> {
+        let window = self.window.get_single().ok()?;
+        let cursor = window.cursor_position()?;
+        let centre = Vec2::new(window.width(), window.height()) / 2.;
+        Some((cursor - centre) * Vec2::new(1., -1.))
+    }
+
+    // how far the cursor moved since the last frame of the drag
+    fn drag(&mut self, offset: Option<Vec2>) -> Option<Vec2> {
+        let last = std::mem::replace(&mut *self.last_drag_pos, offset);
+        Some(offset? - last?)
+    }
+}
+
 // A simple camera system for moving and zooming the camera.
 pub fn camera_movement(
-    keyboard_input: Res<Input<KeyCode>>,
-    mut scroll_evr: EventReader<MouseWheel>,
+    input: ActionInput,
+    mut zoom_input: ZoomInput,
     mut query: Query<(&mut Transform, &mut OrthographicProjection), With<MainCamera>>,
-    mut zoom: ResMut<Zoom>,
+    mut cursor: CameraCursor,
+    map: Query<&Transform, (With<BuildingLayer>, Without<MainCamera>)>,
+    settings: Res<Settings>,
     time: Res<Time>,
 ) {
     let (mut transform, mut ortho) = query.single_mut();
 
+    let cursor_offset = cursor.offset();
+
     let mut direction = Vec3::ZERO;
 
-    if keyboard_input.pressed(KeyCode::Left) {
+    if input.pressed(Action::CameraLeft) {
         direction -= Vec3::new(1.0, 0.0, 0.0);
     }
 
-    if keyboard_input.pressed(KeyCode::Right) {
+    if input.pressed(Action::CameraRight) {
         direction += Vec3::new(1.0, 0.0, 0.0);
     }
 
-    if keyboard_input.pressed(KeyCode::Up) {
+    if input.pressed(Action::CameraUp) {
         direction += Vec3::new(0.0, 1.0, 0.0);
     }
 
-    if keyboard_input.pressed(KeyCode::Down) {
+    if input.pressed(Action::CameraDown) {
         direction -= Vec3::new(0.0, 1.0, 0.0);
     }
 
-    for e in scroll_evr.iter() {
-        match e.y {
-            y if y < 0. => {
-                ortho.scale *= 2.;
-            }
-            y if y > 0. => {
-                ortho.scale *= 0.5;
-            }
-            _ => {}
-        }
+    let target = zoom_input.target(ortho.scale);
+
+    // worked out on copies, the camera is only written when it moves so Changed means something
+    let previous_scale = ortho.scale;
+    let smoothing = 1. - (-ZOOM_SMOOTHING * time.delta_seconds()).exp();
+    let mut scale = previous_scale + (target - previous_scale) * smoothing;
+
+    if (target - scale).abs() < 0.001 {
+        scale = target;
     }
 
-    if ortho.scale < 0.25 {
-        ortho.scale = 0.25;
+    let mut translation = transform.translation;
+
+    // keep the point under the cursor in place while zooming
+    if let Some(offset) = cursor_offset {
+        let shift = offset * (previous_scale - scale);
+        translation += shift.extend(0.);
     }
 
-    zoom.0 = ortho.scale;
+    zoom_input.zoom.0 = scale;
+
+    // dragging moves the map together with the cursor
+    if let Some(delta) = cursor.drag(cursor_offset.filter(|_| input.pressed(Action::CameraPan))) {
+        translation -= (delta * scale).extend(0.);
+    }
 
-    let z = transform.translation.z;
-    transform.translation += time.delta_seconds() * direction * 500.;
-    // Important! We need to restore the Z values when moving the camera around.
+    // Important! We need to keep the Z values when moving the camera around.
     // Bevy has a specific camera setup and this can mess with how our layers are shown.
-    transform.translation.z = z;
+    translation += (time.delta_seconds() * direction * settings.pan_speed * scale)
+        .truncate()
+        .extend(0.);
+
+    if let Ok(map_transform) = map.get_single() {
+        let min = map_transform.translation.truncate() - Vec2::new(TILE_SIZE.x, TILE_SIZE.y) / 2.;
+        let max = min
+            + Vec2::new(
+                TILEMAP_SIZE.x as f32 * TILE_SIZE.x,
+                TILEMAP_SIZE.y as f32 * TILE_SIZE.y,
+            );
+
+        translation.x = translation.x.clamp(min.x, max.x);
+        translation.y = translation.y.clamp(min.y, max.y);
+    }
+
+    if ortho.scale != scale {
+        ortho.scale = scale;
+    }
+
+    if transform.translation != translation {
+        transform.translation = translation;
+    }
 }
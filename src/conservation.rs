@@ -0,0 +1,165 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::*;
+
+use crate::belts::{Belt, Item, ItemType};
+use crate::map::{to_tile_pos, BuildingLayer};
+
+// inventories only count their items, an item entity is despawned as it goes in, so every item
+// entity has to be on exactly one belt, the one it thinks it is on
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ItemViolation {
+    // the belt carries an entity that isn't an item
+    MissingItem {
+        item: Entity,
+        belt: TilePos,
+    },
+    // the same item on several belts, or several times on one
+    Duplicated {
+        item: Entity,
+        belts: Vec<TilePos>,
+    },
+    // the item is carried by another belt than the one it thinks it is on
+    WrongBelt {
+        item: Entity,
+        belt: TilePos,
+        recorded: Option<TilePos>,
+    },
+    // no belt carries the item, it was last drawn at `tile_pos`
+    Lost {
+        item: Entity,
+        item_type: ItemType,
+        tile_pos: Option<TilePos>,
+    },
+}
+
+fn fmt_tile(tile_pos: Option<&TilePos>) -> String {
+    tile_pos.map_or("nowhere".to_string(), |tile_pos| {
+        format!("{}, {}", tile_pos.x, tile_pos.y)
+    })
+}
+
+impl fmt::Display for ItemViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ItemViolation::MissingItem { item, belt } => {
+                write!(
+                    f,
+                    "belt at {} carries {item:?} which isn't an item",
+                    fmt_tile(Some(belt))
+                )
+            }
+            ItemViolation::Duplicated { item, belts } => {
+                let belts: Vec<_> = belts.iter().map(|belt| fmt_tile(Some(belt))).collect();
+                write!(f, "item {item:?} is on belts at {}", belts.join(" and "))
+            }
+            ItemViolation::WrongBelt {
+                item,
+                belt,
+                recorded,
+            } => write!(
+                f,
+                "item {item:?} is on the belt at {} but thinks it is on the belt at {}",
+                fmt_tile(Some(belt)),
+                fmt_tile(recorded.as_ref())
+            ),
+            ItemViolation::Lost {
+                item,
+                item_type,
+                tile_pos,
+            } => write!(
+                f,
+                "{} item {item:?} at {} isn't on any belt",
+                item_type.as_str(),
+                fmt_tile(tile_pos.as_ref())
+            ),
+        }
+    }
+}
+
+// what the last check found, only checked in debug builds
+#[derive(Resource, Default)]
+pub struct ItemConservation {
+    violations: Vec<ItemViolation>,
+}
+
+impl ItemConservation {
+    pub fn violations(&self) -> &[ItemViolation] {
+        &self.violations
+    }
+}
+
+// runs first in every tick, a violation is logged once when it shows up
+pub fn check_item_conservation(
+    mut conservation: ResMut<ItemConservation>,
+    items: Query<(Entity, &Item, Option<&Transform>)>,
+    belts: Query<(Entity, &Belt, &TilePos)>,
+    building_layer_query: Query<(&TilemapTileSize, &TilemapSize, &Transform), With<BuildingLayer>>,
+) {
+    let mut violations = Vec::new();
+    let mut carried: HashMap<Entity, Vec<(Entity, TilePos)>> = HashMap::new();
+
+    for (belt_entity, belt, belt_pos) in belts.iter() {
+        for (item, _) in belt.items.iter() {
+            if items.contains(*item) {
+                carried
+                    .entry(*item)
+                    .or_default()
+                    .push((belt_entity, *belt_pos));
+            } else {
+                violations.push(ItemViolation::MissingItem {
+                    item: *item,
+                    belt: *belt_pos,
+                });
+            }
+        }
+    }
+
+    let building_layer = building_layer_query.get_single().ok();
+
+    for (entity, item, transform) in items.iter() {
+        match carried.get(&entity).map(Vec::as_slice) {
+            None | Some([]) => {
+                let tile_pos = building_layer.zip(transform).and_then(
+                    |((tile_size, map_size, map_transform), transform)| {
+                        to_tile_pos(
+                            transform.translation.truncate(),
+                            tile_size,
+                            map_size,
+                            map_transform,
+                        )
+                    },
+                );
+
+                violations.push(ItemViolation::Lost {
+                    item: entity,
+                    item_type: item.item_type,
+                    tile_pos,
+                });
+            }
+            Some([(belt, belt_pos)]) => {
+                if *belt != item.belt {
+                    violations.push(ItemViolation::WrongBelt {
+                        item: entity,
+                        belt: *belt_pos,
+                        recorded: belts.get(item.belt).ok().map(|(_, _, tile_pos)| *tile_pos),
+                    });
+                }
+            }
+            Some(belts) => violations.push(ItemViolation::Duplicated {
+                item: entity,
+                belts: belts.iter().map(|(_, belt_pos)| *belt_pos).collect(),
+            }),
+        }
+    }
+
+    for violation in violations.iter() {
+        if !conservation.violations.contains(violation) {
+            error!("{violation}");
+        }
+    }
+
+    conservation.violations = violations;
+}
@@ -1,6 +1,7 @@
 use bevy::ecs::component::Component;
+use serde::{Deserialize, Serialize};
 
-#[derive(Component, Clone, Copy, PartialEq, Default, Eq)]
+#[derive(Component, Clone, Copy, PartialEq, Default, Eq, Hash, Serialize, Deserialize)]
 pub enum MapDirection {
     #[default]
     Up,
@@ -20,21 +21,40 @@ impl MapDirection {
     }
 }
 
+impl MapDirection {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Up => "up",
+            Self::Down => "down",
+            Self::Left => "left",
+            Self::Right => "right",
+        }
+    }
+}
+
+pub struct UnknownDirection;
+
+impl std::str::FromStr for MapDirection {
+    type Err = UnknownDirection;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        [Self::Up, Self::Down, Self::Left, Self::Right]
+            .into_iter()
+            .find(|direction| direction.as_str() == s)
+            .ok_or(UnknownDirection)
+    }
+}
+
 impl<S> PartialEq<S> for MapDirection
 where
     S: AsRef<str>,
 {
     fn eq(&self, other: &S) -> bool {
-        match self {
-            Self::Up => other.as_ref() == "up",
-            Self::Down => other.as_ref() == "down",
-            Self::Left => other.as_ref() == "left",
-            Self::Right => other.as_ref() == "right",
-        }
+        self.as_str() == other.as_ref()
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Directional<T> {
     pub up: T,
     pub down: T,
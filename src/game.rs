@@ -0,0 +1,120 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::*;
+
+use crate::belts::Item;
+use crate::build_mode::BuildMode;
+use crate::buildings::status::InspectedBuilding;
+use crate::buildings::{Building, Tool};
+use crate::camera::MainCamera;
+use crate::history::History;
+use crate::map::{BuildingLayer, Terrain};
+use crate::mods::registry::ModRegistry;
+use crate::save::{SaveGame, SavedInventories};
+use crate::simulation::{Simulation, SimulationSet};
+use crate::statistics::ProductionStatistics;
+use crate::ui::inventory::Hand;
+
+pub struct GamePlugin;
+
+impl Plugin for GamePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_state::<GameState>()
+            .configure_set(Update, SimulationSet.run_if(in_state(GameState::InGame)))
+            .init_resource::<GameSetup>()
+            .init_resource::<GameSeed>()
+            .add_systems(OnExit(GameState::MainMenu), start_game)
+            .add_systems(OnEnter(GameState::InGame), enable_build_mode)
+            .add_systems(OnExit(GameState::InGame), disable_build_mode);
+    }
+}
+
+#[derive(States, Debug, Clone, Copy, Default, Eq, PartialEq, Hash)]
+pub enum GameState {
+    #[default]
+    MainMenu,
+    InGame,
+    Paused,
+}
+
+#[derive(Resource, Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GameSeed(pub u64);
+
+// chosen in the main menu, used once the game starts
+#[derive(Resource)]
+pub struct GameSetup {
+    pub seed: u64,
+    pub save: Option<SaveGame>,
+    // buildings and items of mods the save, replay or host came with
+    pub mods: ModRegistry,
+}
+
+impl Default for GameSetup {
+    fn default() -> Self {
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos() as u64 % 1_000_000);
+
+        GameSetup {
+            seed,
+            save: None,
+            mods: ModRegistry::default(),
+        }
+    }
+}
+
+// leaving the main menu always starts a new game, whatever was left from the previous one goes
+pub fn start_game(
+    mut commands: Commands,
+    mut setup: ResMut<GameSetup>,
+    mut mods: ResMut<ModRegistry>,
+    buildings: Query<(Entity, &Building)>,
+    items: Query<Entity, With<Item>>,
+    mut building_layer: Query<&mut TileStorage, With<BuildingLayer>>,
+    mut camera: Query<&mut Transform, With<MainCamera>>,
+) {
+    if let Ok(mut building_layer) = building_layer.get_single_mut() {
+        for (building_entity, building) in buildings.iter() {
+            for (entity, tile_pos, _) in &building.layout.tiles {
+                commands.entity(*entity).despawn_recursive();
+                building_layer.checked_remove(tile_pos);
+            }
+
+            commands.entity(building_entity).despawn();
+        }
+    }
+
+    for item in items.iter() {
+        commands.entity(item).despawn();
+    }
+
+    commands.insert_resource(History::default());
+    commands.insert_resource(ProductionStatistics::default());
+    commands.insert_resource(Simulation::default());
+    commands.insert_resource(InspectedBuilding::default());
+    commands.insert_resource(Hand::default());
+    commands.insert_resource(Tool::default());
+    commands.insert_resource(GameSeed(setup.seed));
+    commands.insert_resource(Terrain::generate(setup.seed));
+    mods.extend(setup.mods.clone());
+
+    let inventories = match setup.save.take() {
+        Some(save) => save.restore(&mut commands),
+        None => SavedInventories::default(),
+    };
+    commands.insert_resource(inventories);
+
+    if let Ok(mut transform) = camera.get_single_mut() {
+        transform.translation.x = 0.;
+        transform.translation.y = 0.;
+    }
+}
+
+pub fn enable_build_mode(mut next_state: ResMut<NextState<BuildMode>>) {
+    next_state.set(BuildMode::Enabled);
+}
+
+pub fn disable_build_mode(mut next_state: ResMut<NextState<BuildMode>>) {
+    next_state.set(BuildMode::Disabled);
+}
@@ -5,13 +5,14 @@ use bevy_ecs_tilemap::prelude::*;
 use crate::camera::Zoom;
 use crate::input::handle_keyboard_input;
 use crate::map::{MapEvent, GRID_SIZE, TILEMAP_SIZE, TILE_SIZE};
+use crate::settings::{load_settings, Settings};
 
 pub struct GridPlugin;
 
 impl Plugin for GridPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<Grid>()
-            .add_systems(Startup, create_grid_layer)
+            .add_systems(Startup, (create_grid_layer, init_grid.after(load_settings)))
             .add_systems(Update, toggle_grid.after(handle_keyboard_input));
     }
 }
@@ -61,8 +62,6 @@ impl Grid {
 #[derive(Component)]
 pub struct GridLayer;
 
-const MAX_GRID_ZOOM: f32 = 2.;
-
 pub fn create_grid_layer(mut commands: Commands, asset_server: Res<AssetServer>) {
     let grid_texture = asset_server.load("tilesets/grid.png");
 
@@ -93,11 +92,16 @@ pub fn create_grid_layer(mut commands: Commands, asset_server: Res<AssetServer>)
     });
 }
 
+pub fn init_grid(settings: Res<Settings>, mut grid: ResMut<Grid>) {
+    *grid = settings.show_grid.into();
+}
+
 pub fn toggle_grid(
     mut grid_layer: Query<&mut Visibility, With<GridLayer>>,
     mut map_events: EventReader<MapEvent>,
     mut grid_state: ResMut<Grid>,
     zoom: Res<Zoom>,
+    settings: Res<Settings>,
 ) {
     for _ in map_events
         .iter()
@@ -106,14 +110,15 @@ pub fn toggle_grid(
         grid_state.toggle();
     }
 
-    if !grid_state.is_changed() && !zoom.is_changed() {
+    if !grid_state.is_changed() && !zoom.is_changed() && !settings.is_changed() {
         return;
     }
 
     if let Ok(mut grid_visibility) = grid_layer.get_single_mut() {
-        *grid_visibility = match matches!(*grid_state, Grid::Enabled) && zoom.0 < MAX_GRID_ZOOM {
-            true => Visibility::Visible,
-            false => Visibility::Hidden,
-        }
+        *grid_visibility =
+            match matches!(*grid_state, Grid::Enabled) && zoom.0 < settings.max_grid_zoom {
+                true => Visibility::Visible,
+                false => Visibility::Hidden,
+            }
     };
 }
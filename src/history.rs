@@ -0,0 +1,202 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+
+use crate::belts::{redirect_belts, InventoryEvent};
+use crate::build_mode::BuildMode;
+use crate::buildings::{
+    build_building, demolish_building, BuildRequestedEvent, BuildingChangedEvent, BuildingType,
+    DemolishEvent,
+};
+use crate::input::{Action, ActionInput};
+use crate::map::clear_buildings;
+use crate::replay::record_inputs;
+
+const MAX_HISTORY: usize = 100;
+
+pub struct HistoryPlugin;
+
+impl Plugin for HistoryPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<History>()
+            .add_event::<HistoryEvent>()
+            .add_event::<BuildingChangedEvent>()
+            .add_systems(
+                Update,
+                (
+                    // recorded together with the inputs of the player
+                    apply_history
+                        .before(record_inputs)
+                        .before(build_building)
+                        .before(redirect_belts)
+                        .before(demolish_building),
+                    record_history
+                        .after(build_building)
+                        .after(redirect_belts)
+                        .after(demolish_building)
+                        .after(clear_buildings),
+                )
+                    .run_if(in_state(BuildMode::Enabled)),
+            );
+    }
+}
+
+#[derive(Event, Clone, Copy, PartialEq, Eq)]
+pub enum HistoryEvent {
+    Undo,
+    Redo,
+}
+
+// what a frame of local inputs was, the stack its changes go to
+#[derive(Clone, Copy)]
+enum Step {
+    History(HistoryEvent),
+    Action { drag: bool },
+}
+
+// every entry is a group of changes that are undone together, e.g. one mouse drag
+#[derive(Resource, Default)]
+pub struct History {
+    undo: Vec<Vec<BuildingChangedEvent>>,
+    redo: Vec<Vec<BuildingChangedEvent>>,
+    applying: Option<HistoryEvent>,
+    // under lockstep the inputs of this frame were sent and come back in a later turn
+    sending: bool,
+    // steps sent and not applied yet, in the order they were sent
+    sent: VecDeque<Step>,
+    // set when a turn applies the inputs of a player, whether they are ours
+    turn: Option<bool>,
+}
+
+impl History {
+    pub fn send_inputs(&mut self) {
+        self.sending = true;
+    }
+
+    pub fn apply_turn(&mut self, own: bool) {
+        self.turn = Some(own);
+    }
+
+    fn push(stack: &mut Vec<Vec<BuildingChangedEvent>>, group: Vec<BuildingChangedEvent>) {
+        stack.push(group);
+
+        if stack.len() > MAX_HISTORY {
+            stack.remove(0);
+        }
+    }
+}
+
+pub fn apply_history(
+    mut history_events: EventReader<HistoryEvent>,
+    mut history: ResMut<History>,
+    mut build_events: EventWriter<BuildRequestedEvent>,
+    mut demolish_events: EventWriter<DemolishEvent>,
+    mut inventory_events: EventWriter<InventoryEvent>,
+) {
+    // only one step per frame, the next one depends on the result of this one
+    let Some(event) = history_events.iter().last().copied() else {
+        return;
+    };
+
+    let group = match event {
+        HistoryEvent::Undo => history.undo.pop(),
+        HistoryEvent::Redo => history.redo.pop(),
+    };
+
+    let Some(group) = group else {
+        return;
+    };
+
+    let mut build = |building_type, tile_pos, direction| {
+        build_events.send(BuildRequestedEvent {
+            building_type,
+            direction,
+            tile_pos,
+        })
+    };
+
+    // the redo stack holds what undoing did, so both steps revert their group
+    for change in group.into_iter().rev() {
+        match change {
+            BuildingChangedEvent::Built { origin, .. } => {
+                demolish_events.send(DemolishEvent { tile_pos: origin })
+            }
+            BuildingChangedEvent::Demolished {
+                building_type,
+                origin,
+                direction,
+                inventory,
+            } => {
+                build(building_type, origin, direction);
+
+                // lands in the same frame, once the building is back
+                if let Some(inventory) = inventory {
+                    inventory_events.send(InventoryEvent::Restore {
+                        tile_pos: origin,
+                        slots: inventory.slots.to_vec(),
+                    });
+                }
+            }
+            BuildingChangedEvent::Redirected { tile_pos, from, .. } => {
+                build(BuildingType::Belt, tile_pos, from)
+            }
+        }
+    }
+
+    history.applying = Some(event);
+}
+
+pub fn record_history(
+    mut changed_events: EventReader<BuildingChangedEvent>,
+    mut history: ResMut<History>,
+    input: ActionInput,
+    mut dragging: Local<bool>,
+) {
+    if input.just_pressed(Action::Primary) || !input.pressed(Action::Primary) {
+        *dragging = false;
+    }
+
+    let changes: Vec<_> = changed_events.iter().cloned().collect();
+    let sending = std::mem::take(&mut history.sending);
+    let turn = history.turn.take();
+
+    let step = match history.applying.take() {
+        Some(event) => Step::History(event),
+        None => Step::Action { drag: *dragging },
+    };
+
+    if matches!(step, Step::Action { .. }) && (sending || turn.is_none() && !changes.is_empty()) {
+        *dragging = input.pressed(Action::Primary);
+    }
+
+    if sending {
+        history.sent.push_back(step);
+    }
+
+    // other players' changes aren't ours to undo, ours are recorded as what they were sent as
+    let step = match turn {
+        None => step,
+        Some(true) => match history.sent.pop_front() {
+            Some(step) => step,
+            None => return,
+        },
+        Some(false) => return,
+    };
+
+    if changes.is_empty() {
+        return;
+    }
+
+    match step {
+        Step::History(HistoryEvent::Undo) => History::push(&mut history.redo, changes),
+        Step::History(HistoryEvent::Redo) => History::push(&mut history.undo, changes),
+        Step::Action { drag } => {
+            history.redo.clear();
+
+            match history.undo.last_mut() {
+                Some(group) if drag => group.extend(changes),
+                _ => History::push(&mut history.undo, changes),
+            }
+        }
+    }
+}
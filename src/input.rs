@@ -1,49 +1,117 @@
-use bevy::input::keyboard::KeyboardInput;
-use bevy::input::ButtonState;
 use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::*;
 
+pub use self::actions::{Action, ActionInput, ActionMap};
 pub use self::cursor::GameCursor;
-use crate::buildings::{BuildRequestedEvent, BuildTool, BuildingType, DemolishEvent, Tool};
-use crate::map::MapEvent;
-use crate::ui::MapInteraction;
+use crate::belts::belt_dir_between;
+use crate::blueprints::CopyAreaEvent;
+use crate::buildings::{
+    BuildRequestedEvent, BuildTool, BuildingTile, BuildingType, DemolishAreaEvent, DemolishFilter,
+    SelectEvent, Tool,
+};
+use crate::direction::MapDirection;
+use crate::game::GameState;
+use crate::history::HistoryEvent;
+use crate::map::{BuildingLayer, BuildingTileType, MapEvent, TileArea};
+use crate::replay::ReplayPlayback;
+use crate::simulation::{Simulation, SimulationSet};
+use crate::ui::{MapInteraction, UiEvent};
 
+pub mod actions;
 pub mod cursor;
 
 pub struct InputPlugin;
 
 impl Plugin for InputPlugin {
     fn build(&self, app: &mut App) {
-        app.init_resource::<GameCursor>().add_systems(
-            Update,
-            (
-                handle_mouse_input,
-                handle_keyboard_input,
-                (cursor::update_world_cursor, cursor::update_map_cursor).chain(),
-            ),
-        );
+        // the defaults until the config file is read, adding the plugin doesn't touch the disk
+        app.init_resource::<ActionMap>()
+            .init_resource::<GameCursor>()
+            .init_resource::<AreaSelection>()
+            .add_event::<SelectEvent>()
+            .add_systems(Startup, load_action_map)
+            .add_systems(
+                Update,
+                (
+                    handle_mouse_input,
+                    handle_keyboard_input,
+                    handle_pipette,
+                    handle_area_selection,
+                    handle_simulation_input,
+                    (cursor::update_world_cursor, cursor::update_map_cursor).chain(),
+                )
+                    .run_if(in_state(GameState::InGame))
+                    .run_if(not(resource_exists::<ReplayPlayback>()))
+                    .before(SimulationSet),
+            );
     }
 }
 
+fn load_action_map(mut action_map: ResMut<ActionMap>) {
+    *action_map = ActionMap::load();
+}
+
 pub fn handle_mouse_input(
-    mouse: Res<Input<MouseButton>>,
+    input: ActionInput,
     cursor_pos: Res<GameCursor>,
     mut build_events: EventWriter<BuildRequestedEvent>,
-    mut demolish_events: EventWriter<DemolishEvent>,
+    mut select_events: EventWriter<SelectEvent>,
     map_interaction: Res<MapInteraction>,
     selected_tool: Res<Tool>,
+    mut belt_drag: Local<Option<(TilePos, MapDirection)>>,
 ) {
     let Some(tile_pos) = cursor_pos.tile_pos else {
         return;
     };
 
-    if mouse.pressed(MouseButton::Left) && map_interaction.is_allowed() {
+    if input.just_pressed(Action::Primary) {
+        *belt_drag = None;
+    }
+
+    if input.pressed(Action::Primary) && map_interaction.is_allowed() {
         match &*selected_tool {
+            Tool::Build(build_tool) if build_tool.building == BuildingType::Belt => {
+                // dragging belts points the previous belt towards the one under the cursor
+                let direction = match *belt_drag {
+                    Some((last_pos, last_direction)) if last_pos == tile_pos => last_direction,
+                    Some((last_pos, _)) => {
+                        match belt_dir_between(tile_pos, last_pos).and_then(|t| t.belt_direction())
+                        {
+                            Some(direction) => {
+                                build_events.send(BuildRequestedEvent {
+                                    building_type: BuildingType::Belt,
+                                    direction,
+                                    tile_pos: last_pos,
+                                });
+                                direction
+                            }
+                            None => build_tool.direction,
+                        }
+                    }
+                    None => build_tool.direction,
+                };
+
+                build_events.send(BuildRequestedEvent {
+                    building_type: BuildingType::Belt,
+                    direction,
+                    tile_pos,
+                });
+
+                *belt_drag = Some((tile_pos, direction));
+            }
+
             Tool::Build(build_tool) => {
                 build_events.send(build_tool.request_at(tile_pos));
             }
 
-            Tool::Buldozer => {
-                demolish_events.send(DemolishEvent { tile_pos });
+            Tool::Blueprint(blueprint) if input.just_pressed(Action::Primary) => {
+                for request in blueprint.requests_at(tile_pos) {
+                    build_events.send(request);
+                }
+            }
+
+            Tool::None if input.just_pressed(Action::Primary) => {
+                select_events.send(SelectEvent { tile_pos });
             }
 
             _ => {}
@@ -51,60 +119,176 @@ pub fn handle_mouse_input(
     }
 }
 
+#[derive(Resource, Default, Debug)]
+pub struct AreaSelection {
+    pub start: Option<TilePos>,
+    pub filter: DemolishFilter,
+}
+
+impl AreaSelection {
+    pub fn area(&self, cursor: TilePos) -> TileArea {
+        TileArea::from_corners(self.start.unwrap_or(cursor), cursor)
+    }
+}
+
+fn demolish_filter(input: &ActionInput) -> DemolishFilter {
+    if input.pressed(Action::DemolishBeltsOnly) {
+        DemolishFilter::BeltsOnly
+    } else if input.pressed(Action::DemolishBuildingsOnly) {
+        DemolishFilter::BuildingsOnly
+    } else {
+        DemolishFilter::All
+    }
+}
+
+// dragging with the bulldozer demolishes an area, with the copy tool it creates a blueprint
+pub fn handle_area_selection(
+    input: ActionInput,
+    cursor_pos: Res<GameCursor>,
+    map_interaction: Res<MapInteraction>,
+    selected_tool: Res<Tool>,
+    mut selection: ResMut<AreaSelection>,
+    mut demolish_events: EventWriter<DemolishAreaEvent>,
+    mut copy_events: EventWriter<CopyAreaEvent>,
+) {
+    if !matches!(*selected_tool, Tool::Buldozer | Tool::Copy) {
+        if selection.start.is_some() {
+            selection.start = None;
+        }
+        return;
+    }
+
+    let filter = demolish_filter(&input);
+    if selection.filter != filter {
+        selection.filter = filter;
+    }
+
+    let Some(tile_pos) = cursor_pos.tile_pos else {
+        return;
+    };
+
+    if input.just_pressed(Action::Primary) && map_interaction.is_allowed() {
+        selection.start = Some(tile_pos);
+    }
+
+    if input.just_released(Action::Primary) && selection.start.is_some() {
+        let area = selection.area(tile_pos);
+
+        match *selected_tool {
+            Tool::Copy => copy_events.send(CopyAreaEvent { area }),
+            _ => demolish_events.send(DemolishAreaEvent { area, filter }),
+        }
+
+        selection.start = None;
+    }
+}
+
 pub fn handle_keyboard_input(
-    mut key_events: EventReader<KeyboardInput>,
+    input: ActionInput,
     mut map_events: EventWriter<MapEvent>,
+    mut ui_events: EventWriter<UiEvent>,
+    mut history_events: EventWriter<HistoryEvent>,
     mut selected_tool: ResMut<Tool>,
 ) {
-    for event in key_events.iter() {
-        match event {
-            KeyboardInput {
-                state: ButtonState::Pressed,
-                key_code: Some(KeyCode::G),
-                ..
-            } => map_events.send(MapEvent::ToggleGrid),
-
-            KeyboardInput {
-                state: ButtonState::Pressed,
-                key_code: Some(KeyCode::C),
-                ..
-            } => map_events.send(MapEvent::ClearBuildings),
-
-            KeyboardInput {
-                state: ButtonState::Pressed,
-                key_code: Some(KeyCode::M),
-                ..
-            } => {
-                *selected_tool = Tool::Build(BuildTool {
-                    building: BuildingType::Mine,
-                    direction: default(),
-                })
-            }
+    if input.just_pressed(Action::Undo) {
+        history_events.send(HistoryEvent::Undo);
+    }
 
-            KeyboardInput {
-                state: ButtonState::Pressed,
-                key_code: Some(KeyCode::B),
-                ..
-            } => {
-                *selected_tool = Tool::Build(BuildTool {
-                    building: BuildingType::Belt,
-                    direction: default(),
-                })
-            }
+    if input.just_pressed(Action::Redo) {
+        history_events.send(HistoryEvent::Redo);
+    }
 
-            KeyboardInput {
-                state: ButtonState::Pressed,
-                key_code: Some(KeyCode::D),
-                ..
-            } => *selected_tool = Tool::Buldozer,
+    if input.just_pressed(Action::ToggleGrid) {
+        map_events.send(MapEvent::ToggleGrid);
+    }
 
-            KeyboardInput {
-                state: ButtonState::Pressed,
-                key_code: Some(KeyCode::R),
-                ..
-            } => selected_tool.rotate(),
+    if input.just_pressed(Action::ClearBuildings) {
+        map_events.send(MapEvent::ClearBuildings);
+    }
 
-            _ => {}
+    if input.just_pressed(Action::ToggleStatistics) {
+        ui_events.send(UiEvent::ToggleStatistics);
+    }
+
+    for (action, building) in [
+        (Action::SelectBelt, BuildingType::Belt),
+        (Action::SelectMine, BuildingType::Mine),
+        (Action::SelectChest, BuildingType::Chest),
+    ] {
+        if input.just_pressed(action) {
+            *selected_tool = Tool::Build(BuildTool {
+                building,
+                direction: default(),
+            });
         }
     }
+
+    if input.just_pressed(Action::SelectBulldozer) {
+        *selected_tool = Tool::Buldozer;
+    }
+
+    if input.just_pressed(Action::SelectBlueprint) {
+        *selected_tool = Tool::Copy;
+    }
+
+    if input.just_pressed(Action::Rotate) {
+        selected_tool.rotate();
+    }
+}
+
+pub fn handle_simulation_input(input: ActionInput, mut simulation: ResMut<Simulation>) {
+    if input.just_pressed(Action::TogglePause) {
+        simulation.paused = !simulation.paused;
+    }
+
+    if input.just_pressed(Action::StepSimulation) {
+        simulation.step();
+    }
+
+    if input.just_pressed(Action::SpeedUp) {
+        simulation.speed = simulation.speed.faster();
+    }
+
+    if input.just_pressed(Action::SlowDown) {
+        simulation.speed = simulation.speed.slower();
+    }
+}
+
+// copies the building under the cursor into the build tool
+pub fn handle_pipette(
+    input: ActionInput,
+    cursor_pos: Res<GameCursor>,
+    building_tiles: Query<(&BuildingTile, &TileTextureIndex)>,
+    buildings: Query<(&BuildingType, &MapDirection)>,
+    building_layer: Query<&TileStorage, With<BuildingLayer>>,
+    mut selected_tool: ResMut<Tool>,
+) {
+    if !input.just_pressed(Action::Pipette) {
+        return;
+    }
+
+    let (Some(tile_pos), Ok(building_layer)) = (cursor_pos.tile_pos, building_layer.get_single())
+    else {
+        return;
+    };
+
+    let Some((building_tile, tile_texture)) = building_layer
+        .checked_get(&tile_pos)
+        .and_then(|tile| building_tiles.get(tile).ok())
+    else {
+        return;
+    };
+
+    let Ok((building_type, building_direction)) = buildings.get(building_tile.building) else {
+        return;
+    };
+
+    let direction = BuildingTileType::from(*tile_texture)
+        .belt_direction()
+        .unwrap_or(*building_direction);
+
+    *selected_tool = Tool::Build(BuildTool {
+        building: *building_type,
+        direction,
+    });
 }
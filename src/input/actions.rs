@@ -0,0 +1,308 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::settings::config_path;
+
+const CONTROLS_FILE: &str = "controls.ron";
+
+const MODIFIER_KEYS: [KeyCode; 4] = [
+    KeyCode::ControlLeft,
+    KeyCode::ControlRight,
+    KeyCode::ShiftLeft,
+    KeyCode::ShiftRight,
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Action {
+    Primary,
+    CameraUp,
+    CameraDown,
+    CameraLeft,
+    CameraRight,
+    CameraPan,
+    ToggleGrid,
+    ClearBuildings,
+    ToggleStatistics,
+    SelectBelt,
+    SelectMine,
+    SelectChest,
+    SelectBulldozer,
+    SelectBlueprint,
+    Rotate,
+    Pipette,
+    Undo,
+    Redo,
+    DemolishBeltsOnly,
+    DemolishBuildingsOnly,
+    SaveBlueprint,
+    LoadBlueprint,
+    ExportBlueprint,
+    ImportBlueprint,
+    TogglePause,
+    StepSimulation,
+    SpeedUp,
+    SlowDown,
+    Menu,
+    ToggleDebugOverlay,
+    ToggleConsole,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Button {
+    Key(KeyCode),
+    Mouse(MouseButton),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Chord {
+    #[serde(default)]
+    pub ctrl: bool,
+    #[serde(default)]
+    pub shift: bool,
+    pub button: Button,
+}
+
+impl Chord {
+    fn key(key: KeyCode) -> Self {
+        Chord {
+            ctrl: false,
+            shift: false,
+            button: Button::Key(key),
+        }
+    }
+
+    fn ctrl(key: KeyCode) -> Self {
+        Chord {
+            ctrl: true,
+            ..Chord::key(key)
+        }
+    }
+
+    fn mouse(button: MouseButton) -> Self {
+        Chord {
+            ctrl: false,
+            shift: false,
+            button: Button::Mouse(button),
+        }
+    }
+
+    // keys must match control exactly so Z and ctrl+Z don't trigger each other,
+    // mouse buttons and modifier keys only need the required modifiers
+    fn modifiers_match(&self, keyboard: &Input<KeyCode>) -> bool {
+        let ctrl = keyboard.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+        let shift = keyboard.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+
+        let is_exact = matches!(self.button, Button::Key(key) if !MODIFIER_KEYS.contains(&key));
+
+        (!self.ctrl || ctrl) && (!self.shift || shift) && (!is_exact || self.ctrl == ctrl)
+    }
+
+    pub fn label(&self) -> String {
+        let button = match self.button {
+            Button::Key(key) => format!("{key:?}"),
+            Button::Mouse(MouseButton::Other(button)) => format!("mouse {button}"),
+            Button::Mouse(button) => format!("mouse {button:?}"),
+        };
+
+        let mut label = String::new();
+
+        if self.ctrl {
+            label.push_str("ctrl+");
+        }
+        if self.shift {
+            label.push_str("shift+");
+        }
+
+        label.push_str(&button.to_uppercase());
+        label
+    }
+}
+
+// button names as in controls.ron, e.g. ctrl+Z, shift+mouse Left or Space
+impl FromStr for Chord {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut chord = Chord::key(KeyCode::Escape);
+        let mut rest = s;
+
+        loop {
+            if let Some(after) = rest.strip_prefix("ctrl+") {
+                chord.ctrl = true;
+                rest = after;
+            } else if let Some(after) = rest.strip_prefix("shift+") {
+                chord.shift = true;
+                rest = after;
+            } else {
+                break;
+            }
+        }
+
+        chord.button = match rest.strip_prefix("mouse ") {
+            Some(button) => Button::Mouse(ron::from_str(button)?),
+            None => Button::Key(ron::from_str(rest)?),
+        };
+
+        Ok(chord)
+    }
+}
+
+#[derive(Resource, Clone, Serialize, Deserialize)]
+pub struct ActionMap {
+    bindings: BTreeMap<Action, Vec<Chord>>,
+}
+
+impl Default for ActionMap {
+    fn default() -> Self {
+        use Action::*;
+
+        let bindings = [
+            (Primary, vec![Chord::mouse(MouseButton::Left)]),
+            (CameraUp, vec![Chord::key(KeyCode::Up)]),
+            (CameraDown, vec![Chord::key(KeyCode::Down)]),
+            (CameraLeft, vec![Chord::key(KeyCode::Left)]),
+            (CameraRight, vec![Chord::key(KeyCode::Right)]),
+            (CameraPan, vec![Chord::mouse(MouseButton::Middle)]),
+            (ToggleGrid, vec![Chord::key(KeyCode::G)]),
+            (ClearBuildings, vec![Chord::key(KeyCode::C)]),
+            (ToggleStatistics, vec![Chord::key(KeyCode::P)]),
+            (SelectBelt, vec![Chord::key(KeyCode::B)]),
+            (SelectMine, vec![Chord::key(KeyCode::M)]),
+            (SelectChest, vec![Chord::key(KeyCode::H)]),
+            (SelectBulldozer, vec![Chord::key(KeyCode::D)]),
+            (SelectBlueprint, vec![Chord::key(KeyCode::U)]),
+            (Rotate, vec![Chord::key(KeyCode::R)]),
+            (Pipette, vec![Chord::key(KeyCode::Q)]),
+            (Undo, vec![Chord::ctrl(KeyCode::Z)]),
+            (Redo, vec![Chord::ctrl(KeyCode::Y)]),
+            (
+                DemolishBeltsOnly,
+                vec![
+                    Chord::key(KeyCode::ShiftLeft),
+                    Chord::key(KeyCode::ShiftRight),
+                ],
+            ),
+            (
+                DemolishBuildingsOnly,
+                vec![
+                    Chord::key(KeyCode::ControlLeft),
+                    Chord::key(KeyCode::ControlRight),
+                ],
+            ),
+            (SaveBlueprint, vec![Chord::key(KeyCode::S)]),
+            (LoadBlueprint, vec![Chord::key(KeyCode::L)]),
+            (ExportBlueprint, vec![Chord::ctrl(KeyCode::E)]),
+            (ImportBlueprint, vec![Chord::ctrl(KeyCode::I)]),
+            (TogglePause, vec![Chord::key(KeyCode::Space)]),
+            (StepSimulation, vec![Chord::key(KeyCode::Period)]),
+            (SpeedUp, vec![Chord::key(KeyCode::Equals)]),
+            (SlowDown, vec![Chord::key(KeyCode::Minus)]),
+            (Menu, vec![Chord::key(KeyCode::Escape)]),
+            (ToggleDebugOverlay, vec![Chord::key(KeyCode::F3)]),
+            (ToggleConsole, vec![Chord::key(KeyCode::Grave)]),
+        ];
+
+        ActionMap {
+            bindings: bindings.into_iter().collect(),
+        }
+    }
+}
+
+impl ActionMap {
+    fn path() -> PathBuf {
+        config_path(CONTROLS_FILE)
+    }
+
+    // actions missing from the config file keep their default bindings,
+    // nothing is written until a rebinding is saved
+    pub fn load() -> Self {
+        let mut action_map = ActionMap::default();
+
+        let path = Self::path();
+
+        let Ok(contents) = fs::read_to_string(&path) else {
+            return action_map;
+        };
+
+        match ron::from_str::<ActionMap>(&contents) {
+            Ok(loaded) => action_map.bindings.extend(loaded.bindings),
+            Err(e) => error!("invalid controls in {}: {}", path.display(), e),
+        }
+
+        action_map
+    }
+
+    // the whole map is written, defaults included, so the file lists every action
+    pub fn save(&self) -> anyhow::Result<()> {
+        let path = Self::path();
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let contents = ron::ser::to_string_pretty(self, Default::default())?;
+        fs::write(path, contents)?;
+        Ok(())
+    }
+
+    pub fn bind(&mut self, action: Action, chord: Chord) {
+        self.bindings.insert(action, vec![chord]);
+    }
+
+    pub fn chords(&self, action: Action) -> &[Chord] {
+        self.bindings.get(&action).map_or(&[], Vec::as_slice)
+    }
+
+    // label of the first chord bound to the action
+    pub fn label(&self, action: Action) -> String {
+        self.chords(action)
+            .first()
+            .map_or_else(|| String::from("-"), Chord::label)
+    }
+}
+
+#[derive(SystemParam)]
+pub struct ActionInput<'w> {
+    actions: Res<'w, ActionMap>,
+    keyboard: Res<'w, Input<KeyCode>>,
+    mouse: Res<'w, Input<MouseButton>>,
+}
+
+impl ActionInput<'_> {
+    pub fn pressed(&self, action: Action) -> bool {
+        self.actions.chords(action).iter().any(|chord| {
+            chord.modifiers_match(&self.keyboard)
+                && match chord.button {
+                    Button::Key(key) => self.keyboard.pressed(key),
+                    Button::Mouse(button) => self.mouse.pressed(button),
+                }
+        })
+    }
+
+    pub fn just_pressed(&self, action: Action) -> bool {
+        self.actions.chords(action).iter().any(|chord| {
+            chord.modifiers_match(&self.keyboard)
+                && match chord.button {
+                    Button::Key(key) => self.keyboard.just_pressed(key),
+                    Button::Mouse(button) => self.mouse.just_pressed(button),
+                }
+        })
+    }
+
+    // modifiers are ignored, they may be let go before the button
+    pub fn just_released(&self, action: Action) -> bool {
+        self.actions
+            .chords(action)
+            .iter()
+            .any(|chord| match chord.button {
+                Button::Key(key) => self.keyboard.just_released(key),
+                Button::Mouse(button) => self.mouse.just_released(button),
+            })
+    }
+}
@@ -0,0 +1,103 @@
+#![feature(let_chains)]
+
+use bevy::app::PluginGroupBuilder;
+use bevy::prelude::*;
+use bevy_ecs_tilemap::TilemapPlugin;
+
+mod belts;
+mod bench;
+mod blueprints;
+mod build_mode;
+mod buildings;
+mod camera;
+mod conservation;
+mod direction;
+mod game;
+mod grid;
+mod history;
+mod input;
+mod map;
+mod mods;
+mod multiplayer;
+mod render;
+mod replay;
+mod save;
+mod settings;
+mod simulation;
+mod snapshot;
+mod statistics;
+mod ui;
+
+// the public api, everything else is free to change
+pub use crate::belts::{
+    belt_dir_between, Belt, Inventory, InventoryEvent, Item, ItemType, SpawnItemEvent,
+};
+pub use crate::bench::{
+    run_benchmark, BenchmarkReport, Scenario, SystemTiming, UnknownScenario, BENCHMARK_TICKS,
+};
+pub use crate::blueprints::encoding::{
+    decode, encode, BlueprintDecodeError, BLUEPRINT_VERSION, MAX_BLUEPRINT_BYTES,
+};
+pub use crate::blueprints::{Blueprint, BlueprintEntry, BlueprintPlugin};
+pub use crate::build_mode::{BuildMode, BuildModePlugin};
+pub use crate::buildings::templates::BuildingRegistry;
+pub use crate::buildings::{
+    BuildRequestedEvent, BuildTool, Building, BuildingChangedEvent, BuildingLayout, BuildingTile,
+    BuildingType, DemolishAreaEvent, DemolishEvent, DemolishFilter, Tool,
+};
+pub use crate::camera::CameraPlugin;
+pub use crate::conservation::{ItemConservation, ItemViolation};
+pub use crate::direction::MapDirection;
+pub use crate::game::{GamePlugin, GameState};
+pub use crate::grid::GridPlugin;
+pub use crate::history::{HistoryEvent, HistoryPlugin};
+pub use crate::input::{ActionMap, InputPlugin};
+pub use crate::map::{
+    BuildingLayer, BuildingTileType, MapEvent, Terrain, TerrainType, TileArea, TILEMAP_SIZE,
+};
+pub use crate::mods::registry::{ModBuilding, ModName, ModRegistry};
+pub use crate::mods::{ModPlugin, Mods};
+pub use crate::multiplayer::desync::DesyncReport;
+pub use crate::multiplayer::{Lockstep, MultiplayerPlugin, DEFAULT_PORT};
+pub use crate::render::MapRenderPlugin;
+pub use crate::replay::{
+    Replay, ReplayFrame, ReplayInput, ReplayPlayback, ReplayPlugin, ReplayRecorder, LAST_REPLAY,
+};
+pub use crate::save::SavePlugin;
+pub use crate::settings::{Settings, SettingsPlugin};
+pub use crate::simulation::{
+    run_ticks, BuildSet, Simulation, SimulationPlugin, SimulationSet, SimulationSpeed,
+    SimulationTick, TICK,
+};
+pub use crate::snapshot::{capture_belt_items, MapSnapshot};
+pub use crate::statistics::{
+    FlowHistory, ItemFlow, ItemFlowEvent, ItemHistory, ProductionStatistics, StatisticsPlugin,
+    StatisticsWindow, SAMPLES,
+};
+pub use crate::ui::UiPlugin;
+
+// the whole game on top of DefaultPlugins, without the window and asset settings of the binary
+pub struct GamePlugins;
+
+impl PluginGroup for GamePlugins {
+    fn build(self) -> PluginGroupBuilder {
+        PluginGroupBuilder::start::<Self>()
+            .add(SimulationPlugin)
+            .add(TilemapPlugin)
+            .add(MapRenderPlugin)
+            .add(CameraPlugin)
+            .add(UiPlugin)
+            .add(InputPlugin)
+            .add(GridPlugin)
+            .add(BuildModePlugin)
+            .add(StatisticsPlugin)
+            .add(HistoryPlugin)
+            .add(BlueprintPlugin)
+            .add(ModPlugin)
+            .add(GamePlugin)
+            .add(SavePlugin)
+            .add(ReplayPlugin)
+            .add(MultiplayerPlugin)
+            .add(SettingsPlugin)
+    }
+}
@@ -1,41 +1,35 @@
-#![feature(let_chains)]
-
 use std::time::Duration;
+use std::{env, process};
 
+use bevactorio::{
+    run_benchmark, BuildingRegistry, GamePlugins, Lockstep, Scenario, BENCHMARK_TICKS, DEFAULT_PORT,
+};
 use bevy::asset::ChangeWatcher;
 use bevy::diagnostic::{FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin};
 use bevy::prelude::*;
-use bevy_ecs_tilemap::TilemapPlugin;
-use build_mode::BuildMode;
-
-use crate::belts::{input_from_belts, move_items_on_belts};
-use crate::build_mode::BuildModePlugin;
-use crate::buildings::mine::mine_produce;
-use crate::buildings::templates::loader::BuildingTemplateLoader;
-use crate::buildings::templates::{
-    load_building_templates, register_building_templates, BuildingRegistry, BuildingTemplate,
-};
-use crate::buildings::Tool;
-use crate::camera::{camera_movement, MainCamera, Zoom};
-use crate::grid::GridPlugin;
-use crate::input::InputPlugin;
-use crate::map::{init_map, MapEvent};
-use crate::ui::UiPlugin;
-
-mod belts;
-mod build_mode;
-mod buildings;
-mod camera;
-mod direction;
-mod grid;
-mod input;
-mod map;
-mod ui;
 
 fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    if args.first().is_some_and(|arg| arg == "--bench") {
+        if let Err(e) = benchmark(&args[1..]) {
+            eprintln!("{e}");
+            process::exit(1);
+        }
+        return;
+    }
+
+    let lockstep = match multiplayer(&args) {
+        Ok(lockstep) => lockstep,
+        Err(e) => {
+            eprintln!("{e}");
+            process::exit(1);
+        }
+    };
+
+    // resolution and window mode follow once the settings are loaded on startup
     let window_settings = WindowPlugin {
         primary_window: Some(Window {
-            resolution: (1270., 720.).into(),
             title: String::from("Bevactorio"),
             ..default()
         }),
@@ -48,42 +42,79 @@ fn main() {
         ..default()
     };
 
-    App::new()
-        .add_plugins((
-            DefaultPlugins
-                .set(window_settings)
-                .set(asset_settings)
-                .set(ImagePlugin::default_nearest()),
-            LogDiagnosticsPlugin::default(),
-            FrameTimeDiagnosticsPlugin::default(),
-            TilemapPlugin,
-            UiPlugin,
-            InputPlugin,
-            GridPlugin,
-            BuildModePlugin,
-        ))
-        .add_asset::<BuildingTemplate>()
-        .add_asset_loader(BuildingTemplateLoader)
-        .init_resource::<Tool>()
-        .init_resource::<BuildingRegistry>()
-        .init_resource::<Zoom>()
-        .add_event::<MapEvent>()
-        .add_systems(Startup, (startup, init_map, load_building_templates))
-        .add_systems(
-            Update,
-            (
-                register_building_templates,
-                camera_movement,
-                mine_produce.before(move_items_on_belts),
-                move_items_on_belts,
-                input_from_belts.after(move_items_on_belts),
-            ),
-        )
-        .run();
+    let mut app = App::new();
+
+    app.add_plugins((
+        DefaultPlugins
+            .set(window_settings)
+            .set(asset_settings)
+            .set(ImagePlugin::default_nearest()),
+        LogDiagnosticsPlugin::default(),
+        FrameTimeDiagnosticsPlugin::default(),
+        GamePlugins,
+    ));
+
+    if let Some(lockstep) = lockstep {
+        app.insert_resource(lockstep);
+    }
+
+    app.run();
+}
+
+// `--host [port]` lets players join from the main menu until the game starts,
+// `--join <host[:port]>` plays on the factory of the host
+fn multiplayer(args: &[String]) -> anyhow::Result<Option<Lockstep>> {
+    match args.first().map(String::as_str) {
+        Some("--host") => {
+            let port = match args.get(1) {
+                Some(port) => port
+                    .parse()
+                    .map_err(|_| anyhow::anyhow!("invalid port {port}"))?,
+                None => DEFAULT_PORT,
+            };
+
+            let lockstep = Lockstep::host(port)
+                .map_err(|e| anyhow::anyhow!("failed to host on port {port}: {e}"))?;
+            println!("waiting for players on port {port}");
+            Ok(Some(lockstep))
+        }
+        Some("--join") => {
+            let host = args
+                .get(1)
+                .ok_or_else(|| anyhow::anyhow!("--join needs the address of the host"))?;
+
+            let addr = match host.contains(':') {
+                true => host.clone(),
+                false => format!("{host}:{DEFAULT_PORT}"),
+            };
+
+            let lockstep =
+                Lockstep::join(&addr).map_err(|e| anyhow::anyhow!("failed to join {addr}: {e}"))?;
+            Ok(Some(lockstep))
+        }
+        _ => Ok(None),
+    }
 }
 
-fn startup(mut commands: Commands, mut next_state: ResMut<NextState<BuildMode>>) {
-    commands.spawn(Camera2dBundle::default()).insert(MainCamera);
+// `--bench [scenario...]` runs the given scenarios headlessly, or all of them
+fn benchmark(names: &[String]) -> anyhow::Result<()> {
+    let scenarios = match names.is_empty() {
+        true => Scenario::ALL.to_vec(),
+        false => names
+            .iter()
+            .map(|name| {
+                name.parse().map_err(|_| {
+                    let known: Vec<_> = Scenario::ALL.iter().map(|s| s.as_str()).collect();
+                    anyhow::anyhow!("unknown scenario {name}, known scenarios {known:?}")
+                })
+            })
+            .collect::<anyhow::Result<_>>()?,
+    };
+
+    for scenario in scenarios {
+        let registry = BuildingRegistry::load_dir("assets/buildings")?;
+        println!("{}", run_benchmark(scenario, registry, BENCHMARK_TICKS));
+    }
 
-    next_state.set(BuildMode::Enabled);
+    Ok(())
 }
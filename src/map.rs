@@ -1,7 +1,10 @@
 use bevy::prelude::*;
 use bevy_ecs_tilemap::prelude::*;
+use serde::{Deserialize, Serialize};
 
-use crate::buildings::Building;
+use crate::belts::Inventory;
+use crate::buildings::{Building, BuildingChangedEvent, BuildingType};
+use crate::direction::MapDirection;
 
 #[derive(Component)]
 pub struct TerrainLayer;
@@ -12,13 +15,87 @@ pub struct BuildingLayer;
 #[derive(Component)]
 pub struct BuildGuideLayer;
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 #[repr(u32)]
 pub enum TerrainType {
     Grass = 0,
+    Rock = 1,
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+// size of the noise cells rock grows in, and how much of the map ends up rock
+const ROCK_CELL_SIZE: u32 = 8;
+const ROCK_THRESHOLD: f32 = 0.65;
+// tiles around the centre, where the camera starts, are always free to build on
+const CLEAR_RADIUS: i32 = 10;
+
+// the ground under the buildings, the same seed always gives the same map
+#[derive(Resource, Clone, Debug, PartialEq, Eq)]
+pub struct Terrain {
+    tiles: Vec<TerrainType>,
+}
+
+impl Default for Terrain {
+    fn default() -> Self {
+        Terrain {
+            tiles: vec![TerrainType::Grass; (TILEMAP_SIZE.x * TILEMAP_SIZE.y) as usize],
+        }
+    }
+}
+
+impl Terrain {
+    pub fn generate(seed: u64) -> Self {
+        // value noise, random corners of each cell blended across it
+        let corner = |x: u32, y: u32| {
+            let mut hash = seed ^ ((x as u64) << 32 | y as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+            hash = (hash ^ (hash >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+            hash = (hash ^ (hash >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+            (hash ^ (hash >> 31)) as f32 / u64::MAX as f32
+        };
+
+        let smooth = |t: f32| t * t * (3. - 2. * t);
+        let centre = TilePos::new(TILEMAP_SIZE.x / 2, TILEMAP_SIZE.y / 2);
+
+        let tiles = (0..TILEMAP_SIZE.y)
+            .flat_map(|y| (0..TILEMAP_SIZE.x).map(move |x| TilePos::new(x, y)))
+            .map(|tile_pos| {
+                let dx = tile_pos.x as i32 - centre.x as i32;
+                let dy = tile_pos.y as i32 - centre.y as i32;
+
+                if dx * dx + dy * dy <= CLEAR_RADIUS * CLEAR_RADIUS {
+                    return TerrainType::Grass;
+                }
+
+                let (cx, cy) = (tile_pos.x / ROCK_CELL_SIZE, tile_pos.y / ROCK_CELL_SIZE);
+                let tx = smooth((tile_pos.x % ROCK_CELL_SIZE) as f32 / ROCK_CELL_SIZE as f32);
+                let ty = smooth((tile_pos.y % ROCK_CELL_SIZE) as f32 / ROCK_CELL_SIZE as f32);
+
+                let bottom = corner(cx, cy) * (1. - tx) + corner(cx + 1, cy) * tx;
+                let top = corner(cx, cy + 1) * (1. - tx) + corner(cx + 1, cy + 1) * tx;
+
+                match bottom * (1. - ty) + top * ty > ROCK_THRESHOLD {
+                    true => TerrainType::Rock,
+                    false => TerrainType::Grass,
+                }
+            })
+            .collect();
+
+        Terrain { tiles }
+    }
+
+    // outside the map is rock, nothing can be built there either
+    pub fn get(&self, tile_pos: TilePos) -> TerrainType {
+        match tile_pos.within_map_bounds(&TILEMAP_SIZE) {
+            true => self.tiles[tile_pos.to_index(&TILEMAP_SIZE)],
+            false => TerrainType::Rock,
+        }
+    }
+
+    pub fn is_buildable(&self, tile_pos: TilePos) -> bool {
+        self.get(tile_pos) == TerrainType::Grass
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 #[repr(u32)]
 #[allow(dead_code)]
 pub enum BuildingTileType {
@@ -43,6 +120,60 @@ impl BuildingTileType {
         )
     }
 
+    pub fn belt(direction: MapDirection) -> Self {
+        match direction {
+            MapDirection::Up => Self::BeltUp,
+            MapDirection::Down => Self::BeltDown,
+            MapDirection::Left => Self::BeltLeft,
+            MapDirection::Right => Self::BeltRight,
+        }
+    }
+
+    pub fn belt_direction(&self) -> Option<MapDirection> {
+        match self {
+            Self::BeltUp => Some(MapDirection::Up),
+            Self::BeltDown => Some(MapDirection::Down),
+            Self::BeltLeft => Some(MapDirection::Left),
+            Self::BeltRight => Some(MapDirection::Right),
+            _ => None,
+        }
+    }
+
+    // belts point where they carry items to, a mine reads MI over NE
+    pub fn as_char(&self) -> char {
+        match self {
+            Self::BeltUp => '^',
+            Self::BeltDown => 'v',
+            Self::BeltLeft => '<',
+            Self::BeltRight => '>',
+            Self::MineTopLeft => 'M',
+            Self::MineTopRight => 'I',
+            Self::MineBottomLeft => 'N',
+            Self::MineBottomRight => 'E',
+            Self::Explosion => '*',
+            Self::Chest => 'C',
+            Self::Unknown => '?',
+        }
+    }
+
+    pub fn from_char(c: char) -> Option<Self> {
+        let tile_type = match c {
+            '^' => Self::BeltUp,
+            'v' => Self::BeltDown,
+            '<' => Self::BeltLeft,
+            '>' => Self::BeltRight,
+            'M' => Self::MineTopLeft,
+            'I' => Self::MineTopRight,
+            'N' => Self::MineBottomLeft,
+            'E' => Self::MineBottomRight,
+            '*' => Self::Explosion,
+            'C' => Self::Chest,
+            _ => return None,
+        };
+
+        Some(tile_type)
+    }
+
     pub fn next_belt_pos(&self, TilePos { x, y }: TilePos) -> Option<TilePos> {
         use BuildingTileType::*;
         let next_belt_pos = match self {
@@ -128,80 +259,61 @@ pub fn to_tile_pos(
     Some(TilePos::new(x as u32, y as u32))
 }
 
-pub const TILE_SIZE: TilemapTileSize = TilemapTileSize { x: 16., y: 16. };
-pub const TILEMAP_SIZE: TilemapSize = TilemapSize { x: 64, y: 64 };
+// world position of the centre of a tile, the other way around than to_tile_pos
+pub fn tile_center(tile_pos: TilePos, map_transform: &Transform) -> Vec2 {
+    map_transform.translation.truncate()
+        + Vec2::new(
+            tile_pos.x as f32 * TILE_SIZE.x,
+            tile_pos.y as f32 * TILE_SIZE.y,
+        )
+}
 
-pub const GRID_SIZE: TilemapGridSize = TilemapGridSize { x: 16., y: 16. };
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TileArea {
+    pub min: TilePos,
+    pub max: TilePos,
+}
 
-pub fn init_map(mut commands: Commands, asset_server: Res<AssetServer>) {
-    let terrain_texture = asset_server.load("tilesets/terrain.png");
-    let buildings_texture = asset_server.load("tilesets/buildings.png");
+impl TileArea {
+    pub fn from_corners(a: TilePos, b: TilePos) -> Self {
+        TileArea {
+            min: TilePos::new(a.x.min(b.x), a.y.min(b.y)),
+            max: TilePos::new(a.x.max(b.x), a.y.max(b.y)),
+        }
+    }
 
-    // Terrain layer
+    pub fn iter(&self) -> impl Iterator<Item = TilePos> {
+        let TileArea { min, max } = *self;
 
-    let mut terrain_storage = TileStorage::empty(TILEMAP_SIZE);
-    let terrain_tilemap = commands.spawn(TerrainLayer).id();
+        (min.y..=max.y).flat_map(move |y| (min.x..=max.x).map(move |x| TilePos::new(x, y)))
+    }
+}
 
-    bevy_ecs_tilemap::helpers::filling::fill_tilemap(
-        TileTextureIndex(0),
+pub const TILE_SIZE: TilemapTileSize = TilemapTileSize { x: 16., y: 16. };
+pub const TILEMAP_SIZE: TilemapSize = TilemapSize { x: 64, y: 64 };
+
+pub const GRID_SIZE: TilemapGridSize = TilemapGridSize { x: 16., y: 16. };
+
+// only the storage of the building layer, textures are attached by the render plugin
+pub fn init_map(mut commands: Commands) {
+    commands.spawn((
+        BuildingLayer,
+        TileStorage::empty(TILEMAP_SIZE),
         TILEMAP_SIZE,
-        TilemapId(terrain_tilemap),
-        &mut commands,
-        &mut terrain_storage,
-    );
-
-    commands.entity(terrain_tilemap).insert(TilemapBundle {
-        grid_size: GRID_SIZE,
-        size: TILEMAP_SIZE,
-        storage: terrain_storage,
-        texture: TilemapTexture::Single(terrain_texture),
-        tile_size: TILE_SIZE,
-        transform: bevy_ecs_tilemap::helpers::geometry::get_tilemap_center_transform(
-            &TILEMAP_SIZE,
-            &GRID_SIZE,
-            &TilemapType::Square,
-            0.0,
-        ),
-        ..default()
-    });
-
-    // Building Layer
-
-    commands
-        .spawn(TilemapBundle {
-            grid_size: GRID_SIZE,
-            size: TILEMAP_SIZE,
-            storage: TileStorage::empty(TILEMAP_SIZE),
-            texture: TilemapTexture::Single(buildings_texture.clone()),
-            tile_size: TILE_SIZE,
-            transform: bevy_ecs_tilemap::helpers::geometry::get_tilemap_center_transform(
-                &TILEMAP_SIZE,
-                &GRID_SIZE,
-                &TilemapType::Square,
-                1.0,
-            ),
-            ..default()
-        })
-        .insert(BuildingLayer);
-
-    // Build guide layer
-
-    commands
-        .spawn(TilemapBundle {
-            grid_size: GRID_SIZE,
-            size: TILEMAP_SIZE,
-            storage: TileStorage::empty(TILEMAP_SIZE),
-            texture: TilemapTexture::Single(buildings_texture),
-            tile_size: TILE_SIZE,
-            transform: bevy_ecs_tilemap::helpers::geometry::get_tilemap_center_transform(
-                &TILEMAP_SIZE,
-                &GRID_SIZE,
-                &TilemapType::Square,
-                2.0,
-            ),
-            ..default()
-        })
-        .insert(BuildGuideLayer);
+        TILE_SIZE,
+        GRID_SIZE,
+        TilemapType::Square,
+        TransformBundle::from_transform(map_transform(1.0)),
+    ));
+}
+
+pub fn map_transform(z: f32) -> Transform {
+    bevy_ecs_tilemap::helpers::geometry::get_tilemap_center_transform(
+        &TILEMAP_SIZE,
+        &GRID_SIZE,
+        &TilemapType::Square,
+        z,
+    )
 }
 
 #[derive(Event)]
@@ -212,21 +324,37 @@ pub enum MapEvent {
 
 pub fn clear_buildings(
     mut commands: Commands,
-    buildings: Query<(Entity, &Building)>,
+    buildings: Query<(
+        Entity,
+        &Building,
+        &BuildingType,
+        &TilePos,
+        &MapDirection,
+        Option<&Inventory>,
+    )>,
     mut building_tilemap: Query<&mut TileStorage, With<BuildingLayer>>,
+    mut changed_events: EventWriter<BuildingChangedEvent>,
 ) {
     let Ok(mut building_tilemap) = building_tilemap.get_single_mut() else {
-            error!("no building layer");
-            return;
-        };
+        error!("no building layer");
+        return;
+    };
 
-    for (building_entity, building) in buildings.iter() {
+    for (building_entity, building, building_type, origin, direction, inventory) in buildings.iter()
+    {
         for (entity, tile_pos, _) in &building.layout.tiles {
             commands.entity(*entity).despawn_recursive();
             building_tilemap.checked_remove(tile_pos);
         }
 
         commands.entity(building_entity).despawn();
+
+        changed_events.send(BuildingChangedEvent::Demolished {
+            building_type: *building_type,
+            origin: *origin,
+            direction: *direction,
+            inventory: inventory.cloned().map(Box::new),
+        });
     }
 }
 
@@ -261,7 +389,7 @@ impl TryFrom<TileTextureIndex> for TerrainType {
 
     fn try_from(tile: TileTextureIndex) -> Result<Self, ()> {
         match tile.0 {
-            x if x >= TerrainType::Grass as u32 && x <= TerrainType::Grass as u32 => {
+            x if x >= TerrainType::Grass as u32 && x <= TerrainType::Rock as u32 => {
                 Ok(unsafe { std::mem::transmute(x) })
             }
             _ => Err(()),
@@ -0,0 +1,647 @@
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use bevy::asset::LoadedAsset;
+use bevy::prelude::*;
+use bevy::reflect::{TypePath, TypeUuid};
+use bevy::utils::HashMap;
+use bevy_ecs_tilemap::prelude::*;
+use rhai::module_resolvers::DummyModuleResolver;
+use rhai::{Array, Dynamic, Engine, EvalAltResult, FuncArgs, Map, Scope, AST, INT};
+
+use crate::belts::{Inventory, InventoryEvent, ItemType, SpawnItemEvent};
+use crate::buildings::{
+    BuildRequestedEvent, Building, BuildingChangedEvent, BuildingType, DemolishEvent,
+};
+use crate::direction::MapDirection;
+use crate::game::GameState;
+use crate::map::TILEMAP_SIZE;
+use crate::multiplayer::Lockstep;
+use crate::replay::ReplayPlayback;
+use crate::simulation::{BuildSet, SimulationSet};
+
+use self::registry::{ModBuilding, ModName, ModRegistry};
+
+pub mod registry;
+
+// scripts in assets/mods, written in rhai, can
+//   add_button(label, callback) - at the top level, the callback is a function without arguments
+//   register_item(name), register_building(name, "chest"), register_building(name, "mine", item)
+//     - at the top level, new buildings work like the built-in one they name
+//   fn on_building_placed(building, x, y, direction) / fn on_building_removed(...)
+//   inventory(x, y) - slots of the building at the tile as #{item, amount} or (), () without one
+//   insert_item(x, y, item, amount), clear_slot(x, y, slot), clear_inventory(x, y)
+//   build(building, x, y, direction), demolish(x, y)
+
+// next to the building templates, so mods are hot reloaded together with them
+const MODS_PATH: &str = "mods";
+const MOD_EXTENSION: &str = "rhai";
+
+// a mod that loops forever is stopped instead of freezing the game
+const MAX_OPERATIONS: u64 = 1_000_000;
+
+pub struct ModPlugin;
+
+impl Plugin for ModPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<ModScript>()
+            .add_asset_loader(ModScriptLoader)
+            .init_resource::<ModHandles>()
+            .init_resource::<Mods>()
+            .add_systems(Startup, (load_mods, init_mod_buttons))
+            .add_systems(
+                Update,
+                (
+                    register_mods,
+                    register_mod_types
+                        .after(register_mods)
+                        .before(BuildSet)
+                        .run_if(resource_changed::<Mods>()),
+                    update_mod_buttons
+                        .after(register_mods)
+                        .run_if(resource_changed::<Mods>()),
+                    // a replay already contains everything the mods did
+                    (run_mod_callbacks, apply_mod_actions)
+                        .chain()
+                        .after(register_mods)
+                        .after(SimulationSet)
+                        .run_if(in_state(GameState::InGame))
+                        .run_if(not(resource_exists::<ReplayPlayback>())),
+                ),
+            );
+    }
+}
+
+#[derive(Debug, TypeUuid, TypePath)]
+#[uuid = "c28da411-d0b3-434f-a0d6-0e8c221a6769"]
+pub struct ModScript {
+    name: String,
+    source: String,
+}
+
+pub struct ModScriptLoader;
+
+impl bevy::asset::AssetLoader for ModScriptLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut bevy::asset::LoadContext,
+    ) -> bevy::asset::BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let path = load_context.path();
+
+            let script = ModScript {
+                name: path
+                    .file_stem()
+                    .and_then(|s| s.to_str())
+                    .unwrap_or_default()
+                    .to_string(),
+                source: String::from_utf8(bytes.to_vec())
+                    .map_err(|e| anyhow::anyhow!("{}: {e}", path.display()))?,
+            };
+
+            load_context.set_default_asset(LoadedAsset::new(script));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        static EXTENSIONS: &[&str] = &[MOD_EXTENSION];
+        EXTENSIONS
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct ModHandles(Vec<HandleUntyped>);
+
+enum ModAction {
+    Build(BuildRequestedEvent),
+    Demolish(DemolishEvent),
+    Insert(SpawnItemEvent),
+    Inventory(InventoryEvent),
+}
+
+type Slots = Vec<Option<(ItemType, usize)>>;
+
+// what the scripts see of the game and what they asked for, shared with the functions
+// registered in the engine
+#[derive(Default)]
+struct ModApi {
+    // taken before the callbacks run, so writes only show up in the next frame
+    inventories: HashMap<TilePos, Slots>,
+    actions: Vec<ModAction>,
+    buttons: Vec<ModButton>,
+    registry: ModRegistry,
+}
+
+#[derive(Component, Clone)]
+pub struct ModButton {
+    mod_name: String,
+    label: String,
+    callback: String,
+}
+
+struct LoadedMod {
+    name: String,
+    handle: Handle<ModScript>,
+    ast: AST,
+    buttons: Vec<ModButton>,
+}
+
+impl LoadedMod {
+    fn has_function(&self, name: &str, params: usize) -> bool {
+        self.ast
+            .iter_functions()
+            .any(|f| f.name == name && f.params.len() == params)
+    }
+}
+
+#[derive(Resource)]
+pub struct Mods {
+    engine: Engine,
+    api: Arc<Mutex<ModApi>>,
+    loaded: Vec<LoadedMod>,
+    // what loaded scripts registered, until the simulation takes it
+    registered: ModRegistry,
+}
+
+impl Default for Mods {
+    fn default() -> Self {
+        let api = Arc::new(Mutex::new(ModApi::default()));
+
+        Mods {
+            engine: sandboxed_engine(&api),
+            api,
+            loaded: Vec::new(),
+            registered: ModRegistry::default(),
+        }
+    }
+}
+
+impl Mods {
+    fn call(&self, loaded: &LoadedMod, name: &str, args: impl FuncArgs) {
+        if let Err(e) = self
+            .engine
+            .call_fn::<Dynamic>(&mut Scope::new(), &loaded.ast, name, args)
+        {
+            error!("mod {}: {name}: {e}", loaded.name);
+        }
+    }
+
+    // every mod defining the callback gets it, in the order of their names
+    fn call_all(&self, name: &str, args: impl FuncArgs + Clone, params: usize) {
+        for loaded in self.loaded.iter().filter(|m| m.has_function(name, params)) {
+            self.call(loaded, name, args.clone());
+        }
+    }
+
+    fn api(&self) -> MutexGuard<'_, ModApi> {
+        self.api.lock().unwrap()
+    }
+
+    // for scripts that don't come from the mods folder
+    pub fn load(&mut self, name: &str, source: &str) -> Result<(), Box<EvalAltResult>> {
+        self.load_script(name, source, Handle::default())
+    }
+
+    pub fn is_loaded(&self, name: &str) -> bool {
+        self.loaded.iter().any(|loaded| loaded.name == name)
+    }
+
+    // the top level of a script runs once after every load, that's where it adds its buttons
+    // and registers its buildings and items, a script that fails to compile or stops with an
+    // error keeps its previous version running
+    fn load_script(
+        &mut self,
+        name: &str,
+        source: &str,
+        handle: Handle<ModScript>,
+    ) -> Result<(), Box<EvalAltResult>> {
+        let ast = self.engine.compile(source)?;
+        let result = self.engine.run_ast(&ast);
+
+        let (buttons, registry) = {
+            let mut api = self.api();
+            (
+                std::mem::take(&mut api.buttons),
+                std::mem::take(&mut api.registry),
+            )
+        };
+
+        result?;
+
+        let buttons = buttons
+            .into_iter()
+            .map(|button| ModButton {
+                mod_name: name.to_string(),
+                ..button
+            })
+            .collect();
+
+        self.registered.extend(registry);
+        self.loaded.retain(|loaded| loaded.name != name);
+        self.loaded.push(LoadedMod {
+            name: name.to_string(),
+            handle,
+            ast,
+            buttons,
+        });
+        self.loaded.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(())
+    }
+}
+
+type ApiResult<T> = Result<T, Box<EvalAltResult>>;
+
+fn tile(x: INT, y: INT) -> ApiResult<TilePos> {
+    match (u32::try_from(x), u32::try_from(y)) {
+        (Ok(x), Ok(y)) if x < TILEMAP_SIZE.x && y < TILEMAP_SIZE.y => Ok(TilePos::new(x, y)),
+        _ => Err(format!("{x}, {y} is outside the map").into()),
+    }
+}
+
+fn item(name: &str) -> ApiResult<ItemType> {
+    name.parse()
+        .map_err(|_| format!("unknown item '{name}'").into())
+}
+
+// registered names can't take the place of the built-in buildings and items
+fn mod_name(name: &str) -> ApiResult<ModName> {
+    let is_builtin = matches!(
+        name.parse(),
+        Ok(BuildingType::Belt | BuildingType::Mine | BuildingType::Chest)
+    ) || matches!(name.parse(), Ok(ItemType::Coal));
+
+    match name.is_empty() || is_builtin {
+        true => Err(format!("'{name}' can't be registered").into()),
+        false => Ok(ModName::new(name)),
+    }
+}
+
+fn mod_building(base: &str, item_type: ItemType) -> ApiResult<ModBuilding> {
+    match base {
+        "chest" => Ok(ModBuilding::Chest),
+        "mine" => Ok(ModBuilding::Mine { item: item_type }),
+        _ => Err(format!("buildings can't be based on '{base}'").into()),
+    }
+}
+
+fn slots_to_array(slots: &Slots) -> Array {
+    slots
+        .iter()
+        .map(|slot| match slot {
+            Some((item_type, amount)) => {
+                let mut map = Map::new();
+                map.insert("item".into(), item_type.as_str().into());
+                map.insert("amount".into(), (*amount as INT).into());
+                map.into()
+            }
+            None => Dynamic::UNIT,
+        })
+        .collect()
+}
+
+// scripts can't touch files, import modules or evaluate code, and run with limits on
+// everything they allocate
+fn sandboxed_engine(api: &Arc<Mutex<ModApi>>) -> Engine {
+    let mut engine = Engine::new();
+
+    engine
+        .set_module_resolver(DummyModuleResolver::new())
+        .disable_symbol("eval")
+        .set_max_operations(MAX_OPERATIONS)
+        .set_max_call_levels(32)
+        .set_max_expr_depths(64, 32)
+        .set_max_string_size(10_000)
+        .set_max_array_size(10_000)
+        .set_max_map_size(10_000)
+        .on_print(|text| info!("mod: {text}"))
+        .on_debug(|text, _, pos| debug!("mod: {pos:?} {text}"));
+
+    let shared = api.clone();
+    engine.register_fn("add_button", move |label: &str, callback: &str| {
+        shared.lock().unwrap().buttons.push(ModButton {
+            mod_name: String::new(),
+            label: label.to_uppercase(),
+            callback: callback.to_string(),
+        });
+    });
+
+    let shared = api.clone();
+    engine.register_fn("register_item", move |name: &str| -> ApiResult<()> {
+        shared.lock().unwrap().registry.items.insert(mod_name(name)?);
+        Ok(())
+    });
+
+    let shared = api.clone();
+    engine.register_fn(
+        "register_building",
+        move |name: &str, base: &str| -> ApiResult<()> {
+            let building = mod_building(base, ItemType::Coal)?;
+            shared
+                .lock()
+                .unwrap()
+                .registry
+                .buildings
+                .insert(mod_name(name)?, building);
+            Ok(())
+        },
+    );
+
+    let shared = api.clone();
+    engine.register_fn(
+        "register_building",
+        move |name: &str, base: &str, item_name: &str| -> ApiResult<()> {
+            let building = mod_building(base, item(item_name)?)?;
+            shared
+                .lock()
+                .unwrap()
+                .registry
+                .buildings
+                .insert(mod_name(name)?, building);
+            Ok(())
+        },
+    );
+
+    let shared = api.clone();
+    engine.register_fn("inventory", move |x: INT, y: INT| -> ApiResult<Dynamic> {
+        let tile_pos = tile(x, y)?;
+        let api = shared.lock().unwrap();
+
+        Ok(api
+            .inventories
+            .get(&tile_pos)
+            .map_or(Dynamic::UNIT, |slots| slots_to_array(slots).into()))
+    });
+
+    let shared = api.clone();
+    engine.register_fn(
+        "insert_item",
+        move |x: INT, y: INT, name: &str, amount: INT| -> ApiResult<()> {
+            shared
+                .lock()
+                .unwrap()
+                .actions
+                .push(ModAction::Insert(SpawnItemEvent {
+                    item_type: item(name)?,
+                    tile_pos: tile(x, y)?,
+                    amount: amount.max(0) as usize,
+                }));
+            Ok(())
+        },
+    );
+
+    let shared = api.clone();
+    engine.register_fn(
+        "clear_slot",
+        move |x: INT, y: INT, slot: INT| -> ApiResult<()> {
+            shared
+                .lock()
+                .unwrap()
+                .actions
+                .push(ModAction::Inventory(InventoryEvent::ClearSlot {
+                    tile_pos: tile(x, y)?,
+                    slot: slot.max(0) as usize,
+                }));
+            Ok(())
+        },
+    );
+
+    let shared = api.clone();
+    engine.register_fn("clear_inventory", move |x: INT, y: INT| -> ApiResult<()> {
+        shared
+            .lock()
+            .unwrap()
+            .actions
+            .push(ModAction::Inventory(InventoryEvent::Clear {
+                tile_pos: tile(x, y)?,
+            }));
+        Ok(())
+    });
+
+    let shared = api.clone();
+    engine.register_fn(
+        "build",
+        move |name: &str, x: INT, y: INT, direction: &str| -> ApiResult<()> {
+            let building_type = name
+                .parse::<BuildingType>()
+                .map_err(|_| format!("unknown building '{name}'"))?;
+            let direction = direction
+                .parse::<MapDirection>()
+                .map_err(|_| format!("unknown direction '{direction}'"))?;
+
+            shared
+                .lock()
+                .unwrap()
+                .actions
+                .push(ModAction::Build(BuildRequestedEvent {
+                    building_type,
+                    direction,
+                    tile_pos: tile(x, y)?,
+                }));
+            Ok(())
+        },
+    );
+
+    let shared = api.clone();
+    engine.register_fn("demolish", move |x: INT, y: INT| -> ApiResult<()> {
+        shared
+            .lock()
+            .unwrap()
+            .actions
+            .push(ModAction::Demolish(DemolishEvent {
+                tile_pos: tile(x, y)?,
+            }));
+        Ok(())
+    });
+
+    engine
+}
+
+pub fn load_mods(assets: Res<AssetServer>, mut handles: ResMut<ModHandles>) {
+    match assets.load_folder(MODS_PATH) {
+        Ok(loaded) => handles.0.extend(loaded),
+        Err(e) => info!("no mods loaded: {}", e),
+    }
+}
+
+pub fn register_mods(
+    scripts: Res<Assets<ModScript>>,
+    mut asset_events: EventReader<AssetEvent<ModScript>>,
+    mut mods: ResMut<Mods>,
+) {
+    for event in asset_events.iter() {
+        let handle = match event {
+            AssetEvent::Created { handle } | AssetEvent::Modified { handle } => handle,
+            AssetEvent::Removed { handle } => {
+                mods.loaded.retain(|loaded| loaded.handle != *handle);
+                continue;
+            }
+        };
+
+        let Some(script) = scripts.get(handle) else {
+            continue;
+        };
+
+        match mods.load_script(&script.name, &script.source, handle.clone_weak()) {
+            Ok(()) => info!("mod {} loaded", script.name),
+            Err(e) => error!("mod {}: {e}", script.name),
+        }
+    }
+}
+
+// registrations are never taken back, buildings of a removed mod keep working
+pub fn register_mod_types(mut mods: ResMut<Mods>, mut registry: ResMut<ModRegistry>) {
+    if !mods.registered.is_empty() {
+        registry.extend(std::mem::take(&mut mods.registered));
+    }
+}
+
+#[derive(Component)]
+pub struct ModButtonPanel;
+
+pub fn init_mod_buttons(mut commands: Commands) {
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.),
+                bottom: Val::Px(16.),
+                justify_content: JustifyContent::Center,
+                column_gap: Val::Px(16.),
+                ..default()
+            },
+            ..default()
+        })
+        .insert(ModButtonPanel);
+}
+
+pub fn update_mod_buttons(
+    mut commands: Commands,
+    mods: Res<Mods>,
+    panel: Query<Entity, With<ModButtonPanel>>,
+    asset_server: Res<AssetServer>,
+) {
+    let Ok(panel) = panel.get_single() else {
+        return;
+    };
+
+    let style = TextStyle {
+        font: asset_server.load("AsepriteFont.ttf"),
+        color: Color::DARK_GRAY,
+        font_size: 24.,
+    };
+
+    commands
+        .entity(panel)
+        .despawn_descendants()
+        .with_children(|panel| {
+            for button in mods.loaded.iter().flat_map(|loaded| loaded.buttons.iter()) {
+                panel
+                    .spawn(ButtonBundle {
+                        style: Style {
+                            padding: UiRect {
+                                left: Val::Px(16.),
+                                right: Val::Px(16.),
+                                top: Val::Px(8.),
+                                bottom: Val::Px(8.),
+                            },
+                            ..default()
+                        },
+                        ..default()
+                    })
+                    .insert(button.clone())
+                    .with_children(|parent| {
+                        parent.spawn(TextBundle::from_section(&button.label, style.clone()));
+                    });
+            }
+        });
+}
+
+// fn on_building_placed(building, x, y, direction) and fn on_building_removed(...) are called
+// for every building, button callbacks take no arguments
+pub fn run_mod_callbacks(
+    mods: Res<Mods>,
+    mut changed_events: EventReader<BuildingChangedEvent>,
+    buttons: Query<(&ModButton, &Interaction), Changed<Interaction>>,
+    inventories: Query<(&Building, &Inventory)>,
+    lockstep: Option<Res<Lockstep>>,
+) {
+    // every peer sees the same buildings change, only the host answers them
+    let remote = lockstep.is_some_and(|lockstep| !lockstep.is_host());
+
+    let events: Vec<_> = changed_events
+        .iter()
+        .filter(|_| !remote)
+        .filter_map(|event| match event {
+            BuildingChangedEvent::Built {
+                building_type,
+                origin,
+                direction,
+            } => Some(("on_building_placed", building_type, origin, direction)),
+            BuildingChangedEvent::Demolished {
+                building_type,
+                origin,
+                direction,
+                ..
+            } => Some(("on_building_removed", building_type, origin, direction)),
+            BuildingChangedEvent::Redirected { .. } => None,
+        })
+        .collect();
+
+    let pressed: Vec<_> = buttons
+        .iter()
+        .filter(|(_, interaction)| matches!(interaction, Interaction::Pressed))
+        .map(|(button, _)| button)
+        .collect();
+
+    if mods.loaded.is_empty() || (events.is_empty() && pressed.is_empty()) {
+        return;
+    }
+
+    mods.api().inventories = inventories
+        .iter()
+        .flat_map(|(building, inventory)| {
+            building
+                .layout
+                .tiles
+                .iter()
+                .map(|(_, tile_pos, _)| (*tile_pos, inventory.slots.to_vec()))
+        })
+        .collect();
+
+    for (callback, building_type, origin, direction) in events {
+        let args = (
+            building_type.as_str().to_string(),
+            origin.x as INT,
+            origin.y as INT,
+            direction.as_str().to_string(),
+        );
+        mods.call_all(callback, args, 4);
+    }
+
+    for button in pressed {
+        if let Some(loaded) = mods.loaded.iter().find(|m| m.name == button.mod_name) {
+            mods.call(loaded, &button.callback, ());
+        }
+    }
+}
+
+// builds and items go through the same events as the player's, inventories are changed
+// directly like from the inventory window
+pub fn apply_mod_actions(
+    mods: Res<Mods>,
+    mut build_events: EventWriter<BuildRequestedEvent>,
+    mut demolish_events: EventWriter<DemolishEvent>,
+    mut spawn_events: EventWriter<SpawnItemEvent>,
+    mut inventory_events: EventWriter<InventoryEvent>,
+) {
+    for action in mods.api().actions.drain(..) {
+        match action {
+            ModAction::Build(event) => build_events.send(event),
+            ModAction::Demolish(event) => demolish_events.send(event),
+            ModAction::Insert(event) => spawn_events.send(event),
+            ModAction::Inventory(event) => inventory_events.send(event),
+        }
+    }
+}
@@ -0,0 +1,135 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::sync::Mutex;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::belts::ItemType;
+use crate::buildings::templates::BuildingRegistry;
+use crate::buildings::BuildingType;
+
+// each name is leaked once, there are only as many as mods register and games carry
+static NAMES: Mutex<BTreeSet<&'static str>> = Mutex::new(BTreeSet::new());
+
+// name of a building type or item a mod added, interned so those types stay Copy
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ModName(&'static str);
+
+impl ModName {
+    pub fn new(name: &str) -> Self {
+        let mut names = NAMES.lock().unwrap();
+
+        if let Some(name) = names.get(name) {
+            return ModName(name);
+        }
+
+        let name: &'static str = Box::leak(name.into());
+        names.insert(name);
+        ModName(name)
+    }
+
+    // only names seen before, parsing what a player typed doesn't make up new ones
+    pub fn existing(name: &str) -> Option<Self> {
+        NAMES.lock().unwrap().get(name).map(|name| ModName(name))
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        self.0
+    }
+}
+
+impl fmt::Debug for ModName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self.0)
+    }
+}
+
+impl Serialize for ModName {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for ModName {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(|name| ModName::new(&name))
+    }
+}
+
+// a building added by a mod works like one of the built-in ones
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ModBuilding {
+    Chest,
+    Mine { item: ItemType },
+}
+
+// building types and items mods added, saves, replays and the start of a multiplayer game
+// carry it so their factories work the same where the mods aren't loaded
+#[derive(Resource, Clone, Default, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModRegistry {
+    pub items: BTreeSet<ModName>,
+    pub buildings: BTreeMap<ModName, ModBuilding>,
+}
+
+impl ModRegistry {
+    // definitions of the other registry replace those with the same name
+    pub fn extend(&mut self, other: ModRegistry) {
+        self.items.extend(other.items);
+        self.buildings.extend(other.buildings);
+    }
+
+    pub fn merged(&self, other: &ModRegistry) -> ModRegistry {
+        let mut merged = self.clone();
+        merged.extend(other.clone());
+        merged
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty() && self.buildings.is_empty()
+    }
+
+    // the built-in building the type works like, None for types nothing registered
+    pub fn base(&self, building_type: BuildingType) -> Option<BuildingType> {
+        match building_type {
+            BuildingType::Mod(name) => match self.buildings.get(&name)? {
+                ModBuilding::Chest => Some(BuildingType::Chest),
+                ModBuilding::Mine { .. } => Some(BuildingType::Mine),
+            },
+            building_type => Some(building_type),
+        }
+    }
+
+    pub fn mined_item(&self, building_type: BuildingType) -> ItemType {
+        match building_type {
+            BuildingType::Mod(name) => match self.buildings.get(&name) {
+                Some(ModBuilding::Mine { item }) => *item,
+                _ => ItemType::Coal,
+            },
+            _ => ItemType::Coal,
+        }
+    }
+}
+
+// mod buildings are placed from the template of their base, once that is loaded, and again
+// when a mod changes what they are based on
+pub fn register_mod_templates(mods: Res<ModRegistry>, mut templates: ResMut<BuildingRegistry>) {
+    for name in mods.buildings.keys() {
+        let building_type = BuildingType::Mod(*name);
+
+        if templates.contains(building_type) && !mods.is_changed() {
+            continue;
+        }
+
+        let Some(base) = mods
+            .base(building_type)
+            .filter(|base| templates.contains(*base))
+        else {
+            continue;
+        };
+
+        let mut template = templates.get(base).clone();
+        template.building_type = building_type;
+        templates.register(template);
+    }
+}
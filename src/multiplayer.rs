@@ -0,0 +1,617 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::net::{SocketAddr, TcpListener, ToSocketAddrs};
+use std::path::Path;
+
+use bevy::ecs::event::ManualEventReader;
+use bevy::prelude::*;
+
+use self::connection::{Connection, Message, PlayerInputs, Turn};
+use self::desync::{
+    dump_factory, factory_checksum, ChecksumBelts, ChecksumBuildings, DesyncReport,
+};
+use crate::belts::{input_from_belts, InventoryEvent, Item, SpawnItemEvent};
+use crate::buildings::{BuildRequestedEvent, DemolishAreaEvent, DemolishEvent};
+use crate::game::{start_game, GameSetup, GameState};
+use crate::grid::toggle_grid;
+use crate::history::{apply_history, History};
+use crate::map::MapEvent;
+use crate::mods::registry::ModRegistry;
+use crate::replay::{record_inputs, FactoryEvents, ReplayInput, ReplayPlayback};
+use crate::simulation::{BuildSet, Simulation, SimulationSet, SimulationTick};
+use crate::ui::console::Console;
+
+pub mod connection;
+pub mod desync;
+
+pub const DEFAULT_PORT: u16 = 7777;
+// inputs are handed out this many ticks ahead, so they reach every player before they are due
+const INPUT_DELAY: u64 = 12;
+// a turn every few ticks even when nobody did anything, the players can't run past the last one
+const TURN_TICKS: u64 = 6;
+// the host waits for players that fall further behind than this
+const MAX_LEAD: u64 = 60;
+const DESYNCS_PATH: &str = "desyncs";
+
+// several instances playing the same factory: only inputs are sent, the host collects them into
+// turns and every peer applies a turn at the same tick, so the factories never have to be synced
+pub struct MultiplayerPlugin;
+
+impl Plugin for MultiplayerPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GameSetup>()
+            .add_systems(
+                OnExit(GameState::MainMenu),
+                start_lockstep
+                    .before(start_game)
+                    .run_if(resource_exists::<Lockstep>()),
+            )
+            .add_systems(
+                Update,
+                (
+                    accept_players.run_if(in_state(GameState::MainMenu)),
+                    receive_messages,
+                )
+                    .chain()
+                    .before(SimulationSet)
+                    .run_if(resource_exists::<Lockstep>()),
+            )
+            .add_systems(
+                Update,
+                (
+                    run_lockstep
+                        .in_set(SimulationSet)
+                        .before(BuildSet)
+                        .after(apply_history)
+                        .before(record_inputs)
+                        .before(toggle_grid),
+                    report_desync.after(SimulationSet),
+                )
+                    .run_if(resource_exists::<Lockstep>())
+                    .run_if(not(resource_exists::<ReplayPlayback>())),
+            )
+            .add_systems(
+                SimulationTick,
+                record_checksum
+                    .after(input_from_belts)
+                    .run_if(resource_exists::<Lockstep>()),
+            );
+    }
+}
+
+enum Peer {
+    Host {
+        listener: TcpListener,
+        players: Vec<RemotePlayer>,
+        next_turn: u64,
+        // inputs of every player waiting for the next turn
+        inputs: Vec<PlayerInputs>,
+        // kept until every player sent theirs for the same tick
+        checksums: BTreeMap<u64, u64>,
+    },
+    Client {
+        host: Connection,
+        // applied once the game is back in the main menu
+        start: Option<GameSetup>,
+    },
+}
+
+struct RemotePlayer {
+    player: u8,
+    connection: Connection,
+    // ticks the player has simulated
+    confirmed: u64,
+    checksums: VecDeque<(u64, u64)>,
+}
+
+// inputs of this player are taken out of the events before the factory sees them and sent back
+// once their turn comes
+#[derive(Default)]
+struct InputReaders {
+    build: ManualEventReader<BuildRequestedEvent>,
+    demolish: ManualEventReader<DemolishEvent>,
+    demolish_area: ManualEventReader<DemolishAreaEvent>,
+    spawn: ManualEventReader<SpawnItemEvent>,
+    inventory: ManualEventReader<InventoryEvent>,
+    map: ManualEventReader<MapEvent>,
+}
+
+fn take_events<T: Event>(
+    reader: &mut ManualEventReader<T>,
+    events: &mut Events<T>,
+    inputs: &mut Vec<ReplayInput>,
+) where
+    for<'a> ReplayInput: From<&'a T>,
+{
+    let taken = inputs.len();
+    inputs.extend(reader.iter(events).map(ReplayInput::from));
+
+    if inputs.len() > taken {
+        events.clear();
+    }
+}
+
+impl InputReaders {
+    fn take(&mut self, events: &mut FactoryEvents) -> Vec<ReplayInput> {
+        let mut inputs = Vec::new();
+
+        take_events(&mut self.build, &mut events.build, &mut inputs);
+        take_events(&mut self.demolish, &mut events.demolish, &mut inputs);
+        take_events(
+            &mut self.demolish_area,
+            &mut events.demolish_area,
+            &mut inputs,
+        );
+        take_events(&mut self.spawn, &mut events.spawn, &mut inputs);
+        take_events(&mut self.inventory, &mut events.inventory, &mut inputs);
+
+        // the grid is only shown here, it stays with this player
+        let map: Vec<_> = self.map.iter(&events.map).map(ReplayInput::from).collect();
+
+        if map.iter().any(|e| matches!(e, ReplayInput::ClearBuildings)) {
+            events.map.clear();
+
+            for input in map {
+                match input {
+                    ReplayInput::ToggleGrid => events.map.send(MapEvent::ToggleGrid),
+                    input => inputs.push(input),
+                }
+            }
+        }
+
+        inputs
+    }
+
+    // events sent from here on are new inputs again
+    fn skip(&mut self, events: &FactoryEvents) {
+        self.build = events.build.get_reader_current();
+        self.demolish = events.demolish.get_reader_current();
+        self.demolish_area = events.demolish_area.get_reader_current();
+        self.spawn = events.spawn.get_reader_current();
+        self.inventory = events.inventory.get_reader_current();
+        self.map = events.map.get_reader_current();
+    }
+}
+
+#[derive(Resource)]
+pub struct Lockstep {
+    peer: Peer,
+    // the host is player 0
+    player: u8,
+    // turns not applied yet, in tick order
+    turns: VecDeque<Turn>,
+    last_turn: u64,
+    // checksums not sent to the host yet, the first one is of tick `checksums_from`
+    checksums: Vec<u64>,
+    checksums_from: u64,
+    desync: Option<DesyncReport>,
+    desync_reported: bool,
+    readers: InputReaders,
+}
+
+impl Lockstep {
+    fn new(peer: Peer) -> Self {
+        Lockstep {
+            peer,
+            player: 0,
+            turns: VecDeque::new(),
+            last_turn: 0,
+            checksums: Vec::new(),
+            checksums_from: 0,
+            desync: None,
+            desync_reported: false,
+            readers: InputReaders::default(),
+        }
+    }
+
+    // players join while the host is in the main menu
+    pub fn host(port: u16) -> io::Result<Self> {
+        let listener = TcpListener::bind(("0.0.0.0", port))?;
+        listener.set_nonblocking(true)?;
+
+        Ok(Lockstep::new(Peer::Host {
+            listener,
+            players: Vec::new(),
+            next_turn: 0,
+            inputs: Vec::new(),
+            checksums: BTreeMap::new(),
+        }))
+    }
+
+    pub fn join(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        Ok(Lockstep::new(Peer::Client {
+            host: Connection::connect(addr)?,
+            start: None,
+        }))
+    }
+
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        match &self.peer {
+            Peer::Host { listener, .. } => listener.local_addr().ok(),
+            Peer::Client { .. } => None,
+        }
+    }
+
+    pub fn is_host(&self) -> bool {
+        matches!(self.peer, Peer::Host { .. })
+    }
+
+    pub fn player(&self) -> u8 {
+        self.player
+    }
+
+    // only the host knows who plays
+    pub fn players(&self) -> usize {
+        match &self.peer {
+            Peer::Host { players, .. } => players.len() + 1,
+            Peer::Client { .. } => 0,
+        }
+    }
+
+    pub fn desync(&self) -> Option<&DesyncReport> {
+        self.desync.as_ref()
+    }
+
+    fn reset(&mut self) {
+        self.turns.clear();
+        self.last_turn = 0;
+        self.checksums.clear();
+        self.checksums_from = 0;
+        self.desync = None;
+        self.desync_reported = false;
+    }
+
+    // non empty turns hold the simulation until they are applied, empty ones only when they
+    // are the last one known
+    fn tick_limit(&self) -> u64 {
+        self.turns
+            .iter()
+            .find(|turn| !turn.inputs.is_empty())
+            .or(self.turns.back())
+            .map_or(self.last_turn, |turn| turn.tick)
+    }
+}
+
+pub fn start_lockstep(
+    mut lockstep: ResMut<Lockstep>,
+    setup: Res<GameSetup>,
+    mods: Res<ModRegistry>,
+) {
+    let Peer::Host {
+        players,
+        next_turn,
+        inputs,
+        checksums,
+        ..
+    } = &mut lockstep.peer
+    else {
+        return;
+    };
+
+    *next_turn = 0;
+    inputs.clear();
+    checksums.clear();
+
+    for remote in players.iter_mut() {
+        remote.confirmed = 0;
+        remote.checksums.clear();
+        remote.connection.send(&Message::Start {
+            seed: setup.seed,
+            save: setup.save.clone(),
+            mods: mods.merged(&setup.mods),
+        });
+    }
+
+    lockstep.reset();
+}
+
+pub fn accept_players(mut lockstep: ResMut<Lockstep>) {
+    let Peer::Host {
+        listener, players, ..
+    } = &mut lockstep.peer
+    else {
+        return;
+    };
+
+    loop {
+        let (stream, addr) = match listener.accept() {
+            Ok(accepted) => accepted,
+            Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+            Err(e) => {
+                warn!("failed to accept a player: {e}");
+                break;
+            }
+        };
+
+        let player = players.iter().map(|p| p.player).max().unwrap_or(0) + 1;
+
+        match Connection::new(stream) {
+            Ok(mut connection) => {
+                connection.send(&Message::Welcome { player });
+                info!("player {player} joined from {addr}");
+
+                players.push(RemotePlayer {
+                    player,
+                    connection,
+                    confirmed: 0,
+                    checksums: VecDeque::new(),
+                });
+            }
+            Err(e) => warn!("failed to accept a player from {addr}: {e}"),
+        }
+    }
+}
+
+pub fn receive_messages(
+    mut commands: Commands,
+    mut lockstep: ResMut<Lockstep>,
+    state: Res<State<GameState>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let lockstep = &mut *lockstep;
+
+    match &mut lockstep.peer {
+        Peer::Host {
+            players, inputs, ..
+        } => {
+            for remote in players.iter_mut() {
+                for message in remote.connection.receive() {
+                    match message {
+                        Message::Inputs(received) => inputs.push(PlayerInputs {
+                            player: remote.player,
+                            inputs: received,
+                        }),
+                        Message::Checksums { from, checksums } => {
+                            remote.confirmed = from + checksums.len() as u64;
+                            remote.checksums.extend((from..).zip(checksums));
+                        }
+                        _ => warn!("unexpected message from player {}", remote.player),
+                    }
+                }
+            }
+
+            players.retain(|remote| {
+                if remote.connection.is_closed() {
+                    info!("player {} left", remote.player);
+                }
+                !remote.connection.is_closed()
+            });
+        }
+        Peer::Client { host, start } => {
+            for message in host.receive() {
+                match message {
+                    Message::Welcome { player } => {
+                        info!("joined as player {player}");
+                        lockstep.player = player;
+                    }
+                    // turns of the previous game may still be queued, the new game starts clean
+                    Message::Start { seed, save, mods } => {
+                        lockstep.turns.clear();
+                        lockstep.desync = None;
+                        *start = Some(GameSetup { seed, save, mods });
+                    }
+                    Message::Turn(turn) => lockstep.turns.push_back(turn),
+                    Message::Desync(report) => lockstep.desync = Some(report),
+                    _ => warn!("unexpected message from the host"),
+                }
+            }
+
+            if let Some(setup) = start.take() {
+                match state.get() {
+                    GameState::MainMenu => {
+                        let turns = std::mem::take(&mut lockstep.turns);
+                        lockstep.reset();
+                        lockstep.turns = turns;
+
+                        commands.insert_resource(setup);
+                        next_state.set(GameState::InGame);
+                    }
+                    _ => {
+                        *start = Some(setup);
+                        next_state.set(GameState::MainMenu);
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn compare_checksums(
+    players: &mut [RemotePlayer],
+    checksums: &mut BTreeMap<u64, u64>,
+) -> Option<(u64, Vec<(u8, u64)>)> {
+    let mut desync = None;
+
+    for remote in players.iter_mut() {
+        while let Some(&(tick, checksum)) = remote.checksums.front()
+            && let Some(&host_checksum) = checksums.get(&tick)
+        {
+            remote.checksums.pop_front();
+
+            if checksum != host_checksum && desync.is_none() {
+                desync = Some((tick, vec![(0, host_checksum), (remote.player, checksum)]));
+            }
+        }
+    }
+
+    // checksums of the host are kept until the slowest player sent theirs
+    let compared = players
+        .iter()
+        .map(|remote| remote.checksums.front().map_or(remote.confirmed, |c| c.0))
+        .min();
+
+    match compared {
+        Some(tick) => *checksums = checksums.split_off(&tick),
+        None => checksums.clear(),
+    }
+
+    desync
+}
+
+pub fn run_lockstep(
+    mut lockstep: ResMut<Lockstep>,
+    mut simulation: ResMut<Simulation>,
+    mut events: FactoryEvents,
+    mut history: Option<ResMut<History>>,
+) {
+    let lockstep = &mut *lockstep;
+    let local = lockstep.readers.take(&mut events);
+
+    if !local.is_empty()
+        && let Some(history) = history.as_mut()
+    {
+        history.send_inputs();
+    }
+
+    match &mut lockstep.peer {
+        Peer::Host {
+            players,
+            next_turn,
+            inputs,
+            checksums,
+            ..
+        } => {
+            if !local.is_empty() {
+                inputs.push(PlayerInputs {
+                    player: lockstep.player,
+                    inputs: local,
+                });
+            }
+
+            if lockstep.desync.is_none()
+                && let Some((tick, checksums)) = compare_checksums(players, checksums)
+            {
+                let report = DesyncReport {
+                    tick,
+                    checksums,
+                    stop_tick: next_turn.saturating_sub(TURN_TICKS),
+                };
+
+                for remote in players.iter_mut() {
+                    remote.connection.send(&Message::Desync(report.clone()));
+                }
+
+                lockstep.desync = Some(report);
+            }
+
+            // no more turns after a desync, everyone runs up to the last one and stops there
+            while lockstep.desync.is_none()
+                && simulation.tick() + INPUT_DELAY >= *next_turn
+                && players
+                    .iter()
+                    .all(|remote| remote.confirmed + MAX_LEAD >= *next_turn)
+            {
+                let turn = Turn {
+                    tick: *next_turn,
+                    inputs: std::mem::take(inputs),
+                };
+
+                for remote in players.iter_mut() {
+                    remote.connection.send(&Message::Turn(turn.clone()));
+                }
+
+                lockstep.turns.push_back(turn);
+                *next_turn += TURN_TICKS;
+            }
+        }
+        Peer::Client { host, .. } => {
+            if !local.is_empty() {
+                host.send(&Message::Inputs(local));
+            }
+
+            if !lockstep.checksums.is_empty() {
+                let checksums = std::mem::take(&mut lockstep.checksums);
+                let from = lockstep.checksums_from;
+                lockstep.checksums_from += checksums.len() as u64;
+
+                host.send(&Message::Checksums { from, checksums });
+            }
+        }
+    }
+
+    // turns of a game that is about to start don't belong to this factory
+    let starting = matches!(lockstep.peer, Peer::Client { start: Some(_), .. });
+
+    // one player's inputs a frame, so the changes they make can be told apart, the simulation
+    // waits at the tick of the turn until all of them are applied
+    while !starting
+        && let Some(turn) = lockstep.turns.front_mut()
+        && turn.tick <= simulation.tick()
+    {
+        let applied = (!turn.inputs.is_empty()).then(|| turn.inputs.remove(0));
+
+        if turn.inputs.is_empty() {
+            lockstep.last_turn = turn.tick;
+            lockstep.turns.pop_front();
+        }
+
+        let Some(applied) = applied else {
+            continue;
+        };
+
+        for input in &applied.inputs {
+            events.send(input);
+        }
+
+        if let Some(history) = history.as_mut() {
+            history.apply_turn(applied.player == lockstep.player);
+        }
+
+        break;
+    }
+
+    simulation.limit_ticks(Some(lockstep.tick_limit()));
+    lockstep.readers.skip(&events);
+}
+
+pub fn record_checksum(
+    mut lockstep: ResMut<Lockstep>,
+    simulation: Res<Simulation>,
+    buildings: ChecksumBuildings,
+    belts: ChecksumBelts,
+    items: Query<&Item>,
+) {
+    let checksum = factory_checksum(&buildings, &belts, &items);
+
+    match &mut lockstep.peer {
+        Peer::Host { checksums, .. } => {
+            checksums.insert(simulation.tick(), checksum);
+        }
+        Peer::Client { .. } => lockstep.checksums.push(checksum),
+    }
+}
+
+// once the factory reached the tick everyone stops at, it's written out next to the report
+pub fn report_desync(world: &mut World) {
+    let tick = world.resource::<Simulation>().tick();
+    let lockstep = world.resource::<Lockstep>();
+
+    let Some(report) = lockstep
+        .desync
+        .clone()
+        .filter(|report| !lockstep.desync_reported && report.stop_tick <= tick)
+    else {
+        return;
+    };
+
+    let player = lockstep.player;
+    world.resource_mut::<Lockstep>().desync_reported = true;
+
+    let path = Path::new(DESYNCS_PATH).join(format!("player-{player}.txt"));
+    let dump = format!(
+        "{report}\n\nplayer {player} at tick {tick}\n{}",
+        dump_factory(world)
+    );
+
+    match fs::create_dir_all(DESYNCS_PATH).and_then(|_| fs::write(&path, dump)) {
+        Ok(()) => error!("{report}\nfactory written to {}", path.display()),
+        Err(e) => error!("{report}\nfailed to write {}: {e}", path.display()),
+    }
+
+    if let Some(mut console) = world.get_resource_mut::<Console>() {
+        console.print(&report.to_string());
+        console.print(&format!("factory written to {}", path.display()));
+        console.open = true;
+    }
+}
@@ -0,0 +1,173 @@
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::desync::DesyncReport;
+use crate::mods::registry::ModRegistry;
+use crate::replay::ReplayInput;
+use crate::save::SaveGame;
+
+// a start message carries a whole save, anything longer isn't from a peer of this game
+const MAX_LINE_BYTES: usize = 4 << 20;
+// a peer that doesn't read its messages for this long is gone
+const MAX_QUEUED_BYTES: usize = 4 * MAX_LINE_BYTES;
+
+// what one player sent in one message, tagged by the host with who sent it
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PlayerInputs {
+    pub player: u8,
+    pub inputs: Vec<ReplayInput>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Turn {
+    pub tick: u64,
+    pub inputs: Vec<PlayerInputs>,
+}
+
+// everything the peers tell each other, one ron value per line
+#[derive(Serialize, Deserialize)]
+pub enum Message {
+    // host to a player that just joined
+    Welcome {
+        player: u8,
+    },
+    // host to everyone, the same setup as a replay starts from
+    Start {
+        seed: u64,
+        save: Option<SaveGame>,
+        mods: ModRegistry,
+    },
+    // player to host, what the player did since the last message
+    Inputs(Vec<ReplayInput>),
+    // host to everyone, inputs of all players applied before the simulation runs `tick`
+    Turn(Turn),
+    // player to host, the state after each tick starting with `from`
+    Checksums {
+        from: u64,
+        checksums: Vec<u64>,
+    },
+    // host to everyone, the factories stop at the same tick so they can be compared
+    Desync(DesyncReport),
+}
+
+// a tcp stream that never blocks the frame, messages are read whole or not at all and what
+// the socket doesn't take right away is written in later frames
+pub struct Connection {
+    stream: TcpStream,
+    received: Vec<u8>,
+    queued: Vec<u8>,
+    closed: bool,
+}
+
+impl Connection {
+    pub fn new(stream: TcpStream) -> io::Result<Self> {
+        stream.set_nodelay(true)?;
+        stream.set_nonblocking(true)?;
+
+        Ok(Connection {
+            stream,
+            received: Vec::new(),
+            queued: Vec::new(),
+            closed: false,
+        })
+    }
+
+    pub fn connect(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        Connection::new(TcpStream::connect(addr)?)
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    pub fn send(&mut self, message: &Message) {
+        if self.closed {
+            return;
+        }
+
+        let mut line = match ron::to_string(message) {
+            Ok(line) => line,
+            Err(e) => {
+                error!("failed to serialize message: {e}");
+                return;
+            }
+        };
+        line.push('\n');
+
+        self.queued.extend_from_slice(line.as_bytes());
+        self.flush();
+    }
+
+    // writes as much of the queue as the socket takes without blocking
+    fn flush(&mut self) {
+        while !self.closed && !self.queued.is_empty() {
+            match self.stream.write(&self.queued) {
+                Ok(0) => self.closed = true,
+                Ok(written) => {
+                    self.queued.drain(..written);
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    warn!("connection lost: {e}");
+                    self.closed = true;
+                }
+            }
+        }
+
+        if self.queued.len() > MAX_QUEUED_BYTES {
+            warn!("connection lost: the peer stopped reading");
+            self.closed = true;
+        }
+    }
+
+    // called every frame, so it also sends what is still queued
+    pub fn receive(&mut self) -> Vec<Message> {
+        self.flush();
+
+        let mut buffer = [0; 4096];
+
+        // the rest is read in the next frame
+        while !self.closed && self.received.len() <= MAX_LINE_BYTES {
+            match self.stream.read(&mut buffer) {
+                Ok(0) => self.closed = true,
+                Ok(read) => self.received.extend_from_slice(&buffer[..read]),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    warn!("connection lost: {e}");
+                    self.closed = true;
+                }
+            }
+        }
+
+        let mut messages = Vec::new();
+
+        while let Some(end) = self.received.iter().position(|b| *b == b'\n') {
+            let line: Vec<_> = self.received.drain(..=end).collect();
+
+            match std::str::from_utf8(&line)
+                .map_err(anyhow::Error::from)
+                .and_then(|line| Ok(ron::from_str(line)?))
+            {
+                Ok(message) => messages.push(message),
+                Err(e) => {
+                    // the peer runs a different version, nothing after this can be trusted
+                    error!("invalid message: {e}");
+                    self.closed = true;
+                    break;
+                }
+            }
+        }
+
+        if self.received.len() > MAX_LINE_BYTES {
+            error!("invalid message: longer than {MAX_LINE_BYTES} bytes");
+            self.closed = true;
+        }
+
+        messages
+    }
+}
@@ -0,0 +1,181 @@
+use std::collections::hash_map::DefaultHasher;
+use std::fmt;
+use std::fmt::Write;
+use std::hash::{Hash, Hasher};
+
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::belts::{Belt, Inventory, Item, ItemType};
+use crate::buildings::mine::Mine;
+use crate::buildings::{Building, BuildingType};
+use crate::direction::MapDirection;
+use crate::map::BuildingTileType;
+
+pub type ChecksumBuildings<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static BuildingType,
+        &'static TilePos,
+        &'static MapDirection,
+        Option<&'static Inventory>,
+        Option<&'static Mine>,
+    ),
+    With<Building>,
+>;
+
+pub type ChecksumBelts<'w, 's> =
+    Query<'w, 's, (&'static TilePos, &'static TileTextureIndex, &'static Belt)>;
+
+// the host found a tick where a player's factory differs from its own
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DesyncReport {
+    pub tick: u64,
+    // the host first, then the player that differs
+    pub checksums: Vec<(u8, u64)>,
+    // every factory stops at this tick, so their dumps can be compared line by line
+    pub stop_tick: u64,
+}
+
+impl fmt::Display for DesyncReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "DESYNC after tick {}", self.tick)?;
+
+        for (player, checksum) in &self.checksums {
+            writeln!(f, "  player {player} checksum {checksum:016x}")?;
+        }
+
+        write!(f, "every factory stopped at tick {}", self.stop_tick)
+    }
+}
+
+fn hash_one(value: impl Hash) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn item_type(items: &Query<&Item>, item: Entity) -> Option<ItemType> {
+    items.get(item).ok().map(|item| item.item_type)
+}
+
+// entities are numbered differently on every peer, so each building and belt is hashed on its
+// own by where it is and the hashes are added up in whatever order they come
+pub fn factory_checksum(
+    buildings: &ChecksumBuildings,
+    belts: &ChecksumBelts,
+    items: &Query<&Item>,
+) -> u64 {
+    let buildings = buildings
+        .iter()
+        .map(|(building_type, origin, direction, inventory, mine)| {
+            let inventory = inventory.map(|inventory| (&inventory.slots, inventory.rejected));
+            hash_one((building_type, origin, direction, inventory, mine))
+        });
+
+    let belts = belts.iter().map(|(tile_pos, texture, belt)| {
+        let mut hasher = DefaultHasher::new();
+        (tile_pos, texture.0, belt.dead_end).hash(&mut hasher);
+
+        for (item, progress) in belt.items.iter() {
+            (item_type(items, *item), progress.to_bits()).hash(&mut hasher);
+        }
+
+        hasher.finish()
+    });
+
+    buildings
+        .chain(belts)
+        .fold(hash_one(items.iter().count()), u64::wrapping_add)
+}
+
+// everything the checksum is made of, one line per building or belt sorted by tile
+pub fn dump_factory(world: &mut World) -> String {
+    let mut lines = Vec::new();
+
+    let mut buildings = world.query_filtered::<(
+        &BuildingType,
+        &TilePos,
+        &MapDirection,
+        Option<&Inventory>,
+        Option<&Mine>,
+    ), With<Building>>();
+
+    for (building_type, origin, direction, inventory, mine) in buildings.iter(world) {
+        let mut line = format!(
+            "{} at {}, {} facing {}",
+            building_type.as_str(),
+            origin.x,
+            origin.y,
+            direction.as_str()
+        );
+
+        if let Some(inventory) = inventory {
+            let slots: Vec<_> = inventory
+                .slots
+                .iter()
+                .enumerate()
+                .filter_map(|(slot, stack)| {
+                    stack.map(|(item_type, amount)| {
+                        format!("{slot}: {} x{amount}", item_type.as_str())
+                    })
+                })
+                .collect();
+
+            let _ = write!(
+                line,
+                " {} slots [{}] rejected {}",
+                inventory.slots.len(),
+                slots.join(", "),
+                inventory.rejected
+            );
+        }
+
+        if let Some(mine) = mine {
+            let _ = write!(line, " {mine:?}");
+        }
+
+        lines.push(((origin.y, origin.x, 0), line));
+    }
+
+    let mut items = world.query::<&Item>();
+    let mut belts = world.query::<(&TilePos, &TileTextureIndex, &Belt)>();
+
+    for (tile_pos, texture, belt) in belts.iter(world) {
+        let belt_items: Vec<_> = belt
+            .items
+            .iter()
+            .map(|(item, progress)| {
+                let item_type = items
+                    .get(world, *item)
+                    .map_or("missing", |item| item.item_type.as_str());
+                format!("{item_type} at {progress:?}")
+            })
+            .collect();
+
+        lines.push((
+            (tile_pos.y, tile_pos.x, 1),
+            format!(
+                "belt at {}, {} {:?} dead end {} [{}]",
+                tile_pos.x,
+                tile_pos.y,
+                BuildingTileType::from(*texture),
+                belt.dead_end,
+                belt_items.join(", ")
+            ),
+        ));
+    }
+
+    lines.sort();
+
+    let mut dump = format!("{} items\n", items.iter(world).count());
+
+    for (_, line) in lines {
+        dump.push_str(&line);
+        dump.push('\n');
+    }
+
+    dump
+}
@@ -0,0 +1,128 @@
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::*;
+
+use crate::belts::Item;
+use crate::buildings::templates::loader::BuildingTemplateLoader;
+use crate::buildings::templates::{
+    load_building_templates, register_building_templates, BuildingTemplate, BuildingTemplateHandles,
+};
+use crate::map::{
+    map_transform, BuildGuideLayer, BuildingLayer, Terrain, TerrainLayer, GRID_SIZE, TILEMAP_SIZE,
+    TILE_SIZE,
+};
+
+// textures and sprites for the simulation, building templates come from the asset server
+// here so they can be hot reloaded together with the tilesets
+pub struct MapRenderPlugin;
+
+impl Plugin for MapRenderPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<BuildingTemplate>()
+            .add_asset_loader(BuildingTemplateLoader)
+            .init_resource::<BuildingTemplateHandles>()
+            .add_systems(Startup, (init_map_layers, load_building_templates))
+            .add_systems(
+                Update,
+                (
+                    register_building_templates,
+                    render_building_layer,
+                    render_items,
+                    render_terrain.run_if(resource_changed::<Terrain>()),
+                ),
+            );
+    }
+}
+
+pub fn init_map_layers(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let terrain_texture = asset_server.load("tilesets/terrain.png");
+    let buildings_texture = asset_server.load("tilesets/buildings.png");
+
+    // Terrain layer
+
+    let mut terrain_storage = TileStorage::empty(TILEMAP_SIZE);
+    let terrain_tilemap = commands.spawn(TerrainLayer).id();
+
+    bevy_ecs_tilemap::helpers::filling::fill_tilemap(
+        TileTextureIndex(0),
+        TILEMAP_SIZE,
+        TilemapId(terrain_tilemap),
+        &mut commands,
+        &mut terrain_storage,
+    );
+
+    commands.entity(terrain_tilemap).insert(TilemapBundle {
+        grid_size: GRID_SIZE,
+        size: TILEMAP_SIZE,
+        storage: terrain_storage,
+        texture: TilemapTexture::Single(terrain_texture),
+        tile_size: TILE_SIZE,
+        transform: map_transform(0.0),
+        ..default()
+    });
+
+    // Build guide layer
+
+    commands
+        .spawn(TilemapBundle {
+            grid_size: GRID_SIZE,
+            size: TILEMAP_SIZE,
+            storage: TileStorage::empty(TILEMAP_SIZE),
+            texture: TilemapTexture::Single(buildings_texture),
+            tile_size: TILE_SIZE,
+            transform: map_transform(2.0),
+            ..default()
+        })
+        .insert(BuildGuideLayer);
+}
+
+// the rest of the tilemap bundle, the storage and transform come from the simulation
+pub fn render_building_layer(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    building_layer: Query<Entity, Added<BuildingLayer>>,
+) {
+    for entity in building_layer.iter() {
+        commands.entity(entity).insert((
+            TilemapTexture::Single(asset_server.load("tilesets/buildings.png")),
+            TilemapSpacing::default(),
+            VisibilityBundle::default(),
+            bevy_ecs_tilemap::FrustumCulling::default(),
+            Handle::<StandardTilemapMaterial>::default(),
+        ));
+    }
+}
+
+// only tiles that differ are touched, the minimap repaints whatever changed
+pub fn render_terrain(
+    terrain: Res<Terrain>,
+    terrain_layer: Query<&TileStorage, With<TerrainLayer>>,
+    mut tiles: Query<(&TilePos, &mut TileTextureIndex)>,
+) {
+    let Ok(terrain_storage) = terrain_layer.get_single() else {
+        return;
+    };
+
+    for entity in terrain_storage.iter().flatten() {
+        if let Ok((tile_pos, mut texture)) = tiles.get_mut(*entity) {
+            let index = terrain.get(*tile_pos) as u32;
+
+            if texture.0 != index {
+                texture.0 = index;
+            }
+        }
+    }
+}
+
+pub fn render_items(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    items: Query<(Entity, &Item), Added<Item>>,
+) {
+    for (entity, item) in items.iter() {
+        commands.entity(entity).insert((
+            Sprite::default(),
+            asset_server.load::<Image, _>(item.item_type.icon()),
+            VisibilityBundle::default(),
+        ));
+    }
+}
@@ -0,0 +1,420 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use bevy::app::AppExit;
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::belts::{InventoryEvent, ItemType, SpawnItemEvent};
+use crate::buildings::{
+    BuildRequestedEvent, BuildingType, DemolishAreaEvent, DemolishEvent, DemolishFilter, Tool,
+};
+use crate::direction::MapDirection;
+use crate::game::{start_game, GameSetup, GameState};
+use crate::map::{MapEvent, TileArea};
+use crate::mods::registry::ModRegistry;
+use crate::save::SaveGame;
+use crate::simulation::{BuildSet, Simulation, SimulationSet};
+
+const REPLAYS_PATH: &str = "replays";
+// every game is recorded, the replay of the previous one is overwritten
+pub const LAST_REPLAY: &str = "last";
+
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            OnExit(GameState::MainMenu),
+            start_recording.before(start_game),
+        )
+        .add_systems(OnEnter(GameState::MainMenu), stop_replay)
+        .add_systems(
+            Update,
+            (
+                record_inputs.run_if(resource_exists::<ReplayRecorder>()),
+                play_inputs.run_if(resource_exists::<ReplayPlayback>()),
+            )
+                .in_set(SimulationSet)
+                .before(BuildSet),
+        )
+        .add_systems(Last, stop_replay.run_if(on_event::<AppExit>()));
+    }
+}
+
+// everything the player did to the factory, together with how the game started
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Replay {
+    pub seed: u64,
+    pub save: Option<SaveGame>,
+    #[serde(default)]
+    pub mods: ModRegistry,
+    pub frames: Vec<ReplayFrame>,
+}
+
+// inputs seen in one frame, they were all handled before the simulation ran `tick`
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ReplayFrame {
+    pub tick: u64,
+    pub inputs: Vec<ReplayInput>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub enum ReplayInput {
+    Build {
+        building_type: BuildingType,
+        tile_pos: (u32, u32),
+        direction: MapDirection,
+    },
+    Demolish {
+        tile_pos: (u32, u32),
+    },
+    DemolishArea {
+        min: (u32, u32),
+        max: (u32, u32),
+        filter: DemolishFilter,
+    },
+    SpawnItem {
+        item_type: ItemType,
+        tile_pos: (u32, u32),
+        amount: usize,
+    },
+    TakeItems {
+        tile_pos: (u32, u32),
+        slot: usize,
+        amount: usize,
+    },
+    ClearSlot {
+        tile_pos: (u32, u32),
+        slot: usize,
+    },
+    ClearInventory {
+        tile_pos: (u32, u32),
+    },
+    RestoreInventory {
+        tile_pos: (u32, u32),
+        slots: Vec<Option<(ItemType, usize)>>,
+    },
+    ToggleGrid,
+    ClearBuildings,
+    Tool(Tool),
+}
+
+impl From<&BuildRequestedEvent> for ReplayInput {
+    fn from(e: &BuildRequestedEvent) -> Self {
+        ReplayInput::Build {
+            building_type: e.building_type,
+            tile_pos: (e.tile_pos.x, e.tile_pos.y),
+            direction: e.direction,
+        }
+    }
+}
+
+impl From<&DemolishEvent> for ReplayInput {
+    fn from(e: &DemolishEvent) -> Self {
+        ReplayInput::Demolish {
+            tile_pos: (e.tile_pos.x, e.tile_pos.y),
+        }
+    }
+}
+
+impl From<&DemolishAreaEvent> for ReplayInput {
+    fn from(e: &DemolishAreaEvent) -> Self {
+        ReplayInput::DemolishArea {
+            min: (e.area.min.x, e.area.min.y),
+            max: (e.area.max.x, e.area.max.y),
+            filter: e.filter,
+        }
+    }
+}
+
+impl From<&SpawnItemEvent> for ReplayInput {
+    fn from(e: &SpawnItemEvent) -> Self {
+        ReplayInput::SpawnItem {
+            item_type: e.item_type,
+            tile_pos: (e.tile_pos.x, e.tile_pos.y),
+            amount: e.amount,
+        }
+    }
+}
+
+impl From<&InventoryEvent> for ReplayInput {
+    fn from(e: &InventoryEvent) -> Self {
+        match e {
+            InventoryEvent::Take {
+                tile_pos,
+                slot,
+                amount,
+            } => ReplayInput::TakeItems {
+                tile_pos: (tile_pos.x, tile_pos.y),
+                slot: *slot,
+                amount: *amount,
+            },
+            InventoryEvent::ClearSlot { tile_pos, slot } => ReplayInput::ClearSlot {
+                tile_pos: (tile_pos.x, tile_pos.y),
+                slot: *slot,
+            },
+            InventoryEvent::Clear { tile_pos } => ReplayInput::ClearInventory {
+                tile_pos: (tile_pos.x, tile_pos.y),
+            },
+            InventoryEvent::Restore { tile_pos, slots } => ReplayInput::RestoreInventory {
+                tile_pos: (tile_pos.x, tile_pos.y),
+                slots: slots.clone(),
+            },
+        }
+    }
+}
+
+impl From<&MapEvent> for ReplayInput {
+    fn from(e: &MapEvent) -> Self {
+        match e {
+            MapEvent::ToggleGrid => ReplayInput::ToggleGrid,
+            MapEvent::ClearBuildings => ReplayInput::ClearBuildings,
+        }
+    }
+}
+
+// every event that changes the factory, for whatever hands recorded inputs back to the game
+#[derive(SystemParam)]
+pub struct FactoryEvents<'w> {
+    pub build: ResMut<'w, Events<BuildRequestedEvent>>,
+    pub demolish: ResMut<'w, Events<DemolishEvent>>,
+    pub demolish_area: ResMut<'w, Events<DemolishAreaEvent>>,
+    pub spawn: ResMut<'w, Events<SpawnItemEvent>>,
+    pub inventory: ResMut<'w, Events<InventoryEvent>>,
+    pub map: ResMut<'w, Events<MapEvent>>,
+}
+
+impl FactoryEvents<'_> {
+    // the tool isn't an event, it's left to the caller
+    pub fn send(&mut self, input: &ReplayInput) {
+        let tile = |(x, y): (u32, u32)| TilePos::new(x, y);
+
+        match input {
+            ReplayInput::Build {
+                building_type,
+                tile_pos,
+                direction,
+            } => self.build.send(BuildRequestedEvent {
+                building_type: *building_type,
+                direction: *direction,
+                tile_pos: tile(*tile_pos),
+            }),
+            ReplayInput::Demolish { tile_pos } => self.demolish.send(DemolishEvent {
+                tile_pos: tile(*tile_pos),
+            }),
+            ReplayInput::DemolishArea { min, max, filter } => {
+                self.demolish_area.send(DemolishAreaEvent {
+                    area: TileArea::from_corners(tile(*min), tile(*max)),
+                    filter: *filter,
+                })
+            }
+            ReplayInput::SpawnItem {
+                item_type,
+                tile_pos,
+                amount,
+            } => self.spawn.send(SpawnItemEvent {
+                item_type: *item_type,
+                tile_pos: tile(*tile_pos),
+                amount: *amount,
+            }),
+            ReplayInput::TakeItems {
+                tile_pos,
+                slot,
+                amount,
+            } => self.inventory.send(InventoryEvent::Take {
+                tile_pos: tile(*tile_pos),
+                slot: *slot,
+                amount: *amount,
+            }),
+            ReplayInput::ClearSlot { tile_pos, slot } => {
+                self.inventory.send(InventoryEvent::ClearSlot {
+                    tile_pos: tile(*tile_pos),
+                    slot: *slot,
+                })
+            }
+            ReplayInput::ClearInventory { tile_pos } => {
+                self.inventory.send(InventoryEvent::Clear {
+                    tile_pos: tile(*tile_pos),
+                })
+            }
+            ReplayInput::RestoreInventory { tile_pos, slots } => {
+                self.inventory.send(InventoryEvent::Restore {
+                    tile_pos: tile(*tile_pos),
+                    slots: slots.clone(),
+                })
+            }
+            ReplayInput::ToggleGrid => self.map.send(MapEvent::ToggleGrid),
+            ReplayInput::ClearBuildings => self.map.send(MapEvent::ClearBuildings),
+            ReplayInput::Tool(_) => {}
+        }
+    }
+}
+
+// the same events read back, in the order they are replayed
+#[derive(SystemParam)]
+pub struct FactoryEventReaders<'w, 's> {
+    pub build: EventReader<'w, 's, BuildRequestedEvent>,
+    pub demolish: EventReader<'w, 's, DemolishEvent>,
+    pub demolish_area: EventReader<'w, 's, DemolishAreaEvent>,
+    pub spawn: EventReader<'w, 's, SpawnItemEvent>,
+    pub inventory: EventReader<'w, 's, InventoryEvent>,
+    pub map: EventReader<'w, 's, MapEvent>,
+}
+
+impl FactoryEventReaders<'_, '_> {
+    pub fn read(&mut self, inputs: &mut Vec<ReplayInput>) {
+        inputs.extend(self.build.iter().map(ReplayInput::from));
+        inputs.extend(self.demolish.iter().map(ReplayInput::from));
+        inputs.extend(self.demolish_area.iter().map(ReplayInput::from));
+        inputs.extend(self.spawn.iter().map(ReplayInput::from));
+        inputs.extend(self.inventory.iter().map(ReplayInput::from));
+        inputs.extend(self.map.iter().map(ReplayInput::from));
+    }
+}
+
+impl Replay {
+    pub fn new(seed: u64, save: Option<SaveGame>) -> Self {
+        Replay {
+            seed,
+            save,
+            mods: ModRegistry::default(),
+            frames: Vec::new(),
+        }
+    }
+
+    pub fn path(name: &str) -> PathBuf {
+        Path::new(REPLAYS_PATH).join(name).with_extension("ron")
+    }
+
+    pub fn read(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+
+        ron::from_str(&fs::read_to_string(path)?)
+            .map_err(|e| anyhow::anyhow!("{}: {e}", path.display()))
+    }
+
+    pub fn write(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let path = path.as_ref();
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        fs::write(path, ron::ser::to_string_pretty(self, Default::default())?)?;
+        Ok(())
+    }
+
+    pub fn last_tick(&self) -> u64 {
+        self.frames.last().map_or(0, |frame| frame.tick)
+    }
+}
+
+#[derive(Resource)]
+pub struct ReplayRecorder(pub Replay);
+
+// feeds a replay back frame by frame, input from the player is ignored until it's done
+#[derive(Resource)]
+pub struct ReplayPlayback {
+    replay: Replay,
+    next_frame: usize,
+}
+
+impl ReplayPlayback {
+    pub fn new(replay: Replay) -> Self {
+        ReplayPlayback {
+            replay,
+            next_frame: 0,
+        }
+    }
+}
+
+pub fn start_recording(
+    mut commands: Commands,
+    setup: Res<GameSetup>,
+    mods: Res<ModRegistry>,
+    playback: Option<Res<ReplayPlayback>>,
+) {
+    if playback.is_none() {
+        commands.insert_resource(ReplayRecorder(Replay {
+            mods: mods.merged(&setup.mods),
+            ..Replay::new(setup.seed, setup.save.clone())
+        }));
+    }
+}
+
+pub fn stop_replay(mut commands: Commands, recorder: Option<Res<ReplayRecorder>>) {
+    if let Some(recorder) = recorder {
+        match recorder.0.write(Replay::path(LAST_REPLAY)) {
+            Ok(()) => info!("replay saved as {LAST_REPLAY}"),
+            Err(e) => error!("failed to save replay: {e}"),
+        }
+    }
+
+    commands.remove_resource::<ReplayRecorder>();
+    commands.remove_resource::<ReplayPlayback>();
+}
+
+pub fn record_inputs(
+    mut recorder: ResMut<ReplayRecorder>,
+    simulation: Res<Simulation>,
+    tool: Option<Res<Tool>>,
+    mut events: FactoryEventReaders,
+) {
+    let mut inputs = Vec::new();
+
+    if let Some(tool) = tool
+        && tool.is_changed()
+    {
+        inputs.push(ReplayInput::Tool(tool.clone()));
+    }
+
+    events.read(&mut inputs);
+
+    if !inputs.is_empty() {
+        recorder.0.frames.push(ReplayFrame {
+            tick: simulation.tick(),
+            inputs,
+        });
+    }
+}
+
+// the simulation is held at the tick of the next frame, so every input lands exactly where it
+// was recorded no matter how fast the replay is played
+pub fn play_inputs(
+    mut commands: Commands,
+    mut playback: ResMut<ReplayPlayback>,
+    mut simulation: ResMut<Simulation>,
+    mut tool: Option<ResMut<Tool>>,
+    mut events: FactoryEvents,
+) {
+    let next_frame = playback.next_frame;
+
+    if let Some(frame) = playback.replay.frames.get(next_frame)
+        && frame.tick <= simulation.tick()
+    {
+        for input in &frame.inputs {
+            match input {
+                ReplayInput::Tool(recorded) => {
+                    if let Some(tool) = tool.as_mut() {
+                        **tool = recorded.clone();
+                    }
+                }
+                input => events.send(input),
+            }
+        }
+
+        playback.next_frame += 1;
+    }
+
+    match playback.replay.frames.get(playback.next_frame) {
+        Some(frame) => simulation.limit_ticks(Some(frame.tick)),
+        None => {
+            simulation.limit_ticks(None);
+            commands.remove_resource::<ReplayPlayback>();
+            info!("replay finished at tick {}", simulation.tick());
+        }
+    }
+}
@@ -0,0 +1,187 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::belts::{Inventory, ItemType};
+use crate::buildings::{Building, BuildingBundle, BuildingType};
+use crate::direction::MapDirection;
+use crate::game::GameSeed;
+use crate::map::BuildingTileType;
+use crate::mods::registry::ModRegistry;
+
+const SAVES_PATH: &str = "saves";
+pub const QUICKSAVE: &str = "quicksave";
+
+pub struct SavePlugin;
+
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SavedInventories>()
+            .add_event::<SaveGameEvent>()
+            .add_systems(Update, (save_game, restore_saved_inventories));
+    }
+}
+
+#[derive(Event)]
+pub struct SaveGameEvent {
+    pub name: &'static str,
+}
+
+// buildings and the contents of their inventories, items on belts are not saved
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SaveGame {
+    pub seed: u64,
+    pub buildings: Vec<SavedBuilding>,
+    #[serde(default)]
+    pub mods: ModRegistry,
+}
+
+pub type SavedSlots = Vec<Option<(ItemType, usize)>>;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SavedBuilding {
+    pub building_type: BuildingType,
+    pub origin: (u32, u32),
+    pub direction: MapDirection,
+    #[serde(default)]
+    pub inventory: SavedSlots,
+}
+
+impl SaveGame {
+    fn path(name: &str) -> PathBuf {
+        Path::new(SAVES_PATH).join(name).with_extension("ron")
+    }
+
+    pub fn write(&self, name: &str) -> anyhow::Result<()> {
+        let contents = ron::ser::to_string_pretty(self, Default::default())?;
+        fs::create_dir_all(SAVES_PATH)?;
+        fs::write(Self::path(name), contents)?;
+        Ok(())
+    }
+
+    // the most recently written save
+    pub fn latest() -> anyhow::Result<Option<SaveGame>> {
+        let Ok(dir) = fs::read_dir(SAVES_PATH) else {
+            return Ok(None);
+        };
+
+        let mut latest = None;
+
+        for entry in dir {
+            let entry = entry?;
+            let path = entry.path();
+
+            if path.extension().is_some_and(|ext| ext == "ron") {
+                let modified = entry.metadata()?.modified()?;
+
+                if latest.as_ref().is_none_or(|(time, _)| modified > *time) {
+                    latest = Some((modified, path));
+                }
+            }
+        }
+
+        let Some((_, path)) = latest else {
+            return Ok(None);
+        };
+
+        let save = ron::from_str(&fs::read_to_string(&path)?)
+            .map_err(|e| anyhow::anyhow!("{}: {e}", path.display()))?;
+
+        Ok(Some(save))
+    }
+
+    // buildings are spawned directly so loading doesn't end up in the undo history,
+    // inventories are filled once the buildings get them
+    pub fn restore(self, commands: &mut Commands) -> SavedInventories {
+        let mut inventories = Vec::new();
+
+        for building in self.buildings {
+            let origin = TilePos::new(building.origin.0, building.origin.1);
+
+            commands.spawn(BuildingBundle {
+                building_type: building.building_type,
+                origin,
+                direction: building.direction,
+            });
+
+            if !building.inventory.is_empty() {
+                inventories.push((origin, building.inventory));
+            }
+        }
+
+        SavedInventories(inventories)
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct SavedInventories(Vec<(TilePos, SavedSlots)>);
+
+pub fn save_game(
+    mut save_events: EventReader<SaveGameEvent>,
+    seed: Res<GameSeed>,
+    mods: Res<ModRegistry>,
+    buildings: Query<(
+        &Building,
+        &BuildingType,
+        &TilePos,
+        &MapDirection,
+        Option<&Inventory>,
+    )>,
+    tile_textures: Query<&TileTextureIndex>,
+) {
+    for event in save_events.iter() {
+        let buildings = buildings
+            .iter()
+            .map(|(building, building_type, origin, direction, inventory)| {
+                // belts can be redirected after they are built, keep their current direction
+                let direction = building
+                    .layout
+                    .tiles
+                    .first()
+                    .and_then(|(e, _, _)| tile_textures.get(*e).ok())
+                    .and_then(|texture| BuildingTileType::from(*texture).belt_direction())
+                    .unwrap_or(*direction);
+
+                SavedBuilding {
+                    building_type: *building_type,
+                    origin: (origin.x, origin.y),
+                    direction,
+                    inventory: inventory.map_or(Vec::new(), |i| i.slots.to_vec()),
+                }
+            })
+            .collect();
+
+        let save = SaveGame {
+            seed: seed.0,
+            buildings,
+            mods: mods.clone(),
+        };
+
+        match save.write(event.name) {
+            Ok(()) => info!("game saved as {}", event.name),
+            Err(e) => error!("failed to save game: {e}"),
+        }
+    }
+}
+
+pub fn restore_saved_inventories(
+    mut saved: ResMut<SavedInventories>,
+    mut inventories: Query<(&TilePos, &mut Inventory), Added<Inventory>>,
+) {
+    if saved.0.is_empty() {
+        return;
+    }
+
+    for (origin, mut inventory) in inventories.iter_mut() {
+        if let Some(index) = saved.0.iter().position(|(tile_pos, _)| tile_pos == origin) {
+            let (_, slots) = saved.0.remove(index);
+
+            for (slot, saved_slot) in inventory.slots.iter_mut().zip(slots) {
+                *slot = saved_slot;
+            }
+        }
+    }
+}
@@ -0,0 +1,204 @@
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy::window::{PrimaryWindow, WindowMode};
+use serde::{Deserialize, Serialize};
+
+use crate::camera::MAX_ZOOM;
+use crate::game::GameState;
+use crate::save::SaveGameEvent;
+
+const SETTINGS_FILE: &str = "settings.ron";
+pub const AUTOSAVE: &str = "autosave";
+
+pub const RESOLUTIONS: [(u32, u32); 4] = [(1270, 720), (1600, 900), (1920, 1080), (2560, 1440)];
+// zooming out further than the camera allows would never hide the grid
+pub const GRID_ZOOMS: [f32; 3] = [1., 2., MAX_ZOOM];
+pub const PAN_SPEEDS: [f32; 4] = [250., 500., 750., 1000.];
+pub const UI_SCALES: [f64; 5] = [0.75, 1., 1.25, 1.5, 2.];
+// minutes, zero turns autosaving off
+pub const AUTOSAVE_INTERVALS: [u32; 5] = [0, 1, 5, 10, 30];
+
+pub struct SettingsPlugin;
+
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        // the defaults until the file is read, so problems with it reach the log
+        app.init_resource::<Settings>()
+            .add_systems(Startup, load_settings)
+            .add_systems(
+                Update,
+                (
+                    apply_settings.run_if(resource_changed::<Settings>()),
+                    autosave.run_if(in_state(GameState::InGame)),
+                ),
+            );
+    }
+}
+
+#[derive(Resource, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub resolution: (u32, u32),
+    pub fullscreen: bool,
+    pub show_grid: bool,
+    // the grid is hidden when zoomed out further than this
+    pub max_grid_zoom: f32,
+    pub pan_speed: f32,
+    pub ui_scale: f64,
+    pub autosave_minutes: u32,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            resolution: (1270, 720),
+            fullscreen: false,
+            show_grid: true,
+            max_grid_zoom: 2.,
+            pan_speed: 500.,
+            ui_scale: 1.,
+            autosave_minutes: 5,
+        }
+    }
+}
+
+// the next value after the current one, wrapping around
+pub fn next_option<T: Copy + PartialEq>(options: &[T], current: T) -> T {
+    let index = options
+        .iter()
+        .position(|o| *o == current)
+        .map_or(0, |i| i + 1);
+    options[index % options.len()]
+}
+
+// settings and controls live together in the user config dir
+pub fn config_path(file: &str) -> PathBuf {
+    let Some(dir) = dirs::config_dir() else {
+        warn!("no user config dir, using {file} in the working directory");
+        return PathBuf::from(file);
+    };
+
+    dir.join("bevactorio").join(file)
+}
+
+impl Settings {
+    fn path() -> PathBuf {
+        config_path(SETTINGS_FILE)
+    }
+
+    // missing settings keep their defaults, a broken file is reported and ignored
+    pub fn load() -> Self {
+        let path = Self::path();
+
+        let Ok(contents) = fs::read_to_string(&path) else {
+            return Settings::default();
+        };
+
+        match ron::from_str::<Settings>(&contents) {
+            Ok(settings) => settings.sanitized(),
+            Err(e) => {
+                error!("invalid settings in {}: {}", path.display(), e);
+                Settings::default()
+            }
+        }
+    }
+
+    // a hand edited file can hold values the window and ui can't use, those get their defaults
+    fn sanitized(mut self) -> Self {
+        let defaults = Settings::default();
+
+        if self.resolution.0 == 0 || self.resolution.1 == 0 {
+            warn!(
+                "invalid resolution {:?}, using the default",
+                self.resolution
+            );
+            self.resolution = defaults.resolution;
+        }
+
+        if !(self.ui_scale.is_finite() && self.ui_scale > 0.) {
+            warn!("invalid ui scale {}, using the default", self.ui_scale);
+            self.ui_scale = defaults.ui_scale;
+        }
+
+        if !(self.pan_speed.is_finite() && self.pan_speed > 0.) {
+            warn!("invalid pan speed {}, using the default", self.pan_speed);
+            self.pan_speed = defaults.pan_speed;
+        }
+
+        if !(self.max_grid_zoom.is_finite()
+            && self.max_grid_zoom > 0.
+            && self.max_grid_zoom <= MAX_ZOOM)
+        {
+            warn!(
+                "invalid grid zoom {}, using the default",
+                self.max_grid_zoom
+            );
+            self.max_grid_zoom = defaults.max_grid_zoom;
+        }
+
+        self
+    }
+
+    pub fn save(&self) -> anyhow::Result<()> {
+        let path = Self::path();
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let contents = ron::ser::to_string_pretty(self, Default::default())?;
+        fs::write(path, contents)?;
+        Ok(())
+    }
+
+    pub fn window_mode(&self) -> WindowMode {
+        match self.fullscreen {
+            true => WindowMode::BorderlessFullscreen,
+            false => WindowMode::Windowed,
+        }
+    }
+
+    pub fn autosave_interval(&self) -> Option<Duration> {
+        (self.autosave_minutes > 0).then(|| Duration::from_secs(self.autosave_minutes as u64 * 60))
+    }
+}
+
+pub fn load_settings(mut settings: ResMut<Settings>) {
+    *settings = Settings::load();
+}
+
+// runs once on startup too, resources are changed when they are added
+pub fn apply_settings(
+    settings: Res<Settings>,
+    mut window: Query<&mut Window, With<PrimaryWindow>>,
+    mut ui_scale: ResMut<UiScale>,
+) {
+    if let Ok(mut window) = window.get_single_mut() {
+        let (width, height) = settings.resolution;
+        window.resolution.set(width as f32, height as f32);
+        window.mode = settings.window_mode();
+    }
+
+    ui_scale.scale = settings.ui_scale;
+}
+
+pub fn autosave(
+    settings: Res<Settings>,
+    time: Res<Time>,
+    mut since_save: Local<Duration>,
+    mut save_events: EventWriter<SaveGameEvent>,
+) {
+    let Some(interval) = settings.autosave_interval() else {
+        return;
+    };
+
+    *since_save += time.delta();
+
+    if *since_save >= interval {
+        *since_save = Duration::ZERO;
+        save_events.send(SaveGameEvent { name: AUTOSAVE });
+    }
+}
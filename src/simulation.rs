@@ -0,0 +1,241 @@
+use std::time::Duration;
+
+use bevy::ecs::schedule::ScheduleLabel;
+use bevy::prelude::*;
+
+use crate::belts::{
+    build_belt, change_inventories, despawn_lost_items, input_from_belts, move_items_on_belts,
+    redirect_belts, spawn_items, Belt, InventoryEvent, SpawnItemEvent,
+};
+use crate::buildings::chest::build_chest;
+use crate::buildings::mine::{build_mine, mine_produce};
+use crate::buildings::templates::BuildingRegistry;
+use crate::buildings::{
+    build_building, construct_building, demolish_building, BuildRequestedEvent,
+    BuildingChangedEvent, DemolishAreaEvent, DemolishEvent,
+};
+use crate::conservation::{check_item_conservation, ItemConservation};
+use crate::map::{clear_buildings, init_map, should_clear_buildings, MapEvent, Terrain};
+use crate::mods::registry::{register_mod_templates, ModRegistry};
+use crate::statistics::ItemFlowEvent;
+
+// length of one simulation step, independent of the frame rate
+pub const TICK: Duration = Duration::from_nanos(1_000_000_000 / 60);
+// catching up after a long frame stops here, so a slow frame can't snowball
+const MAX_TICKS_PER_FRAME: u32 = 16;
+
+// the factory itself: map storage, buildings, belts, mines and chests, without any rendering
+// so it also runs under MinimalPlugins, building templates have to be registered by the user
+pub struct SimulationPlugin;
+
+impl Plugin for SimulationPlugin {
+    fn build(&self, app: &mut App) {
+        // a request is fully built before the ticks of the same frame run, so a factory always
+        // looks the same at a given tick no matter how the ticks were spread over frames
+        let build_systems = (
+            (
+                register_mod_templates,
+                clear_buildings.run_if(should_clear_buildings),
+                redirect_belts,
+                build_building.run_if(on_event::<BuildRequestedEvent>()),
+                demolish_building,
+            )
+                .chain(),
+            apply_deferred,
+            construct_building,
+            apply_deferred,
+            (
+                build_belt,
+                build_mine,
+                build_chest,
+                despawn_lost_items.run_if(any_component_removed::<Belt>()),
+            ),
+            apply_deferred,
+            (
+                spawn_items.run_if(on_event::<SpawnItemEvent>()),
+                change_inventories.run_if(on_event::<InventoryEvent>()),
+            ),
+            apply_deferred,
+        )
+            .chain();
+
+        app.init_resource::<Simulation>()
+            .init_resource::<BuildingRegistry>()
+            .init_resource::<ItemConservation>()
+            .init_resource::<Terrain>()
+            .init_resource::<ModRegistry>()
+            .add_event::<BuildRequestedEvent>()
+            .add_event::<DemolishEvent>()
+            .add_event::<DemolishAreaEvent>()
+            .add_event::<SpawnItemEvent>()
+            .add_event::<InventoryEvent>()
+            .add_event::<BuildingChangedEvent>()
+            .add_event::<ItemFlowEvent>()
+            .add_event::<MapEvent>()
+            .init_schedule(SimulationTick)
+            .add_systems(Startup, init_map)
+            .add_systems(
+                Update,
+                (build_systems.in_set(BuildSet), run_simulation)
+                    .chain()
+                    .in_set(SimulationSet),
+            )
+            .add_systems(
+                SimulationTick,
+                (
+                    mine_produce.before(move_items_on_belts),
+                    move_items_on_belts,
+                    input_from_belts.after(move_items_on_belts),
+                ),
+            );
+
+        // sees the factory as the last tick and the requests since then left it
+        if cfg!(debug_assertions) {
+            app.add_systems(SimulationTick, check_item_conservation.before(mine_produce));
+        }
+    }
+}
+
+// everything that changes the factory, the game only runs it while playing
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SimulationSet;
+
+// turns the requests of this frame into buildings, anything sending them in the same frame
+// has to run before it
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BuildSet;
+
+// systems of the factory simulation, the schedule runs once per tick
+#[derive(ScheduleLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SimulationTick;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SimulationSpeed {
+    Half,
+    #[default]
+    Normal,
+    Double,
+    Quadruple,
+}
+
+impl SimulationSpeed {
+    pub const ALL: [SimulationSpeed; 4] = [
+        SimulationSpeed::Half,
+        SimulationSpeed::Normal,
+        SimulationSpeed::Double,
+        SimulationSpeed::Quadruple,
+    ];
+
+    pub fn multiplier(&self) -> f64 {
+        match self {
+            SimulationSpeed::Half => 0.5,
+            SimulationSpeed::Normal => 1.,
+            SimulationSpeed::Double => 2.,
+            SimulationSpeed::Quadruple => 4.,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            SimulationSpeed::Half => "0.5x",
+            SimulationSpeed::Normal => "1x",
+            SimulationSpeed::Double => "2x",
+            SimulationSpeed::Quadruple => "4x",
+        }
+    }
+
+    pub fn faster(&self) -> Self {
+        match self {
+            SimulationSpeed::Half => SimulationSpeed::Normal,
+            SimulationSpeed::Normal => SimulationSpeed::Double,
+            _ => SimulationSpeed::Quadruple,
+        }
+    }
+
+    pub fn slower(&self) -> Self {
+        match self {
+            SimulationSpeed::Quadruple => SimulationSpeed::Double,
+            SimulationSpeed::Double => SimulationSpeed::Normal,
+            _ => SimulationSpeed::Half,
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct Simulation {
+    pub speed: SimulationSpeed,
+    pub paused: bool,
+    tick: u64,
+    step: bool,
+    accumulator: Duration,
+    tick_limit: Option<u64>,
+}
+
+impl Simulation {
+    pub fn delta(&self) -> Duration {
+        TICK
+    }
+
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        TICK.mul_f64(self.tick as f64)
+    }
+
+    // advances one tick on the next frame, only while paused
+    pub fn step(&mut self) {
+        self.step = self.paused;
+    }
+
+    // the simulation doesn't run past this tick until the limit is moved or removed
+    pub fn limit_ticks(&mut self, limit: Option<u64>) {
+        self.tick_limit = limit;
+    }
+
+    fn pending_ticks(&mut self, real_delta: Duration) -> u32 {
+        let ticks = self.ticks_since_last_frame(real_delta);
+
+        match self.tick_limit {
+            Some(limit) => ticks.min(limit.saturating_sub(self.tick) as u32),
+            None => ticks,
+        }
+    }
+
+    fn ticks_since_last_frame(&mut self, real_delta: Duration) -> u32 {
+        if self.paused {
+            return std::mem::take(&mut self.step) as u32;
+        }
+
+        self.accumulator += real_delta.mul_f64(self.speed.multiplier());
+
+        let mut ticks = 0;
+
+        while self.accumulator >= TICK && ticks < MAX_TICKS_PER_FRAME {
+            self.accumulator -= TICK;
+            ticks += 1;
+        }
+
+        if ticks == MAX_TICKS_PER_FRAME {
+            self.accumulator = Duration::ZERO;
+        }
+
+        ticks
+    }
+}
+
+pub fn run_simulation(world: &mut World) {
+    let real_delta = world.resource::<Time>().delta();
+    let ticks = world.resource_mut::<Simulation>().pending_ticks(real_delta);
+
+    run_ticks(world, ticks);
+}
+
+// runs the tick schedule directly, independent of time and pause, for tests and tools
+pub fn run_ticks(world: &mut World, ticks: u32) {
+    for _ in 0..ticks {
+        world.run_schedule(SimulationTick);
+        world.resource_mut::<Simulation>().tick += 1;
+    }
+}
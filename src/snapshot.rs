@@ -0,0 +1,211 @@
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail};
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::*;
+
+use crate::belts::Belt;
+use crate::buildings::{BuildRequestedEvent, BuildingType};
+use crate::direction::MapDirection;
+use crate::map::{BuildingLayer, BuildingTileType, TileArea};
+
+const EMPTY: char = '.';
+
+// the building layer of an area as text, one character per tile with the top row first, e.g.
+//   .MI
+//   vNE
+//   C..
+// a mine dropping its coal on a belt down into a chest
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MapSnapshot {
+    rows: Vec<Vec<Option<BuildingTileType>>>,
+}
+
+impl MapSnapshot {
+    pub fn capture(world: &mut World, area: TileArea) -> Self {
+        let rows = tile_rows(world, area, |world, tile| {
+            world
+                .get::<TileTextureIndex>(tile)
+                .map(|texture| BuildingTileType::from(*texture))
+        });
+
+        MapSnapshot { rows }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.rows.first().map_or(0, |row| row.len() as u32)
+    }
+
+    pub fn height(&self) -> u32 {
+        self.rows.len() as u32
+    }
+
+    // tiles covered by the snapshot with its bottom left corner at `origin`
+    pub fn area(&self, origin: TilePos) -> TileArea {
+        TileArea {
+            min: origin,
+            max: TilePos::new(
+                origin.x + self.width().saturating_sub(1),
+                origin.y + self.height().saturating_sub(1),
+            ),
+        }
+    }
+
+    // relative to the bottom left corner, like tiles on the map
+    pub fn get(&self, x: u32, y: u32) -> Option<BuildingTileType> {
+        let row = self.height().checked_sub(y + 1)?;
+        self.rows
+            .get(row as usize)?
+            .get(x as usize)
+            .copied()
+            .flatten()
+    }
+
+    // the buildings that make up the snapshot, placed with its bottom left corner at `origin`,
+    // mines and chests can't tell their direction and face the default one
+    pub fn build_requests(&self, origin: TilePos) -> anyhow::Result<Vec<BuildRequestedEvent>> {
+        use BuildingTileType::*;
+
+        let mut requests = Vec::new();
+        let request = |building_type, x, y, direction| BuildRequestedEvent {
+            building_type,
+            direction,
+            tile_pos: TilePos::new(origin.x + x, origin.y + y),
+        };
+
+        for y in 0..self.height() {
+            for x in 0..self.width() {
+                let Some(tile_type) = self.get(x, y) else {
+                    continue;
+                };
+
+                if let Some(direction) = tile_type.belt_direction() {
+                    requests.push(request(BuildingType::Belt, x, y, direction));
+                    continue;
+                }
+
+                match tile_type {
+                    Chest => {
+                        requests.push(request(BuildingType::Chest, x, y, MapDirection::default()))
+                    }
+                    // the origin of a mine is its bottom left tile
+                    MineBottomLeft => {
+                        let complete = self.get(x, y + 1) == Some(MineTopLeft)
+                            && self.get(x + 1, y + 1) == Some(MineTopRight)
+                            && self.get(x + 1, y) == Some(MineBottomRight);
+
+                        if !complete {
+                            bail!("incomplete mine at {x}, {y}");
+                        }
+
+                        requests.push(request(BuildingType::Mine, x, y, MapDirection::default()));
+                    }
+                    MineTopLeft | MineTopRight | MineBottomRight => {
+                        let (dx, dy) = match tile_type {
+                            MineTopLeft => (0, 1),
+                            MineTopRight => (1, 1),
+                            _ => (1, 0),
+                        };
+
+                        let origin = x.checked_sub(dx).zip(y.checked_sub(dy));
+
+                        if origin.and_then(|(x, y)| self.get(x, y)) != Some(MineBottomLeft) {
+                            bail!("incomplete mine at {x}, {y}");
+                        }
+                    }
+                    _ => bail!("can't build {} at {x}, {y}", tile_type.as_char()),
+                }
+            }
+        }
+
+        Ok(requests)
+    }
+}
+
+impl fmt::Display for MapSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for row in &self.rows {
+            let line: String = row
+                .iter()
+                .map(|tile| tile.map_or(EMPTY, |tile_type| tile_type.as_char()))
+                .collect();
+            writeln!(f, "{line}")?;
+        }
+
+        Ok(())
+    }
+}
+
+// surrounding blank lines and indentation are ignored, so snapshots can be written inline
+impl FromStr for MapSnapshot {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let rows = s
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .enumerate()
+            .map(|(line, text)| {
+                text.chars()
+                    .map(|c| match c {
+                        EMPTY => Ok(None),
+                        c => BuildingTileType::from_char(c)
+                            .map(Some)
+                            .ok_or_else(|| anyhow!("unknown tile {c} in line {}", line + 1)),
+                    })
+                    .collect::<anyhow::Result<Vec<_>>>()
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        if let Some(first) = rows.first()
+            && let Some(line) = rows.iter().position(|row| row.len() != first.len())
+        {
+            bail!("line {} isn't as long as the first one", line + 1);
+        }
+
+        Ok(MapSnapshot { rows })
+    }
+}
+
+// how many items each belt of an area carries, in the same layout as a snapshot, a belt with
+// more than nine is drawn as +
+pub fn capture_belt_items(world: &mut World, area: TileArea) -> String {
+    let rows = tile_rows(world, area, |world, tile| {
+        world
+            .get::<Belt>(tile)
+            .map(|belt| char::from_digit(belt.items.len() as u32, 10).unwrap_or('+'))
+    });
+
+    let mut text = String::new();
+
+    for row in rows {
+        text.extend(row.into_iter().map(|count| count.unwrap_or(EMPTY)));
+        text.push('\n');
+    }
+
+    text
+}
+
+fn tile_rows<T>(
+    world: &mut World,
+    area: TileArea,
+    tile: impl Fn(&World, Entity) -> Option<T>,
+) -> Vec<Vec<Option<T>>> {
+    let mut building_layer = world.query_filtered::<&TileStorage, With<BuildingLayer>>();
+    let storage = building_layer.get_single(world).ok();
+
+    (area.min.y..=area.max.y)
+        .rev()
+        .map(|y| {
+            (area.min.x..=area.max.x)
+                .map(|x| {
+                    storage
+                        .and_then(|storage| storage.checked_get(&TilePos::new(x, y)))
+                        .and_then(|entity| tile(world, entity))
+                })
+                .collect()
+        })
+        .collect()
+}
@@ -0,0 +1,206 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+
+use crate::belts::ItemType;
+use crate::simulation::Simulation;
+
+pub const SAMPLES: usize = 60;
+
+pub struct StatisticsPlugin;
+
+impl Plugin for StatisticsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ProductionStatistics>()
+            .add_event::<ItemFlowEvent>()
+            .add_systems(Update, record_item_flow);
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ItemFlow {
+    Produced,
+    Consumed,
+}
+
+impl ItemFlow {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ItemFlow::Produced => "produced",
+            ItemFlow::Consumed => "consumed",
+        }
+    }
+}
+
+#[derive(Event)]
+pub struct ItemFlowEvent {
+    pub item_type: ItemType,
+    pub flow: ItemFlow,
+    pub amount: u32,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum StatisticsWindow {
+    #[default]
+    Minute,
+    TenMinutes,
+    Hour,
+}
+
+impl StatisticsWindow {
+    pub const ALL: [StatisticsWindow; 3] = [
+        StatisticsWindow::Minute,
+        StatisticsWindow::TenMinutes,
+        StatisticsWindow::Hour,
+    ];
+
+    pub fn duration(&self) -> Duration {
+        match self {
+            StatisticsWindow::Minute => Duration::from_secs(60),
+            StatisticsWindow::TenMinutes => Duration::from_secs(10 * 60),
+            StatisticsWindow::Hour => Duration::from_secs(60 * 60),
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            StatisticsWindow::Minute => "1M",
+            StatisticsWindow::TenMinutes => "10M",
+            StatisticsWindow::Hour => "1H",
+        }
+    }
+
+    fn index(&self) -> usize {
+        match self {
+            StatisticsWindow::Minute => 0,
+            StatisticsWindow::TenMinutes => 1,
+            StatisticsWindow::Hour => 2,
+        }
+    }
+}
+
+// item counts over one window, split into SAMPLES buckets
+pub struct FlowHistory {
+    buckets: [u32; SAMPLES],
+    bucket_duration: Duration,
+    current: u64,
+    // since the statistics started, a window only covers that much until it is full
+    elapsed: Duration,
+}
+
+impl FlowHistory {
+    fn new(window: StatisticsWindow) -> Self {
+        FlowHistory {
+            buckets: [0; SAMPLES],
+            bucket_duration: window.duration() / SAMPLES as u32,
+            current: 0,
+            elapsed: Duration::ZERO,
+        }
+    }
+
+    fn advance(&mut self, now: Duration) {
+        let bucket = (now.as_secs_f64() / self.bucket_duration.as_secs_f64()) as u64;
+
+        // clear buckets that were skipped since the last update
+        for skipped in (self.current + 1..=bucket).take(SAMPLES) {
+            self.buckets[skipped as usize % SAMPLES] = 0;
+        }
+
+        self.current = self.current.max(bucket);
+        self.elapsed = self.elapsed.max(now);
+    }
+
+    fn record(&mut self, now: Duration, amount: u32) {
+        self.advance(now);
+        self.buckets[self.current as usize % SAMPLES] += amount;
+    }
+
+    pub fn total(&self) -> u32 {
+        self.buckets.iter().sum()
+    }
+
+    // oldest sample first
+    pub fn samples(&self) -> impl Iterator<Item = u32> + '_ {
+        (1..=SAMPLES).map(|offset| self.buckets[(self.current as usize + offset) % SAMPLES])
+    }
+}
+
+pub struct ItemHistory {
+    windows: [FlowHistory; 3],
+}
+
+impl Default for ItemHistory {
+    fn default() -> Self {
+        ItemHistory {
+            windows: StatisticsWindow::ALL.map(FlowHistory::new),
+        }
+    }
+}
+
+impl ItemHistory {
+    pub fn window(&self, window: StatisticsWindow) -> &FlowHistory {
+        &self.windows[window.index()]
+    }
+
+    // items per minute averaged over the window, or the time so far while that is shorter
+    pub fn rate(&self, window: StatisticsWindow) -> f32 {
+        let history = self.window(window);
+        let covered = history.elapsed.min(window.duration()).as_secs_f32();
+
+        if covered > 0. {
+            history.total() as f32 * 60. / covered
+        } else {
+            0.
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct ProductionStatistics {
+    items: HashMap<(ItemType, ItemFlow), ItemHistory>,
+}
+
+impl ProductionStatistics {
+    pub fn record(&mut self, now: Duration, item_type: ItemType, flow: ItemFlow, amount: u32) {
+        let history = self.items.entry((item_type, flow)).or_default();
+
+        for window in history.windows.iter_mut() {
+            window.record(now, amount);
+        }
+    }
+
+    pub fn advance(&mut self, now: Duration) {
+        for history in self.items.values_mut() {
+            for window in history.windows.iter_mut() {
+                window.advance(now);
+            }
+        }
+    }
+
+    pub fn get(&self, item_type: ItemType, flow: ItemFlow) -> Option<&ItemHistory> {
+        self.items.get(&(item_type, flow))
+    }
+
+    pub fn item_types(&self) -> Vec<ItemType> {
+        let mut item_types: Vec<_> = self.items.keys().map(|(item_type, _)| *item_type).collect();
+        item_types.sort_by_key(|item_type| item_type.as_str());
+        item_types.dedup();
+        item_types
+    }
+}
+
+pub fn record_item_flow(
+    mut events: EventReader<ItemFlowEvent>,
+    mut statistics: ResMut<ProductionStatistics>,
+    simulation: Res<Simulation>,
+) {
+    // statistics follow the simulation, so they stand still while paused
+    let now = simulation.elapsed();
+
+    for event in events.iter() {
+        statistics.record(now, event.item_type, event.flow, event.amount);
+    }
+
+    statistics.advance(now);
+}
@@ -1,6 +1,67 @@
+use bevy::input::InputSystem;
 use bevy::prelude::*;
 
+use crate::belts::{Belt, Inventory};
+use crate::buildings::alerts::{
+    collect_alerts, publish_alerts, ActiveAlerts, CollectAlerts, CollectedAlerts,
+};
+use crate::buildings::mine::Mine;
+use crate::buildings::status::{collect_building_status, CollectBuildingStatus, InspectedBuilding};
 use crate::buildings::{BuildTool, BuildingType, Tool};
+use crate::game::GameState;
+use crate::input::{Action, ActionMap};
+use crate::simulation::SimulationSet;
+use crate::ui::menu::MenuScreen;
+
+pub mod alerts;
+pub mod console;
+pub mod debug;
+pub mod info;
+pub mod inventory;
+pub mod menu;
+pub mod minimap;
+pub mod speed;
+pub mod statistics;
+
+#[derive(Component)]
+pub struct HelpText;
+
+#[derive(Component)]
+pub struct ShortcutLabel {
+    label: &'static str,
+    action: Action,
+}
+
+impl ShortcutLabel {
+    fn new(label: &'static str, action: Action) -> Self {
+        ShortcutLabel { label, action }
+    }
+
+    // highlights the shortcut inside the label when it's a single letter, appends it otherwise
+    fn sections(&self, shortcut: &str, style: &TextStyle) -> Vec<TextSection> {
+        let highlight = TextStyle {
+            color: Color::rgb_u8(179, 24, 0),
+            ..style.clone()
+        };
+
+        let section = |value: &str, style: &TextStyle| TextSection {
+            value: value.to_string(),
+            style: style.clone(),
+        };
+
+        match self.label.find(shortcut).filter(|_| shortcut.len() == 1) {
+            Some(index) => vec![
+                section(&self.label[..index], style),
+                section(shortcut, &highlight),
+                section(&self.label[index + 1..], style),
+            ],
+            None => vec![
+                section(self.label, style),
+                section(&format!(" ({shortcut})"), &highlight),
+            ],
+        }
+    }
+}
 
 #[derive(Component, Clone)]
 pub struct SelectToolAction(Tool, fn(&Tool, &Tool) -> bool);
@@ -15,14 +76,117 @@ pub struct UiPlugin;
 
 impl Plugin for UiPlugin {
     fn build(&self, app: &mut App) {
-        app.init_resource::<MapInteraction>()
-            .add_systems(Startup, init_ui)
+        app.add_state::<MenuScreen>()
+            .init_resource::<MapInteraction>()
+            .init_resource::<InspectedBuilding>()
+            .init_resource::<ActiveAlerts>()
+            .init_resource::<CollectedAlerts>()
+            .init_resource::<inventory::Hand>()
+            .init_resource::<statistics::StatisticsView>()
+            .init_resource::<debug::DebugOverlay>()
+            .init_resource::<console::Console>()
+            .add_event::<UiEvent>()
+            .add_systems(
+                Startup,
+                (
+                    init_ui,
+                    minimap::init_minimap,
+                    info::init_info_panel,
+                    statistics::init_statistics_panel,
+                    speed::init_speed_indicator,
+                    alerts::init_alert_list,
+                    debug::init_debug_inspector,
+                    console::init_console,
+                ),
+            )
             .add_systems(
                 Update,
                 (
                     handle_select_tool,
                     highlight_selected_tool,
+                    update_shortcut_labels.run_if(resource_changed::<ActionMap>()),
                     track_ui_interaction,
+                    minimap::update_minimap_tiles,
+                    minimap::update_minimap_viewport,
+                    minimap::minimap_navigation,
+                    inventory::open_inventory_window,
+                    inventory::update_inventory_window,
+                    inventory::update_hand_text.run_if(resource_changed::<inventory::Hand>()),
+                    inventory::handle_inventory_actions.before(SimulationSet),
+                    statistics::toggle_statistics_panel,
+                    statistics::select_statistics_window,
+                    statistics::update_statistics_panel,
+                    speed::update_speed_indicator,
+                ),
+            )
+            .add_systems(
+                Update,
+                (
+                    info::update_inspected_building.before(CollectBuildingStatus),
+                    (
+                        collect_building_status::<Mine>,
+                        collect_building_status::<Belt>,
+                        collect_building_status::<Inventory>,
+                    )
+                        .in_set(CollectBuildingStatus),
+                    info::update_info_panel.after(CollectBuildingStatus),
+                ),
+            )
+            .add_systems(
+                Update,
+                (
+                    (
+                        collect_alerts::<Mine>,
+                        collect_alerts::<Belt>,
+                        collect_alerts::<Inventory>,
+                    )
+                        .in_set(CollectAlerts),
+                    publish_alerts.after(CollectAlerts),
+                    alerts::update_alerts
+                        .after(publish_alerts)
+                        .run_if(resource_changed::<ActiveAlerts>()),
+                    alerts::jump_to_alert,
+                ),
+            )
+            .add_systems(
+                Update,
+                (
+                    debug::toggle_debug_overlay,
+                    debug::update_debug_labels.after(debug::toggle_debug_overlay),
+                    (debug::draw_debug_overlay, debug::update_debug_inspector)
+                        .run_if(debug::is_debug_overlay_enabled),
+                ),
+            )
+            .add_systems(
+                PreUpdate,
+                (console::toggle_console, console::edit_console_input)
+                    .chain()
+                    .after(InputSystem)
+                    .run_if(in_state(GameState::InGame)),
+            )
+            .add_systems(
+                Update,
+                (
+                    console::run_console_commands.before(SimulationSet),
+                    console::update_console_panel.run_if(resource_changed::<console::Console>()),
+                ),
+            )
+            .add_systems(OnEnter(GameState::MainMenu), menu::open_main_menu)
+            .add_systems(OnEnter(GameState::Paused), menu::open_pause_menu)
+            .add_systems(OnEnter(GameState::InGame), menu::close_menu)
+            .add_systems(OnEnter(MenuScreen::Main), menu::spawn_main_menu)
+            .add_systems(OnEnter(MenuScreen::Pause), menu::spawn_pause_menu)
+            .add_systems(OnEnter(MenuScreen::Settings), menu::spawn_settings_menu)
+            .add_systems(OnExit(MenuScreen::Main), menu::despawn_menu)
+            .add_systems(OnExit(MenuScreen::Pause), menu::despawn_menu)
+            .add_systems(OnExit(MenuScreen::Settings), menu::despawn_menu)
+            .add_systems(
+                Update,
+                (
+                    menu::handle_menu_buttons,
+                    menu::handle_menu_key,
+                    menu::edit_seed.run_if(in_state(MenuScreen::Main)),
+                    menu::handle_setting_buttons.run_if(in_state(MenuScreen::Settings)),
                 ),
             );
     }
@@ -45,7 +209,7 @@ pub fn init_ui(mut commands: Commands, asset_server: Res<AssetServer>) {
 
     let font = asset_server.load("AsepriteFont.ttf");
 
-    let button_builder = |parent: &mut ChildBuilder, text, action| {
+    let button_builder = |parent: &mut ChildBuilder, label: ShortcutLabel, action| {
         parent
             .spawn(ButtonBundle {
                 style: Style {
@@ -67,46 +231,14 @@ pub fn init_ui(mut commands: Commands, asset_server: Res<AssetServer>) {
             })
             .insert(action)
             .with_children(|button| {
-                button.spawn(TextBundle { text, ..default() });
+                button.spawn(TextBundle::default()).insert(label);
             });
     };
 
     building_menu.with_children(|menu| {
-        let button_text = |before, shortcut: char, after| {
-            let style = TextStyle {
-                font: font.clone(),
-                color: Color::DARK_GRAY,
-                font_size: 24.,
-                ..default()
-            };
-
-            let sections = vec![
-                TextSection {
-                    value: before,
-                    style: style.clone(),
-                },
-                TextSection {
-                    value: shortcut.into(),
-                    style: TextStyle {
-                        color: Color::rgb_u8(179, 24, 0),
-                        ..style.clone()
-                    },
-                },
-                TextSection {
-                    value: after,
-                    style,
-                },
-            ];
-
-            Text {
-                sections,
-                ..default()
-            }
-        };
-
         button_builder(
             menu,
-            button_text("LAY ".to_string(), 'B', "ELTS".to_string()),
+            ShortcutLabel::new("LAY BELTS", Action::SelectBelt),
             SelectToolAction(
                 Tool::Build(BuildTool {
                     building: BuildingType::Belt,
@@ -118,7 +250,7 @@ pub fn init_ui(mut commands: Commands, asset_server: Res<AssetServer>) {
 
         button_builder(
             menu,
-            button_text("BUILD ".to_string(), 'M', "INE".to_string()),
+            ShortcutLabel::new("BUILD MINE", Action::SelectMine),
             SelectToolAction(
                 Tool::Build(BuildTool {
                     building: BuildingType::Mine,
@@ -130,7 +262,7 @@ pub fn init_ui(mut commands: Commands, asset_server: Res<AssetServer>) {
 
         button_builder(
             menu,
-            button_text("PLACE ".to_string(), 'C', "HEST".to_string()),
+            ShortcutLabel::new("PLACE CHEST", Action::SelectChest),
             SelectToolAction(
                 Tool::Build(BuildTool {
                     building: BuildingType::Chest,
@@ -142,9 +274,15 @@ pub fn init_ui(mut commands: Commands, asset_server: Res<AssetServer>) {
 
         button_builder(
             menu,
-            button_text("".to_string(), 'D', "EMOLISH".to_string()),
+            ShortcutLabel::new("DEMOLISH", Action::SelectBulldozer),
             SelectToolAction(Tool::Buldozer, PartialEq::eq),
         );
+
+        button_builder(
+            menu,
+            ShortcutLabel::new("BLUEPRINT", Action::SelectBlueprint),
+            SelectToolAction(Tool::Copy, is_blueprint_tool),
+        );
     });
 
     commands
@@ -160,18 +298,20 @@ pub fn init_ui(mut commands: Commands, asset_server: Res<AssetServer>) {
             ..default()
         })
         .with_children(|help_box| {
-            help_box.spawn(TextBundle {
-                text: Text::from_section(
-                    HELP_TEXT.to_string(),
-                    TextStyle {
-                        font: font.clone(),
-                        font_size: 16.,
-                        color: Color::DARK_GRAY,
-                        ..default()
-                    },
-                ),
-                ..default()
-            });
+            help_box
+                .spawn(TextBundle {
+                    text: Text::from_section(
+                        "",
+                        TextStyle {
+                            font: font.clone(),
+                            font_size: 16.,
+                            color: Color::DARK_GRAY,
+                            ..default()
+                        },
+                    ),
+                    ..default()
+                })
+                .insert(HelpText);
         });
 }
 
@@ -210,8 +350,13 @@ pub fn highlight_selected_tool(
     }
 }
 
+#[derive(Event)]
+pub enum UiEvent {
+    ToggleStatistics,
+}
+
 #[derive(Resource, Default)]
-pub struct MapInteraction(bool);
+pub struct MapInteraction(pub bool);
 
 impl MapInteraction {
     pub fn is_allowed(&self) -> bool {
@@ -237,11 +382,83 @@ fn is_same_building_tool(a: &Tool, b: &Tool) -> bool {
     }
 }
 
-const HELP_TEXT: &str = r#"
-arrows - move camera
+fn is_blueprint_tool(_: &Tool, b: &Tool) -> bool {
+    matches!(b, Tool::Copy | Tool::Blueprint(_))
+}
+
+// shortcuts and the help box are generated from the action map so they follow rebinding
+pub fn update_shortcut_labels(
+    actions: Res<ActionMap>,
+    asset_server: Res<AssetServer>,
+    mut labels: Query<(&ShortcutLabel, &mut Text)>,
+    mut help_text: Query<&mut Text, (With<HelpText>, Without<ShortcutLabel>)>,
+) {
+    let style = TextStyle {
+        font: asset_server.load("AsepriteFont.ttf"),
+        color: Color::DARK_GRAY,
+        font_size: 24.,
+    };
+
+    for (label, mut text) in labels.iter_mut() {
+        text.sections = label.sections(&actions.label(label.action), &style);
+    }
+
+    for mut text in help_text.iter_mut() {
+        text.sections[0].value = help_text_for(&actions);
+    }
+}
+
+fn help_text_for(actions: &ActionMap) -> String {
+    let key = |action| actions.label(action);
+
+    format!(
+        r#"
+{}/{}/{}/{} - move camera
+drag {} - move camera
 scroll - camera zoom
-G - toggle grid
-C - clear all buildings
+{} - toggle grid
+{} - clear all buildings
+{} - production statistics
+{} - pause, {} - single step while paused
+{} / {} - simulation speed
+{} - menu
+{} - debug overlay
+{} - console
+{} - copy building under cursor
+{} - rotate
+{} / {} - undo / redo
+drag to demolish an area
+  {} - belts only, {} - no belts
+drag with blueprint tool to copy an area
+  {} - save to library, {} - load from library
+  {} / {} - export / import through clipboard
 
 hightlighted shortcuts in build menu
-"#;
+"#,
+        key(Action::CameraUp),
+        key(Action::CameraLeft),
+        key(Action::CameraDown),
+        key(Action::CameraRight),
+        key(Action::CameraPan),
+        key(Action::ToggleGrid),
+        key(Action::ClearBuildings),
+        key(Action::ToggleStatistics),
+        key(Action::TogglePause),
+        key(Action::StepSimulation),
+        key(Action::SlowDown),
+        key(Action::SpeedUp),
+        key(Action::Menu),
+        key(Action::ToggleDebugOverlay),
+        key(Action::ToggleConsole),
+        key(Action::Pipette),
+        key(Action::Rotate),
+        key(Action::Undo),
+        key(Action::Redo),
+        key(Action::DemolishBeltsOnly),
+        key(Action::DemolishBuildingsOnly),
+        key(Action::SaveBlueprint),
+        key(Action::LoadBlueprint),
+        key(Action::ExportBlueprint),
+        key(Action::ImportBlueprint),
+    )
+}
@@ -0,0 +1,174 @@
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::*;
+
+use crate::buildings::alerts::ActiveAlerts;
+use crate::buildings::{Building, BuildingType};
+use crate::camera::MainCamera;
+use crate::map::{tile_center, BuildingLayer, TileArea, TILEMAP_SIZE, TILE_SIZE};
+use crate::ui::minimap::MINIMAP_SCALE;
+
+#[derive(Component)]
+pub struct AlertList;
+
+#[derive(Component)]
+pub struct AlertIcon;
+
+#[derive(Component)]
+pub struct AlertButton {
+    building: Entity,
+}
+
+pub fn init_alert_list(mut commands: Commands) {
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                flex_direction: FlexDirection::Column,
+                // below the minimap
+                top: Val::Px(32. + TILEMAP_SIZE.y as f32 * MINIMAP_SCALE),
+                right: Val::Px(16.),
+                padding: UiRect::all(Val::Px(8.)),
+                ..default()
+            },
+            background_color: Color::WHITE.into(),
+            visibility: Visibility::Hidden,
+            ..default()
+        })
+        .insert(AlertList);
+}
+
+// centre of the building in world coordinates, half its layout away from the corner tile
+fn building_centre(building: &Building, map_transform: &Transform) -> Option<Vec2> {
+    let mut tiles = building
+        .layout
+        .tiles
+        .iter()
+        .map(|(_, tile_pos, _)| *tile_pos);
+    let first = tiles.next()?;
+
+    let area = tiles.fold(TileArea::from_corners(first, first), |area, tile_pos| {
+        TileArea::from_corners(
+            TilePos::new(area.min.x.min(tile_pos.x), area.min.y.min(tile_pos.y)),
+            TilePos::new(area.max.x.max(tile_pos.x), area.max.y.max(tile_pos.y)),
+        )
+    });
+
+    let half_size = Vec2::new(
+        (area.max.x - area.min.x) as f32 * TILE_SIZE.x,
+        (area.max.y - area.min.y) as f32 * TILE_SIZE.y,
+    ) / 2.;
+
+    Some(tile_center(area.min, map_transform) + half_size)
+}
+
+pub fn update_alerts(
+    mut commands: Commands,
+    alerts: Res<ActiveAlerts>,
+    buildings: Query<(&Building, &BuildingType)>,
+    icons: Query<Entity, With<AlertIcon>>,
+    list: Query<Entity, With<AlertList>>,
+    map: Query<&Transform, With<BuildingLayer>>,
+    asset_server: Res<AssetServer>,
+) {
+    let (Ok(list), Ok(map_transform)) = (list.get_single(), map.get_single()) else {
+        return;
+    };
+
+    for icon in icons.iter() {
+        commands.entity(icon).despawn_recursive();
+    }
+
+    let font = asset_server.load("AsepriteFont.ttf");
+
+    let icon_style = TextStyle {
+        font: font.clone(),
+        font_size: 16.,
+        color: Color::rgb_u8(179, 24, 0),
+    };
+
+    let list_style = TextStyle {
+        font,
+        font_size: 16.,
+        color: Color::DARK_GRAY,
+    };
+
+    let mut iconified = Vec::new();
+
+    commands.entity(list).despawn_descendants();
+    commands
+        .entity(list)
+        .insert(match alerts.alerts.is_empty() {
+            true => Visibility::Hidden,
+            false => Visibility::Inherited,
+        });
+
+    for (building_entity, kind) in alerts.alerts.iter() {
+        let Ok((building, building_type)) = buildings.get(*building_entity) else {
+            continue;
+        };
+
+        let Some(centre) = building_centre(building, map_transform) else {
+            continue;
+        };
+
+        // one icon per building even if it has more alerts
+        if !iconified.contains(building_entity) {
+            iconified.push(*building_entity);
+
+            commands
+                .spawn(Text2dBundle {
+                    text: Text::from_section("!", icon_style.clone()),
+                    transform: Transform::from_translation(centre.extend(20.)),
+                    ..default()
+                })
+                .insert(AlertIcon);
+        }
+
+        commands.entity(list).with_children(|list| {
+            list.spawn(ButtonBundle {
+                style: Style {
+                    padding: UiRect::axes(Val::Px(8.), Val::Px(4.)),
+                    ..default()
+                },
+                ..default()
+            })
+            .insert(AlertButton {
+                building: *building_entity,
+            })
+            .with_children(|button| {
+                button.spawn(TextBundle::from_section(
+                    format!(
+                        "{} {}",
+                        building_type.as_str().to_uppercase(),
+                        kind.message()
+                    ),
+                    list_style.clone(),
+                ));
+            });
+        });
+    }
+}
+
+pub fn jump_to_alert(
+    actions: Query<(&AlertButton, &Interaction), Changed<Interaction>>,
+    buildings: Query<&Building>,
+    map: Query<&Transform, (With<BuildingLayer>, Without<MainCamera>)>,
+    mut camera: Query<&mut Transform, With<MainCamera>>,
+) {
+    let (Ok(map_transform), Ok(mut camera_transform)) = (map.get_single(), camera.get_single_mut())
+    else {
+        return;
+    };
+
+    let Some(centre) = actions
+        .iter()
+        .filter(|(_, interaction)| matches!(interaction, Interaction::Pressed))
+        .find_map(|(button, _)| buildings.get(button.building).ok())
+        .and_then(|building| building_centre(building, map_transform))
+    else {
+        return;
+    };
+
+    camera_transform.translation.x = centre.x;
+    camera_transform.translation.y = centre.y;
+}
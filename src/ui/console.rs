@@ -0,0 +1,417 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::path::PathBuf;
+use std::str::{FromStr, SplitWhitespace};
+
+use anyhow::{anyhow, bail};
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::*;
+
+use crate::belts::{ItemType, SpawnItemEvent};
+use crate::buildings::{BuildRequestedEvent, BuildingType, DemolishAreaEvent, DemolishFilter};
+use crate::camera::MainCamera;
+use crate::direction::MapDirection;
+use crate::input::actions::Chord;
+use crate::input::{Action, ActionInput, ActionMap};
+use crate::map::{tile_center, BuildingLayer, TileArea, TILEMAP_SIZE};
+use crate::simulation::{Simulation, SimulationSpeed};
+use crate::ui::debug::describe_tile;
+
+const MAX_LINES: usize = 20;
+// a script running itself would otherwise grow the queue forever
+const MAX_QUEUED_COMMANDS: usize = 10_000;
+
+const HELP: &str = r#"build <belt|mine|chest> <x> <y> [up|down|left|right]
+spawn <item> <x> <y> [amount] - onto a belt or into a chest
+clear <x1> <y1> <x2> <y2> [all|belts|buildings]
+speed <0.5x|1x|2x|4x>
+pause - pause or resume
+camera <x> <y>
+dump <x> <y> - components of the tile, its building and items
+run <file> - one command per line, # starts a comment
+bind <action> <chord> - e.g. bind Undo ctrl+Z, saved to controls.ron"#;
+
+#[derive(Resource, Default)]
+pub struct Console {
+    pub open: bool,
+    input: String,
+    lines: VecDeque<String>,
+    history: Vec<String>,
+    // how far back the input was taken from the history
+    history_pos: Option<usize>,
+    // commands run one per frame, so each sees the buildings of the ones before it
+    queue: VecDeque<QueuedCommand>,
+}
+
+struct QueuedCommand {
+    line: String,
+    // file and line number when the command comes from a script
+    source: Option<String>,
+}
+
+impl Console {
+    pub fn print(&mut self, text: &str) {
+        self.lines.extend(text.lines().map(String::from));
+
+        while self.lines.len() > MAX_LINES {
+            self.lines.pop_front();
+        }
+    }
+
+    fn submit(&mut self) {
+        let line = std::mem::take(&mut self.input);
+        self.history_pos = None;
+
+        if line.trim().is_empty() {
+            return;
+        }
+
+        self.print(&format!("> {line}"));
+
+        if self.history.last() != Some(&line) {
+            self.history.push(line.clone());
+        }
+
+        self.queue.push_back(QueuedCommand { line, source: None });
+    }
+
+    fn browse_history(&mut self, back: bool) {
+        let pos = match (self.history_pos, back) {
+            (None, true) => 0,
+            (None, false) => return,
+            (Some(pos), true) => (pos + 1).min(self.history.len().saturating_sub(1)),
+            (Some(0), false) => {
+                self.history_pos = None;
+                self.input.clear();
+                return;
+            }
+            (Some(pos), false) => pos - 1,
+        };
+
+        if let Some(line) = self.history.iter().rev().nth(pos) {
+            self.input = line.clone();
+            self.history_pos = Some(pos);
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct ConsolePanel;
+
+#[derive(Component)]
+pub struct ConsoleText;
+
+enum ConsoleCommand {
+    Help,
+    Build(BuildRequestedEvent),
+    Spawn(SpawnItemEvent),
+    Clear(DemolishAreaEvent),
+    Speed(SimulationSpeed),
+    Pause,
+    Camera(TilePos),
+    Dump(TilePos),
+    Run(PathBuf),
+    Bind(Action, Chord),
+}
+
+fn arg<T: FromStr>(args: &mut SplitWhitespace, name: &str) -> anyhow::Result<T> {
+    let value = args.next().ok_or_else(|| anyhow!("missing {name}"))?;
+    value
+        .parse()
+        .map_err(|_| anyhow!("invalid {name} '{value}'"))
+}
+
+fn optional_arg<T: FromStr>(
+    args: &mut SplitWhitespace,
+    name: &str,
+    default: T,
+) -> anyhow::Result<T> {
+    match args.clone().next() {
+        Some(_) => arg(args, name),
+        None => Ok(default),
+    }
+}
+
+fn tile_arg(args: &mut SplitWhitespace) -> anyhow::Result<TilePos> {
+    let tile_pos = TilePos::new(arg(args, "x")?, arg(args, "y")?);
+
+    if !tile_pos.within_map_bounds(&TILEMAP_SIZE) {
+        bail!("{}, {} is outside the map", tile_pos.x, tile_pos.y);
+    }
+
+    Ok(tile_pos)
+}
+
+fn filter_arg(args: &mut SplitWhitespace) -> anyhow::Result<DemolishFilter> {
+    match args.next() {
+        None | Some("all") => Ok(DemolishFilter::All),
+        Some("belts") => Ok(DemolishFilter::BeltsOnly),
+        Some("buildings") => Ok(DemolishFilter::BuildingsOnly),
+        Some(value) => bail!("invalid filter '{value}'"),
+    }
+}
+
+impl FromStr for ConsoleCommand {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut args = s.split_whitespace();
+        let name = args.next().unwrap_or_default();
+
+        let command = match name {
+            "help" => ConsoleCommand::Help,
+            "build" => ConsoleCommand::Build(BuildRequestedEvent {
+                building_type: arg::<BuildingType>(&mut args, "building")?,
+                tile_pos: tile_arg(&mut args)?,
+                direction: optional_arg(&mut args, "direction", MapDirection::default())?,
+            }),
+            "spawn" => ConsoleCommand::Spawn(SpawnItemEvent {
+                item_type: arg::<ItemType>(&mut args, "item")?,
+                tile_pos: tile_arg(&mut args)?,
+                amount: optional_arg(&mut args, "amount", 1)?,
+            }),
+            "clear" => ConsoleCommand::Clear(DemolishAreaEvent {
+                area: TileArea::from_corners(tile_arg(&mut args)?, tile_arg(&mut args)?),
+                filter: filter_arg(&mut args)?,
+            }),
+            "speed" => {
+                let label = args.next().ok_or_else(|| anyhow!("missing speed"))?;
+                let speed = SimulationSpeed::ALL
+                    .into_iter()
+                    .find(|speed| speed.label() == label)
+                    .ok_or_else(|| anyhow!("invalid speed '{label}'"))?;
+                ConsoleCommand::Speed(speed)
+            }
+            "pause" => ConsoleCommand::Pause,
+            "camera" => ConsoleCommand::Camera(tile_arg(&mut args)?),
+            "dump" => ConsoleCommand::Dump(tile_arg(&mut args)?),
+            "run" => ConsoleCommand::Run(arg(&mut args, "file")?),
+            "bind" => {
+                let action = args.next().ok_or_else(|| anyhow!("missing action"))?;
+                let action =
+                    ron::from_str(action).map_err(|_| anyhow!("invalid action '{action}'"))?;
+                ConsoleCommand::Bind(action, arg(&mut args, "chord")?)
+            }
+            _ => bail!("unknown command '{name}', try help"),
+        };
+
+        if let Some(extra) = args.next() {
+            bail!("unexpected '{extra}'");
+        }
+
+        Ok(command)
+    }
+}
+
+pub fn init_console(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                left: Val::Px(16.),
+                right: Val::Px(16.),
+                top: Val::Px(16.),
+                padding: UiRect::all(Val::Px(16.)),
+                ..default()
+            },
+            background_color: Color::rgba(0., 0., 0., 0.85).into(),
+            visibility: Visibility::Hidden,
+            z_index: ZIndex::Global(10),
+            ..default()
+        })
+        .insert(ConsolePanel)
+        .with_children(|panel| {
+            panel
+                .spawn(TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font: asset_server.load("AsepriteFont.ttf"),
+                        font_size: 16.,
+                        color: Color::WHITE,
+                    },
+                ))
+                .insert(ConsoleText);
+        });
+}
+
+pub fn toggle_console(input: ActionInput, mut console: ResMut<Console>) {
+    if input.just_pressed(Action::ToggleConsole) {
+        console.open = !console.open;
+    }
+}
+
+// while open the console takes the keyboard for itself, nothing after it sees any keys
+pub fn edit_console_input(
+    mut characters: EventReader<ReceivedCharacter>,
+    mut keyboard: ResMut<Input<KeyCode>>,
+    mut console: ResMut<Console>,
+    mut was_open: Local<bool>,
+) {
+    let typed: String = characters
+        .iter()
+        .map(|c| c.char)
+        .filter(|c| !c.is_control())
+        .collect();
+
+    // the key that opened the console is typed in the same frame
+    let was_open = std::mem::replace(&mut *was_open, console.open);
+
+    if !console.open {
+        return;
+    }
+
+    if was_open {
+        if !typed.is_empty() {
+            console.input.push_str(&typed);
+        }
+
+        if keyboard.just_pressed(KeyCode::Back) {
+            console.input.pop();
+        }
+
+        if keyboard.just_pressed(KeyCode::Up) {
+            console.browse_history(true);
+        }
+
+        if keyboard.just_pressed(KeyCode::Down) {
+            console.browse_history(false);
+        }
+
+        if keyboard.just_pressed(KeyCode::Return) {
+            console.submit();
+        }
+
+        if keyboard.just_pressed(KeyCode::Escape) {
+            console.open = false;
+        }
+    }
+
+    keyboard.reset_all();
+}
+
+pub fn update_console_panel(
+    console: Res<Console>,
+    mut panel: Query<&mut Visibility, With<ConsolePanel>>,
+    mut text: Query<&mut Text, With<ConsoleText>>,
+) {
+    for mut visibility in panel.iter_mut() {
+        *visibility = match console.open {
+            true => Visibility::Inherited,
+            false => Visibility::Hidden,
+        };
+    }
+
+    let mut value = String::new();
+
+    for line in console.lines.iter() {
+        value.push_str(line);
+        value.push('\n');
+    }
+
+    value.push_str(&format!("> {}_", console.input));
+
+    for mut text in text.iter_mut() {
+        text.sections[0].value = value.clone();
+    }
+}
+
+fn move_camera(world: &mut World, tile_pos: TilePos) {
+    let mut map = world.query_filtered::<&Transform, With<BuildingLayer>>();
+    let Ok(center) = map
+        .get_single(world)
+        .map(|map_transform| tile_center(tile_pos, map_transform))
+    else {
+        return;
+    };
+
+    let mut cameras = world.query_filtered::<&mut Transform, With<MainCamera>>();
+
+    for mut transform in cameras.iter_mut(world) {
+        transform.translation.x = center.x;
+        transform.translation.y = center.y;
+    }
+}
+
+// lines of a script are queued in front of everything else, in their order
+fn queue_script(console: &mut Console, path: PathBuf) -> anyhow::Result<()> {
+    let script = fs::read_to_string(&path).map_err(|e| anyhow!("{}: {e}", path.display()))?;
+
+    let commands: Vec<_> = script
+        .lines()
+        .enumerate()
+        .map(|(number, line)| (number, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(number, line)| QueuedCommand {
+            line: line.to_string(),
+            source: Some(format!("{}:{}", path.display(), number + 1)),
+        })
+        .collect();
+
+    if console.queue.len() + commands.len() > MAX_QUEUED_COMMANDS {
+        console.queue.clear();
+        bail!("too many queued commands, is a script running itself?");
+    }
+
+    for command in commands.into_iter().rev() {
+        console.queue.push_front(command);
+    }
+
+    Ok(())
+}
+
+// commands go through the same events and resources as the ui, so they are recorded into
+// replays and undone like anything the player does
+fn execute(world: &mut World, line: &str) -> anyhow::Result<Option<String>> {
+    match line.parse()? {
+        ConsoleCommand::Help => return Ok(Some(HELP.to_string())),
+        ConsoleCommand::Build(event) => world.send_event(event),
+        ConsoleCommand::Spawn(event) => world.send_event(event),
+        ConsoleCommand::Clear(event) => world.send_event(event),
+        ConsoleCommand::Speed(speed) => world.resource_mut::<Simulation>().speed = speed,
+        ConsoleCommand::Pause => {
+            let mut simulation = world.resource_mut::<Simulation>();
+            simulation.paused = !simulation.paused;
+        }
+        ConsoleCommand::Camera(tile_pos) => move_camera(world, tile_pos),
+        ConsoleCommand::Dump(tile_pos) => return Ok(Some(describe_tile(world, tile_pos))),
+        ConsoleCommand::Run(path) => queue_script(&mut world.resource_mut::<Console>(), path)?,
+        ConsoleCommand::Bind(action, chord) => {
+            let mut action_map = world.resource_mut::<ActionMap>();
+            action_map.bind(action, chord);
+            action_map.save()?;
+            return Ok(Some(format!(
+                "{action:?} is now {}",
+                action_map.label(action)
+            )));
+        }
+    }
+
+    Ok(None)
+}
+
+pub fn run_console_commands(world: &mut World) {
+    if world.resource::<Console>().queue.is_empty() {
+        return;
+    }
+
+    let Some(command) = world.resource_mut::<Console>().queue.pop_front() else {
+        return;
+    };
+
+    let output = match (execute(world, &command.line), command.source) {
+        (Ok(output), _) => output,
+        (Err(e), None) => Some(format!("error: {e}")),
+        (Err(e), Some(source)) => {
+            // the rest of the script is dropped, typed commands stay queued
+            world
+                .resource_mut::<Console>()
+                .queue
+                .retain(|command| command.source.is_none());
+            Some(format!("error: {source}: {e}"))
+        }
+    };
+
+    if let Some(output) = output {
+        world.resource_mut::<Console>().print(&output);
+    }
+}
@@ -0,0 +1,395 @@
+use std::any::TypeId;
+use std::fmt::Write;
+
+use bevy::ecs::system::SystemParam;
+use bevy::ecs::world::EntityRef;
+use bevy::prelude::*;
+use bevy::utils::{get_short_name, HashMap};
+use bevy_ecs_tilemap::prelude::*;
+
+use crate::belts::{Belt, BeltInput, BeltTargets, Inventory, Item};
+use crate::buildings::mine::Mine;
+use crate::buildings::status::BuildingStatus;
+use crate::buildings::{Building, BuildingTile, BuildingType};
+use crate::direction::MapDirection;
+use crate::input::{Action, ActionInput, GameCursor};
+use crate::map::{tile_center, BuildingLayer, BuildingTileType};
+
+const ARROW_HEAD: f32 = 3.;
+
+// internals of the simulation drawn over the map, toggled independently of the game state
+// so it also works in menus and during replays
+#[derive(Resource, Default)]
+pub struct DebugOverlay {
+    pub enabled: bool,
+}
+
+#[derive(Component)]
+pub struct DebugLabel;
+
+#[derive(Component)]
+pub struct DebugInspector;
+
+#[derive(Component)]
+pub struct DebugInspectorText;
+
+pub fn is_debug_overlay_enabled(overlay: Res<DebugOverlay>) -> bool {
+    overlay.enabled
+}
+
+pub fn init_debug_inspector(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                left: Val::Px(16.),
+                top: Val::Px(320.),
+                padding: UiRect::all(Val::Px(16.)),
+                ..default()
+            },
+            background_color: Color::rgba(1., 1., 1., 0.9).into(),
+            visibility: Visibility::Hidden,
+            ..default()
+        })
+        .insert(DebugInspector)
+        .with_children(|panel| {
+            panel
+                .spawn(TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font: asset_server.load("AsepriteFont.ttf"),
+                        font_size: 16.,
+                        color: Color::DARK_GRAY,
+                    },
+                ))
+                .insert(DebugInspectorText);
+        });
+}
+
+pub fn toggle_debug_overlay(
+    input: ActionInput,
+    mut overlay: ResMut<DebugOverlay>,
+    mut inspector: Query<&mut Visibility, With<DebugInspector>>,
+) {
+    if !input.just_pressed(Action::ToggleDebugOverlay) {
+        return;
+    }
+
+    overlay.enabled = !overlay.enabled;
+
+    for mut visibility in inspector.iter_mut() {
+        *visibility = match overlay.enabled {
+            true => Visibility::Inherited,
+            false => Visibility::Hidden,
+        };
+    }
+}
+
+// every building gets its own colour, tiles that are not part of their building's layout
+// are crossed out
+fn building_color(building: Entity) -> Color {
+    Color::hsl((building.index() * 47 % 360) as f32, 0.8, 0.5)
+}
+
+fn draw_arrow(gizmos: &mut Gizmos, from: Vec2, to: Vec2, color: Color) {
+    gizmos.line_2d(from, to, color);
+
+    let back = (from - to).normalize_or_zero() * ARROW_HEAD;
+    gizmos.line_2d(to, to + back.rotate(Vec2::from_angle(0.5)), color);
+    gizmos.line_2d(to, to + back.rotate(Vec2::from_angle(-0.5)), color);
+}
+
+pub fn draw_debug_overlay(
+    mut gizmos: Gizmos,
+    belts: Query<(&TilePos, &TileTextureIndex), With<Belt>>,
+    building_tiles: Query<(Entity, &BuildingTile, &TilePos)>,
+    buildings: Query<&Building>,
+    belt_targets: BeltTargets,
+    building_layer: Query<(&TileStorage, &Transform), With<BuildingLayer>>,
+) {
+    let Ok((storage, map_transform)) = building_layer.get_single() else {
+        return;
+    };
+
+    let size = Vec2::new(14., 14.);
+
+    for (tile_entity, building_tile, tile_pos) in building_tiles.iter() {
+        let center = tile_center(*tile_pos, map_transform);
+
+        let in_layout = buildings
+            .get(building_tile.building)
+            .is_ok_and(|b| b.layout.tiles.iter().any(|(e, _, _)| *e == tile_entity));
+
+        if in_layout {
+            gizmos.rect_2d(center, 0., size, building_color(building_tile.building));
+        } else {
+            gizmos.line_2d(center - size / 2., center + size / 2., Color::RED);
+            gizmos.line_2d(
+                center + Vec2::new(-size.x, size.y) / 2.,
+                center + Vec2::new(size.x, -size.y) / 2.,
+                Color::RED,
+            );
+        }
+    }
+
+    // where each belt hands its first item over to, red when there is nothing to take it
+    for (tile_pos, texture) in belts.iter() {
+        let center = tile_center(*tile_pos, map_transform);
+
+        let Some(next_pos) = BuildingTileType::from(*texture).next_belt_pos(*tile_pos) else {
+            continue;
+        };
+
+        let next_center = tile_center(next_pos, map_transform);
+        let connected = storage
+            .checked_get(&next_pos)
+            .is_some_and(|e| belt_targets.contains(e));
+
+        let color = match connected {
+            true => Color::LIME_GREEN,
+            false => Color::RED,
+        };
+
+        draw_arrow(&mut gizmos, center, center.lerp(next_center, 0.8), color);
+    }
+}
+
+fn belt_label(belt: &Belt) -> String {
+    let progress: Vec<_> = belt
+        .items
+        .iter()
+        .map(|(_, progress)| format!("{progress:.2}"))
+        .collect();
+
+    format!("{}\n{}", belt.items.len(), progress.join(" "))
+}
+
+fn mine_label(mine: &Mine) -> String {
+    let mut lines = Vec::new();
+    mine.status(&mut lines);
+    lines.join("\n")
+}
+
+// what gets a label
+#[derive(SystemParam)]
+pub struct LabelSources<'w, 's> {
+    belts: Query<'w, 's, (Entity, &'static Belt, &'static TilePos)>,
+    mines: Query<'w, 's, (Entity, &'static Mine, &'static TilePos)>,
+}
+
+impl LabelSources<'_, '_> {
+    fn labels(&self) -> HashMap<Entity, (String, TilePos)> {
+        let belts = self
+            .belts
+            .iter()
+            .map(|(entity, belt, tile_pos)| (entity, (belt_label(belt), *tile_pos)));
+        let mines = self
+            .mines
+            .iter()
+            .map(|(entity, mine, tile_pos)| (entity, (mine_label(mine), *tile_pos)));
+
+        belts.chain(mines).collect()
+    }
+}
+
+// one label per belt tile and mine, kept in sync with the entity it describes
+pub fn update_debug_labels(
+    mut commands: Commands,
+    overlay: Res<DebugOverlay>,
+    asset_server: Res<AssetServer>,
+    sources: LabelSources,
+    mut labels: Query<&mut Text, With<DebugLabel>>,
+    building_layer: Query<&Transform, With<BuildingLayer>>,
+    mut spawned: Local<HashMap<Entity, Entity>>,
+) {
+    let (true, Ok(map_transform)) = (overlay.enabled, building_layer.get_single()) else {
+        for (_, label) in spawned.drain() {
+            commands.entity(label).despawn();
+        }
+        return;
+    };
+
+    let style = TextStyle {
+        font: asset_server.load("AsepriteFont.ttf"),
+        font_size: 24.,
+        color: Color::BLACK,
+    };
+
+    let sources = sources.labels();
+
+    spawned.retain(|source, label| {
+        let exists = sources.contains_key(source);
+        if !exists {
+            commands.entity(*label).despawn();
+        }
+        exists
+    });
+
+    for (source, (value, tile_pos)) in sources {
+        match spawned
+            .get(&source)
+            .and_then(|label| labels.get_mut(*label).ok())
+        {
+            Some(mut text) => {
+                if text.sections[0].value != value {
+                    text.sections[0].value = value;
+                }
+            }
+            None => {
+                let position = tile_center(tile_pos, map_transform).extend(10.);
+
+                let label = commands
+                    .spawn(Text2dBundle {
+                        text: Text::from_section(value, style.clone())
+                            .with_alignment(TextAlignment::Center),
+                        transform: Transform::from_translation(position)
+                            .with_scale(Vec3::splat(0.2)),
+                        ..default()
+                    })
+                    .insert(DebugLabel)
+                    .id();
+
+                spawned.insert(source, label);
+            }
+        }
+    }
+}
+
+// known components get their values, the rest only their name
+fn component_details(entity: &EntityRef, type_id: TypeId) -> Option<String> {
+    if type_id == TypeId::of::<Belt>() {
+        let belt = entity.get::<Belt>()?;
+        let items: Vec<_> = belt
+            .items
+            .iter()
+            .map(|(item, progress)| format!("{item:?} at {progress:.3}"))
+            .collect();
+        return Some(format!(
+            "dead end {}, [{}]",
+            belt.dead_end,
+            items.join(", ")
+        ));
+    }
+
+    if type_id == TypeId::of::<BuildingTile>() {
+        return Some(format!(
+            "building {:?}",
+            entity.get::<BuildingTile>()?.building
+        ));
+    }
+
+    if type_id == TypeId::of::<Building>() {
+        let tiles: Vec<_> = entity
+            .get::<Building>()?
+            .layout
+            .tiles
+            .iter()
+            .map(|(e, pos, tile_type)| format!("{e:?} {},{} {tile_type:?}", pos.x, pos.y))
+            .collect();
+        return Some(tiles.join(", "));
+    }
+
+    if type_id == TypeId::of::<BeltInput>() {
+        return Some(format!("into {:?}", entity.get::<BeltInput>()?.inventory));
+    }
+
+    if type_id == TypeId::of::<Inventory>() {
+        return Some(format!("{:?}", entity.get::<Inventory>()?.slots));
+    }
+
+    if type_id == TypeId::of::<Mine>() {
+        return Some(mine_label(entity.get::<Mine>()?).replace('\n', ", "));
+    }
+
+    if type_id == TypeId::of::<Item>() {
+        let item = entity.get::<Item>()?;
+        return Some(format!("{} on {:?}", item.item_type.as_str(), item.belt));
+    }
+
+    if type_id == TypeId::of::<TilePos>() {
+        let tile_pos = entity.get::<TilePos>()?;
+        return Some(format!("{}, {}", tile_pos.x, tile_pos.y));
+    }
+
+    if type_id == TypeId::of::<TileTextureIndex>() {
+        let texture = entity.get::<TileTextureIndex>()?;
+        return Some(format!("{:?}", BuildingTileType::from(*texture)));
+    }
+
+    if type_id == TypeId::of::<BuildingType>() {
+        return Some(entity.get::<BuildingType>()?.as_str().to_string());
+    }
+
+    if type_id == TypeId::of::<MapDirection>() {
+        return Some(entity.get::<MapDirection>()?.as_str().to_string());
+    }
+
+    None
+}
+
+fn describe_entity(world: &World, entity: Entity, out: &mut String) {
+    let Some(entity_ref) = world.get_entity(entity) else {
+        let _ = writeln!(out, "{entity:?} doesn't exist");
+        return;
+    };
+
+    let _ = writeln!(out, "{entity:?}");
+
+    for info in world.inspect_entity(entity) {
+        let _ = write!(out, "  {}", get_short_name(info.name()));
+
+        if let Some(details) = info
+            .type_id()
+            .and_then(|type_id| component_details(&entity_ref, type_id))
+        {
+            let _ = write!(out, ": {details}");
+        }
+
+        out.push('\n');
+    }
+}
+
+// the tile, its building and the items on it with all their components
+pub fn describe_tile(world: &mut World, tile_pos: TilePos) -> String {
+    let mut building_layer = world.query_filtered::<&TileStorage, With<BuildingLayer>>();
+    let tile = building_layer
+        .get_single(world)
+        .ok()
+        .and_then(|storage| storage.checked_get(&tile_pos));
+
+    let mut info = format!("TILE {}, {}\n", tile_pos.x, tile_pos.y);
+
+    if let Some(tile) = tile {
+        describe_entity(world, tile, &mut info);
+
+        if let Some(building_tile) = world.get::<BuildingTile>(tile) {
+            info.push('\n');
+            describe_entity(world, building_tile.building, &mut info);
+        }
+
+        if let Some(belt) = world.get::<Belt>(tile) {
+            for (item, _) in belt.items.iter() {
+                info.push('\n');
+                describe_entity(world, *item, &mut info);
+            }
+        }
+    }
+
+    info
+}
+
+pub fn update_debug_inspector(world: &mut World) {
+    let Some(tile_pos) = world.resource::<GameCursor>().tile_pos else {
+        return;
+    };
+
+    let info = describe_tile(world, tile_pos);
+
+    let mut text = world.query_filtered::<&mut Text, With<DebugInspectorText>>();
+
+    for mut text in text.iter_mut(world) {
+        if text.sections[0].value != info {
+            text.sections[0].value = info.clone();
+        }
+    }
+}
@@ -0,0 +1,127 @@
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::*;
+
+use crate::buildings::status::InspectedBuilding;
+use crate::buildings::{building_at, Building, BuildingTile, BuildingType, SelectEvent};
+use crate::direction::MapDirection;
+use crate::input::GameCursor;
+use crate::map::BuildingLayer;
+use crate::ui::MapInteraction;
+
+#[derive(Component)]
+pub struct InfoPanel;
+
+#[derive(Component)]
+pub struct InfoPanelText;
+
+pub fn init_info_panel(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                left: Val::Px(16.),
+                bottom: Val::Px(16.),
+                padding: UiRect::all(Val::Px(16.)),
+                ..default()
+            },
+            background_color: Color::WHITE.into(),
+            visibility: Visibility::Hidden,
+            ..default()
+        })
+        .insert(InfoPanel)
+        .with_children(|panel| {
+            panel
+                .spawn(TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font: asset_server.load("AsepriteFont.ttf"),
+                        font_size: 16.,
+                        color: Color::DARK_GRAY,
+                    },
+                ))
+                .insert(InfoPanelText);
+        });
+}
+
+// buildings that still exist, found by any of their tiles
+#[derive(SystemParam)]
+pub struct BuildingLookup<'w, 's> {
+    building_tiles: Query<'w, 's, &'static BuildingTile>,
+    buildings: Query<'w, 's, (), With<Building>>,
+    building_layer: Query<'w, 's, &'static TileStorage, With<BuildingLayer>>,
+}
+
+impl BuildingLookup<'_, '_> {
+    pub fn at(&self, tile_pos: &TilePos) -> Option<Entity> {
+        let building_layer = self.building_layer.get_single().ok()?;
+
+        building_at(tile_pos, building_layer, &self.building_tiles)
+            .filter(|building| self.exists(*building))
+    }
+
+    fn exists(&self, building: Entity) -> bool {
+        self.buildings.contains(building)
+    }
+}
+
+// the building under the cursor takes precedence over the selected one
+pub fn update_inspected_building(
+    mut inspected: ResMut<InspectedBuilding>,
+    mut selected: Local<Option<Entity>>,
+    mut select_events: EventReader<SelectEvent>,
+    cursor: Res<GameCursor>,
+    map_interaction: Res<MapInteraction>,
+    buildings: BuildingLookup,
+) {
+    for event in select_events.iter() {
+        *selected = buildings.at(&event.tile_pos);
+    }
+
+    if selected.is_some_and(|e| !buildings.exists(e)) {
+        *selected = None;
+    }
+
+    let hovered = cursor
+        .tile_pos
+        .filter(|_| map_interaction.is_allowed())
+        .and_then(|tile_pos| buildings.at(&tile_pos));
+
+    inspected.building = hovered.or(*selected);
+    inspected.status.clear();
+}
+
+pub fn update_info_panel(
+    inspected: Res<InspectedBuilding>,
+    buildings: Query<(&BuildingType, &MapDirection, &TilePos)>,
+    mut panel: Query<&mut Visibility, With<InfoPanel>>,
+    mut panel_text: Query<&mut Text, With<InfoPanelText>>,
+) {
+    let (Ok(mut visibility), Ok(mut text)) = (panel.get_single_mut(), panel_text.get_single_mut())
+    else {
+        return;
+    };
+
+    let Some((building_type, direction, origin)) =
+        inspected.building.and_then(|e| buildings.get(e).ok())
+    else {
+        *visibility = Visibility::Hidden;
+        return;
+    };
+
+    let mut info = format!(
+        "{}\ndirection {}\norigin {}, {}",
+        building_type.as_str().to_uppercase(),
+        direction.as_str(),
+        origin.x,
+        origin.y,
+    );
+
+    for line in inspected.status.iter() {
+        info.push('\n');
+        info.push_str(line);
+    }
+
+    *visibility = Visibility::Inherited;
+    text.sections[0].value = info;
+}
@@ -0,0 +1,340 @@
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::*;
+
+use crate::belts::{Inventory, InventoryEvent, ItemType, SpawnItemEvent};
+use crate::buildings::SelectEvent;
+use crate::ui::info::BuildingLookup;
+
+const SLOTS_PER_ROW: usize = 8;
+const SLOT_SIZE: f32 = 40.;
+
+#[derive(Component)]
+pub struct InventoryWindow {
+    inventory: Entity,
+    tile_pos: TilePos,
+}
+
+// the stack taken out of a chest, until it is put into one again
+#[derive(Resource, Default)]
+pub struct Hand(Option<(ItemType, usize)>);
+
+impl Hand {
+    fn label(&self) -> String {
+        match self.0 {
+            Some((item_type, amount)) => format!("HAND {amount} {}", item_type.as_str()),
+            None => String::new(),
+        }
+    }
+}
+
+#[derive(Component, Clone, Copy)]
+pub enum InventoryAction {
+    Slot(usize),
+    Clear,
+    Close,
+}
+
+#[derive(Component)]
+pub struct InventorySlotIcon(usize);
+
+#[derive(Component)]
+pub struct InventorySlotCount(usize);
+
+#[derive(Component)]
+pub struct HandText;
+
+pub fn open_inventory_window(
+    mut commands: Commands,
+    mut select_events: EventReader<SelectEvent>,
+    buildings: BuildingLookup,
+    inventories: Query<&Inventory>,
+    windows: Query<Entity, With<InventoryWindow>>,
+    hand: Res<Hand>,
+    asset_server: Res<AssetServer>,
+) {
+    let Some((tile_pos, inventory_entity, inventory)) = select_events.iter().find_map(|e| {
+        let building = buildings.at(&e.tile_pos)?;
+        let inventory = inventories.get(building).ok()?;
+        Some((e.tile_pos, building, inventory))
+    }) else {
+        return;
+    };
+
+    for window in windows.iter() {
+        commands.entity(window).despawn_recursive();
+    }
+
+    let font = asset_server.load("AsepriteFont.ttf");
+
+    let text_style = |font_size| TextStyle {
+        font: font.clone(),
+        font_size,
+        color: Color::DARK_GRAY,
+    };
+
+    let button = |parent: &mut ChildBuilder, label: &str, action| {
+        parent
+            .spawn(ButtonBundle {
+                style: Style {
+                    padding: UiRect::axes(Val::Px(8.), Val::Px(4.)),
+                    margin: UiRect::left(Val::Px(8.)),
+                    ..default()
+                },
+                ..default()
+            })
+            .insert(action)
+            .with_children(|button| {
+                button.spawn(TextBundle::from_section(label, text_style(16.)));
+            });
+    };
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.),
+                height: Val::Percent(100.),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            ..default()
+        })
+        .insert(InventoryWindow {
+            inventory: inventory_entity,
+            tile_pos,
+        })
+        .with_children(|root| {
+            let mut window = root.spawn(NodeBundle {
+                style: Style {
+                    flex_direction: FlexDirection::Column,
+                    padding: UiRect::all(Val::Px(16.)),
+                    ..default()
+                },
+                background_color: Color::WHITE.into(),
+                ..default()
+            });
+
+            window
+                .insert(Interaction::default())
+                .with_children(|window| {
+                    window
+                        .spawn(NodeBundle {
+                            style: Style {
+                                align_items: AlignItems::Center,
+                                margin: UiRect::bottom(Val::Px(8.)),
+                                ..default()
+                            },
+                            ..default()
+                        })
+                        .with_children(|header| {
+                            header.spawn(
+                                TextBundle::from_section("CHEST", text_style(24.)).with_style(
+                                    Style {
+                                        flex_grow: 1.,
+                                        ..default()
+                                    },
+                                ),
+                            );
+                            button(header, "CLEAR", InventoryAction::Clear);
+                            button(header, "X", InventoryAction::Close);
+                        });
+
+                    window
+                        .spawn(NodeBundle {
+                            style: Style {
+                                flex_wrap: FlexWrap::Wrap,
+                                width: Val::Px(SLOTS_PER_ROW as f32 * (SLOT_SIZE + 4.)),
+                                ..default()
+                            },
+                            ..default()
+                        })
+                        .with_children(|grid| {
+                            for (index, slot) in inventory.slots.iter().enumerate() {
+                                spawn_slot(grid, index, *slot, &asset_server, text_style(16.));
+                            }
+                        });
+
+                    window
+                        .spawn(
+                            TextBundle::from_section(hand.label(), text_style(16.)).with_style(
+                                Style {
+                                    margin: UiRect::top(Val::Px(8.)),
+                                    ..default()
+                                },
+                            ),
+                        )
+                        .insert(HandText);
+                });
+        });
+}
+
+fn spawn_slot(
+    parent: &mut ChildBuilder,
+    index: usize,
+    slot: Option<(ItemType, usize)>,
+    asset_server: &AssetServer,
+    text_style: TextStyle,
+) {
+    parent
+        .spawn(ButtonBundle {
+            style: Style {
+                width: Val::Px(SLOT_SIZE),
+                height: Val::Px(SLOT_SIZE),
+                margin: UiRect::all(Val::Px(2.)),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            background_color: Color::GRAY.into(),
+            ..default()
+        })
+        .insert(InventoryAction::Slot(index))
+        .with_children(|slot_node| {
+            slot_node
+                .spawn(ImageBundle {
+                    style: Style {
+                        width: Val::Px(32.),
+                        height: Val::Px(32.),
+                        ..default()
+                    },
+                    image: slot
+                        .map(|(item_type, _)| UiImage::new(asset_server.load(item_type.icon())))
+                        .unwrap_or_default(),
+                    visibility: match slot {
+                        Some(_) => Visibility::Inherited,
+                        None => Visibility::Hidden,
+                    },
+                    ..default()
+                })
+                .insert(InventorySlotIcon(index));
+
+            slot_node
+                .spawn(
+                    TextBundle::from_section(
+                        slot.map(|(_, count)| count.to_string()).unwrap_or_default(),
+                        TextStyle {
+                            color: Color::WHITE,
+                            ..text_style
+                        },
+                    )
+                    .with_style(Style {
+                        position_type: PositionType::Absolute,
+                        right: Val::Px(2.),
+                        bottom: Val::Px(0.),
+                        ..default()
+                    }),
+                )
+                .insert(InventorySlotCount(index));
+        });
+}
+
+pub fn update_inventory_window(
+    mut commands: Commands,
+    windows: Query<(Entity, &InventoryWindow)>,
+    inventories: Query<Ref<Inventory>>,
+    mut icons: Query<(&InventorySlotIcon, &mut UiImage, &mut Visibility)>,
+    mut counts: Query<(&InventorySlotCount, &mut Text)>,
+    asset_server: Res<AssetServer>,
+) {
+    for (window_entity, window) in windows.iter() {
+        let Ok(inventory) = inventories.get(window.inventory) else {
+            // chest was demolished
+            commands.entity(window_entity).despawn_recursive();
+            continue;
+        };
+
+        if !inventory.is_changed() {
+            continue;
+        }
+
+        for (InventorySlotIcon(index), mut image, mut visibility) in icons.iter_mut() {
+            match inventory.slots.get(*index).copied().flatten() {
+                Some((item_type, _)) => {
+                    image.texture = asset_server.load(item_type.icon());
+                    *visibility = Visibility::Inherited;
+                }
+                None => *visibility = Visibility::Hidden,
+            }
+        }
+
+        for (InventorySlotCount(index), mut text) in counts.iter_mut() {
+            text.sections[0].value = inventory
+                .slots
+                .get(*index)
+                .copied()
+                .flatten()
+                .map(|(_, count)| count.to_string())
+                .unwrap_or_default();
+        }
+    }
+}
+
+pub fn update_hand_text(hand: Res<Hand>, mut texts: Query<&mut Text, With<HandText>>) {
+    for mut text in texts.iter_mut() {
+        text.sections[0].value = hand.label();
+    }
+}
+
+pub fn handle_inventory_actions(
+    mut commands: Commands,
+    actions: Query<(&InventoryAction, &Interaction), Changed<Interaction>>,
+    windows: Query<(Entity, &InventoryWindow)>,
+    inventories: Query<&Inventory>,
+    mut hand: ResMut<Hand>,
+    mut inventory_events: EventWriter<InventoryEvent>,
+    mut spawn_events: EventWriter<SpawnItemEvent>,
+) {
+    let Ok((window_entity, window)) = windows.get_single() else {
+        return;
+    };
+
+    for (action, _) in actions
+        .iter()
+        .filter(|(_, interaction)| matches!(interaction, Interaction::Pressed))
+    {
+        match action {
+            // goes through the simulation so replays and other players see it too
+            InventoryAction::Slot(slot) => {
+                let Ok(inventory) = inventories.get(window.inventory) else {
+                    continue;
+                };
+
+                match hand.0 {
+                    // the whole stack is taken into the hand
+                    None => {
+                        if let Some((item_type, amount)) =
+                            inventory.slots.get(*slot).copied().flatten()
+                        {
+                            inventory_events.send(InventoryEvent::Take {
+                                tile_pos: window.tile_pos,
+                                slot: *slot,
+                                amount,
+                            });
+                            hand.0 = Some((item_type, amount));
+                        }
+                    }
+                    // and put back into any chest as far as it fits, the rest stays in the hand
+                    Some((item_type, amount)) => {
+                        let fits = inventory.room_for(amount, item_type);
+
+                        if fits > 0 {
+                            spawn_events.send(SpawnItemEvent {
+                                item_type,
+                                tile_pos: window.tile_pos,
+                                amount: fits,
+                            });
+                            hand.0 = Some((item_type, amount - fits)).filter(|(_, left)| *left > 0);
+                        }
+                    }
+                }
+            }
+            InventoryAction::Clear => inventory_events.send(InventoryEvent::Clear {
+                tile_pos: window.tile_pos,
+            }),
+            InventoryAction::Close => {
+                commands.entity(window_entity).despawn_recursive();
+            }
+        }
+    }
+}
@@ -0,0 +1,461 @@
+use bevy::app::AppExit;
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy::ui::FocusPolicy;
+
+use crate::game::{GameSetup, GameState};
+use crate::grid::Grid;
+use crate::input::{Action, ActionInput};
+use crate::mods::registry::ModRegistry;
+use crate::replay::{Replay, ReplayPlayback, LAST_REPLAY};
+use crate::save::{SaveGame, SaveGameEvent, QUICKSAVE};
+use crate::settings::{
+    next_option, Settings, AUTOSAVE_INTERVALS, GRID_ZOOMS, PAN_SPEEDS, RESOLUTIONS, UI_SCALES,
+};
+
+// which menu is open on top of the game
+#[derive(States, Debug, Clone, Copy, Default, Eq, PartialEq, Hash)]
+pub enum MenuScreen {
+    #[default]
+    Closed,
+    Main,
+    Settings,
+    Pause,
+}
+
+#[derive(Component)]
+pub struct MenuPanel;
+
+#[derive(Component)]
+pub struct SeedText;
+
+#[derive(Component, Clone, Copy)]
+pub enum MenuButton {
+    NewGame,
+    LoadGame,
+    WatchReplay,
+    Settings,
+    Quit,
+    Resume,
+    SaveGame,
+    ExitToMenu,
+    Back,
+}
+
+// every click moves the setting to its next value
+#[derive(Component, Clone, Copy)]
+pub enum SettingButton {
+    Resolution,
+    Fullscreen,
+    Grid,
+    MaxGridZoom,
+    PanSpeed,
+    UiScale,
+    Autosave,
+}
+
+impl SettingButton {
+    const ALL: [SettingButton; 7] = [
+        SettingButton::Resolution,
+        SettingButton::Fullscreen,
+        SettingButton::Grid,
+        SettingButton::MaxGridZoom,
+        SettingButton::PanSpeed,
+        SettingButton::UiScale,
+        SettingButton::Autosave,
+    ];
+
+    fn label(&self, settings: &Settings) -> String {
+        let on_off = |value| match value {
+            true => "ON",
+            false => "OFF",
+        };
+
+        match self {
+            SettingButton::Resolution => {
+                let (width, height) = settings.resolution;
+                format!("RESOLUTION {width}x{height}")
+            }
+            SettingButton::Fullscreen => format!("FULLSCREEN {}", on_off(settings.fullscreen)),
+            SettingButton::Grid => format!("GRID {}", on_off(settings.show_grid)),
+            SettingButton::MaxGridZoom => format!("GRID UP TO ZOOM {}", settings.max_grid_zoom),
+            SettingButton::PanSpeed => format!("PAN SPEED {}", settings.pan_speed),
+            SettingButton::UiScale => format!("UI SCALE {}", settings.ui_scale),
+            SettingButton::Autosave => match settings.autosave_minutes {
+                0 => String::from("AUTOSAVE OFF"),
+                minutes => format!("AUTOSAVE EVERY {minutes} MIN"),
+            },
+        }
+    }
+
+    fn next(&self, settings: &mut Settings) {
+        match self {
+            SettingButton::Resolution => {
+                settings.resolution = next_option(&RESOLUTIONS, settings.resolution)
+            }
+            SettingButton::Fullscreen => settings.fullscreen = !settings.fullscreen,
+            SettingButton::Grid => settings.show_grid = !settings.show_grid,
+            SettingButton::MaxGridZoom => {
+                settings.max_grid_zoom = next_option(&GRID_ZOOMS, settings.max_grid_zoom)
+            }
+            SettingButton::PanSpeed => {
+                settings.pan_speed = next_option(&PAN_SPEEDS, settings.pan_speed)
+            }
+            SettingButton::UiScale => {
+                settings.ui_scale = next_option(&UI_SCALES, settings.ui_scale)
+            }
+            SettingButton::Autosave => {
+                settings.autosave_minutes =
+                    next_option(&AUTOSAVE_INTERVALS, settings.autosave_minutes)
+            }
+        }
+    }
+}
+
+impl MenuButton {
+    fn label(&self) -> &'static str {
+        match self {
+            MenuButton::NewGame => "NEW GAME",
+            MenuButton::LoadGame => "LOAD GAME",
+            MenuButton::WatchReplay => "WATCH REPLAY",
+            MenuButton::Settings => "SETTINGS",
+            MenuButton::Quit => "QUIT",
+            MenuButton::Resume => "RESUME",
+            MenuButton::SaveGame => "SAVE GAME",
+            MenuButton::ExitToMenu => "MAIN MENU",
+            MenuButton::Back => "BACK",
+        }
+    }
+}
+
+pub fn open_main_menu(mut next_screen: ResMut<NextState<MenuScreen>>) {
+    next_screen.set(MenuScreen::Main);
+}
+
+pub fn open_pause_menu(mut next_screen: ResMut<NextState<MenuScreen>>) {
+    next_screen.set(MenuScreen::Pause);
+}
+
+pub fn close_menu(mut next_screen: ResMut<NextState<MenuScreen>>) {
+    next_screen.set(MenuScreen::Closed);
+}
+
+// the main menu hides the map completely, in game the menu only dims it
+fn spawn_menu(
+    commands: &mut Commands,
+    background: Color,
+    content: impl FnOnce(&mut ChildBuilder),
+) -> Entity {
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.),
+                height: Val::Percent(100.),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            background_color: background.into(),
+            focus_policy: FocusPolicy::Block,
+            z_index: ZIndex::Global(10),
+            ..default()
+        })
+        .insert(MenuPanel)
+        .with_children(|root| {
+            root.spawn(NodeBundle {
+                style: Style {
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    padding: UiRect::all(Val::Px(32.)),
+                    ..default()
+                },
+                background_color: Color::WHITE.into(),
+                ..default()
+            })
+            .with_children(content);
+        })
+        .id()
+}
+
+fn spawn_button(
+    parent: &mut ChildBuilder,
+    action: impl Component,
+    label: impl Into<String>,
+    style: &TextStyle,
+) {
+    parent
+        .spawn(ButtonBundle {
+            style: Style {
+                padding: UiRect::axes(Val::Px(16.), Val::Px(8.)),
+                margin: UiRect::top(Val::Px(16.)),
+                ..default()
+            },
+            background_color: Color::rgb_u8(230, 230, 230).into(),
+            ..default()
+        })
+        .insert(action)
+        .with_children(|button| {
+            button.spawn(TextBundle::from_section(label, style.clone()));
+        });
+}
+
+fn menu_style(asset_server: &AssetServer, font_size: f32) -> TextStyle {
+    TextStyle {
+        font: asset_server.load("AsepriteFont.ttf"),
+        font_size,
+        color: Color::DARK_GRAY,
+    }
+}
+
+pub fn spawn_main_menu(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    setup: Res<GameSetup>,
+) {
+    let title = menu_style(&asset_server, 48.);
+    let style = menu_style(&asset_server, 24.);
+    let hint = menu_style(&asset_server, 16.);
+
+    spawn_menu(&mut commands, Color::rgb_u8(40, 40, 40), |menu| {
+        menu.spawn(TextBundle::from_section("BEVACTORIO", title));
+
+        menu.spawn(
+            TextBundle::from_section(format!("SEED {}", setup.seed), style.clone()).with_style(
+                Style {
+                    margin: UiRect::top(Val::Px(16.)),
+                    ..default()
+                },
+            ),
+        )
+        .insert(SeedText);
+        menu.spawn(TextBundle::from_section("type to change the seed", hint));
+
+        for button in [
+            MenuButton::NewGame,
+            MenuButton::LoadGame,
+            MenuButton::WatchReplay,
+            MenuButton::Settings,
+            MenuButton::Quit,
+        ] {
+            spawn_button(menu, button, button.label(), &style);
+        }
+    });
+}
+
+pub fn spawn_pause_menu(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let title = menu_style(&asset_server, 48.);
+    let style = menu_style(&asset_server, 24.);
+
+    spawn_menu(&mut commands, Color::rgba(0., 0., 0., 0.5), |menu| {
+        menu.spawn(TextBundle::from_section("PAUSED", title));
+
+        for button in [
+            MenuButton::Resume,
+            MenuButton::SaveGame,
+            MenuButton::Settings,
+            MenuButton::ExitToMenu,
+            MenuButton::Quit,
+        ] {
+            spawn_button(menu, button, button.label(), &style);
+        }
+    });
+}
+
+pub fn spawn_settings_menu(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    settings: Res<Settings>,
+    game_state: Res<State<GameState>>,
+) {
+    let title = menu_style(&asset_server, 48.);
+    let style = menu_style(&asset_server, 24.);
+    let small = menu_style(&asset_server, 16.);
+
+    let background = match game_state.get() {
+        GameState::MainMenu => Color::rgb_u8(40, 40, 40),
+        _ => Color::rgba(0., 0., 0., 0.5),
+    };
+
+    spawn_menu(&mut commands, background, |menu| {
+        menu.spawn(TextBundle::from_section("SETTINGS", title));
+
+        for setting in SettingButton::ALL {
+            spawn_button(menu, setting, setting.label(&settings), &style);
+        }
+
+        menu.spawn(
+            TextBundle::from_section("edit controls.ron to rebind keys", small).with_style(Style {
+                margin: UiRect::top(Val::Px(16.)),
+                ..default()
+            }),
+        );
+
+        spawn_button(menu, MenuButton::Back, MenuButton::Back.label(), &style);
+    });
+}
+
+pub fn despawn_menu(mut commands: Commands, panels: Query<Entity, With<MenuPanel>>) {
+    for panel in panels.iter() {
+        commands.entity(panel).despawn_recursive();
+    }
+}
+
+// where the menus lead, the menu screen follows the game state
+#[derive(SystemParam)]
+pub struct MenuNavigation<'w> {
+    game_state: Res<'w, State<GameState>>,
+    next_game_state: ResMut<'w, NextState<GameState>>,
+    next_screen: ResMut<'w, NextState<MenuScreen>>,
+}
+
+impl MenuNavigation<'_> {
+    fn play(&mut self) {
+        self.next_game_state.set(GameState::InGame);
+    }
+
+    fn game_state(&self) -> &GameState {
+        self.game_state.get()
+    }
+
+    fn set_game_state(&mut self, game_state: GameState) {
+        self.next_game_state.set(game_state);
+    }
+
+    fn open(&mut self, screen: MenuScreen) {
+        self.next_screen.set(screen);
+    }
+
+    // settings are reachable from both menus and go back to the one they were opened from
+    fn back(&mut self) {
+        let screen = match self.game_state.get() {
+            GameState::MainMenu => MenuScreen::Main,
+            _ => MenuScreen::Pause,
+        };
+        self.next_screen.set(screen);
+    }
+}
+
+pub fn handle_menu_buttons(
+    mut commands: Commands,
+    buttons: Query<(&MenuButton, &Interaction), Changed<Interaction>>,
+    mut navigation: MenuNavigation,
+    mut setup: ResMut<GameSetup>,
+    mut save_events: EventWriter<SaveGameEvent>,
+    mut exit: EventWriter<AppExit>,
+) {
+    let Some((button, _)) = buttons
+        .iter()
+        .find(|(_, interaction)| matches!(interaction, Interaction::Pressed))
+    else {
+        return;
+    };
+
+    match button {
+        MenuButton::NewGame => {
+            setup.save = None;
+            setup.mods = ModRegistry::default();
+            navigation.play();
+        }
+        MenuButton::LoadGame => match SaveGame::latest() {
+            Ok(Some(save)) => {
+                setup.seed = save.seed;
+                setup.mods = save.mods.clone();
+                setup.save = Some(save);
+                navigation.play();
+            }
+            Ok(None) => warn!("there is no saved game"),
+            Err(e) => error!("failed to load game: {e}"),
+        },
+        MenuButton::WatchReplay => match Replay::read(Replay::path(LAST_REPLAY)) {
+            Ok(replay) => {
+                setup.seed = replay.seed;
+                setup.save = replay.save.clone();
+                setup.mods = replay.mods.clone();
+                commands.insert_resource(ReplayPlayback::new(replay));
+                navigation.play();
+            }
+            Err(e) => error!("failed to load replay: {e}"),
+        },
+        MenuButton::Settings => navigation.open(MenuScreen::Settings),
+        MenuButton::Quit => exit.send(AppExit),
+        MenuButton::Resume => navigation.play(),
+        MenuButton::SaveGame => save_events.send(SaveGameEvent { name: QUICKSAVE }),
+        MenuButton::ExitToMenu => navigation.set_game_state(GameState::MainMenu),
+        MenuButton::Back => navigation.back(),
+    }
+}
+
+pub fn handle_menu_key(
+    input: ActionInput,
+    screen: Res<State<MenuScreen>>,
+    mut navigation: MenuNavigation,
+) {
+    if !input.just_pressed(Action::Menu) {
+        return;
+    }
+
+    match (navigation.game_state(), screen.get()) {
+        (_, MenuScreen::Settings) => navigation.back(),
+        (GameState::InGame, _) => navigation.set_game_state(GameState::Paused),
+        (GameState::Paused, _) => navigation.play(),
+        _ => {}
+    }
+}
+
+pub fn edit_seed(
+    mut characters: EventReader<ReceivedCharacter>,
+    keyboard: Res<Input<KeyCode>>,
+    mut setup: ResMut<GameSetup>,
+    mut seed_text: Query<&mut Text, With<SeedText>>,
+) {
+    let mut seed = setup.seed;
+
+    for digit in characters.iter().filter_map(|c| c.char.to_digit(10)) {
+        seed = seed
+            .checked_mul(10)
+            .and_then(|s| s.checked_add(digit as u64))
+            .unwrap_or(seed);
+    }
+
+    if keyboard.just_pressed(KeyCode::Back) {
+        seed /= 10;
+    }
+
+    if seed == setup.seed {
+        return;
+    }
+
+    setup.seed = seed;
+
+    for mut text in seed_text.iter_mut() {
+        text.sections[0].value = format!("SEED {seed}");
+    }
+}
+
+pub fn handle_setting_buttons(
+    buttons: Query<(&SettingButton, &Interaction, &Children), Changed<Interaction>>,
+    mut texts: Query<&mut Text>,
+    mut settings: ResMut<Settings>,
+    mut grid: ResMut<Grid>,
+) {
+    for (setting, interaction, children) in buttons.iter() {
+        if !matches!(interaction, Interaction::Pressed) {
+            continue;
+        }
+
+        setting.next(&mut settings);
+
+        if let SettingButton::Grid = setting {
+            *grid = settings.show_grid.into();
+        }
+
+        if let Some(mut text) = children.first().and_then(|c| texts.get_mut(*c).ok()) {
+            text.sections[0].value = setting.label(&settings);
+        }
+
+        if let Err(e) = settings.save() {
+            error!("failed to save settings: {e}");
+        }
+    }
+}
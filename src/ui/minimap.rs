@@ -0,0 +1,238 @@
+use bevy::prelude::*;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+use bevy::ui::RelativeCursorPosition;
+use bevy::utils::HashMap;
+use bevy_ecs_tilemap::prelude::*;
+
+use crate::camera::MainCamera;
+use crate::map::{
+    BuildingLayer, BuildingTileType, TerrainLayer, TerrainType, TILEMAP_SIZE, TILE_SIZE,
+};
+
+pub const MINIMAP_SCALE: f32 = 3.;
+
+#[derive(Component)]
+pub struct Minimap;
+
+#[derive(Component)]
+pub struct MinimapViewport;
+
+#[derive(Resource)]
+pub struct MinimapImage {
+    image: Handle<Image>,
+    // building tiles painted on the minimap, so demolished tiles can be repainted with terrain
+    painted: HashMap<Entity, TilePos>,
+}
+
+pub fn init_minimap(mut commands: Commands, mut images: ResMut<Assets<Image>>) {
+    let image = images.add(Image::new_fill(
+        Extent3d {
+            width: TILEMAP_SIZE.x,
+            height: TILEMAP_SIZE.y,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        &Color::BLACK.as_rgba_u8(),
+        TextureFormat::Rgba8UnormSrgb,
+    ));
+
+    commands
+        .spawn(ImageBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                top: Val::Px(16.),
+                right: Val::Px(16.),
+                width: Val::Px(TILEMAP_SIZE.x as f32 * MINIMAP_SCALE),
+                height: Val::Px(TILEMAP_SIZE.y as f32 * MINIMAP_SCALE),
+                overflow: Overflow::clip(),
+                ..default()
+            },
+            image: UiImage::new(image.clone()),
+            ..default()
+        })
+        .insert((
+            Minimap,
+            Interaction::default(),
+            RelativeCursorPosition::default(),
+        ))
+        .with_children(|minimap| {
+            minimap
+                .spawn(NodeBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        border: UiRect::all(Val::Px(1.)),
+                        ..default()
+                    },
+                    border_color: Color::WHITE.into(),
+                    background_color: Color::NONE.into(),
+                    ..default()
+                })
+                .insert(MinimapViewport);
+        });
+
+    commands.insert_resource(MinimapImage {
+        image,
+        painted: HashMap::default(),
+    });
+}
+
+pub fn update_minimap_tiles(
+    mut minimap: ResMut<MinimapImage>,
+    mut images: ResMut<Assets<Image>>,
+    changed_tiles: Query<
+        (Entity, &TilePos, &TileTextureIndex, &TilemapId),
+        Changed<TileTextureIndex>,
+    >,
+    tiles: Query<&TileTextureIndex>,
+    mut removed_tiles: RemovedComponents<TilePos>,
+    terrain_layer: Query<(Entity, &TileStorage), With<TerrainLayer>>,
+    building_layer: Query<(Entity, &TileStorage), With<BuildingLayer>>,
+) {
+    let (Ok((terrain_entity, terrain_storage)), Ok((building_entity, building_storage))) =
+        (terrain_layer.get_single(), building_layer.get_single())
+    else {
+        return;
+    };
+
+    let terrain_color = |tile_pos: &TilePos| {
+        terrain_storage
+            .get(tile_pos)
+            .and_then(|e| tiles.get(e).ok())
+            .and_then(|t| TerrainType::try_from(*t).ok())
+            .map_or(Color::BLACK, terrain_type_color)
+    };
+
+    let mut dirty = Vec::new();
+
+    // repaint terrain under demolished building tiles
+    for entity in removed_tiles.iter() {
+        if let Some(tile_pos) = minimap.painted.remove(&entity)
+            && building_storage.get(&tile_pos).is_none()
+        {
+            dirty.push((tile_pos, terrain_color(&tile_pos)));
+        }
+    }
+
+    for (entity, tile_pos, texture, tilemap) in changed_tiles.iter() {
+        if tilemap.0 == building_entity {
+            minimap.painted.insert(entity, *tile_pos);
+            dirty.push((
+                *tile_pos,
+                building_tile_color(BuildingTileType::from(*texture)),
+            ));
+        } else if tilemap.0 == terrain_entity && building_storage.get(tile_pos).is_none() {
+            dirty.push((*tile_pos, terrain_color(tile_pos)));
+        }
+    }
+
+    if dirty.is_empty() {
+        return;
+    }
+
+    let Some(image) = images.get_mut(&minimap.image) else {
+        return;
+    };
+
+    for (tile_pos, color) in dirty {
+        // image rows go top to bottom, tile rows bottom to top
+        let row = TILEMAP_SIZE.y - 1 - tile_pos.y;
+        let index = ((row * TILEMAP_SIZE.x + tile_pos.x) * 4) as usize;
+
+        if let Some(pixel) = image.data.get_mut(index..index + 4) {
+            pixel.copy_from_slice(&color.as_rgba_u8());
+        }
+    }
+}
+
+// the camera, only when it moved or zoomed
+type MovedCamera<'w, 's> = Query<
+    'w,
+    's,
+    (&'static Transform, &'static OrthographicProjection),
+    (
+        With<MainCamera>,
+        Or<(Changed<Transform>, Changed<OrthographicProjection>)>,
+    ),
+>;
+
+pub fn update_minimap_viewport(
+    camera: MovedCamera,
+    building_layer: Query<&Transform, With<BuildingLayer>>,
+    mut viewport: Query<&mut Style, With<MinimapViewport>>,
+) {
+    let (Ok((camera_transform, ortho)), Ok(map_transform), Ok(mut style)) = (
+        camera.get_single(),
+        building_layer.get_single(),
+        viewport.get_single_mut(),
+    ) else {
+        return;
+    };
+
+    let to_minimap = |world_pos: Vec2| {
+        let tile = (world_pos - map_transform.translation.truncate())
+            / Vec2::new(TILE_SIZE.x, TILE_SIZE.y)
+            + Vec2::splat(0.5);
+
+        Vec2::new(tile.x, TILEMAP_SIZE.y as f32 - tile.y) * MINIMAP_SCALE
+    };
+
+    let camera_pos = camera_transform.translation.truncate();
+    let top_left = to_minimap(camera_pos + Vec2::new(ortho.area.min.x, ortho.area.max.y));
+    let bottom_right = to_minimap(camera_pos + Vec2::new(ortho.area.max.x, ortho.area.min.y));
+
+    style.left = Val::Px(top_left.x);
+    style.top = Val::Px(top_left.y);
+    style.width = Val::Px(bottom_right.x - top_left.x);
+    style.height = Val::Px(bottom_right.y - top_left.y);
+}
+
+pub fn minimap_navigation(
+    minimap: Query<(&Interaction, &RelativeCursorPosition), With<Minimap>>,
+    building_layer: Query<&Transform, (With<BuildingLayer>, Without<MainCamera>)>,
+    mut camera: Query<&mut Transform, With<MainCamera>>,
+) {
+    let (Ok((interaction, cursor)), Ok(map_transform), Ok(mut camera_transform)) = (
+        minimap.get_single(),
+        building_layer.get_single(),
+        camera.get_single_mut(),
+    ) else {
+        return;
+    };
+
+    if !matches!(interaction, Interaction::Pressed) {
+        return;
+    }
+
+    let Some(normalized) = cursor.normalized.filter(|_| cursor.mouse_over()) else {
+        return;
+    };
+
+    let tile = Vec2::new(
+        normalized.x * TILEMAP_SIZE.x as f32,
+        (1. - normalized.y) * TILEMAP_SIZE.y as f32,
+    ) - Vec2::splat(0.5);
+
+    let world_pos =
+        tile * Vec2::new(TILE_SIZE.x, TILE_SIZE.y) + map_transform.translation.truncate();
+
+    camera_transform.translation.x = world_pos.x;
+    camera_transform.translation.y = world_pos.y;
+}
+
+fn terrain_type_color(terrain_type: TerrainType) -> Color {
+    match terrain_type {
+        TerrainType::Grass => Color::rgb_u8(86, 125, 70),
+        TerrainType::Rock => Color::rgb_u8(110, 110, 118),
+    }
+}
+
+fn building_tile_color(tile_type: BuildingTileType) -> Color {
+    use BuildingTileType::*;
+
+    match tile_type {
+        BeltUp | BeltDown | BeltLeft | BeltRight => Color::rgb_u8(230, 196, 64),
+        MineTopLeft | MineTopRight | MineBottomLeft | MineBottomRight => Color::rgb_u8(120, 80, 50),
+        Chest => Color::rgb_u8(180, 120, 40),
+        Explosion | Unknown => Color::GRAY,
+    }
+}
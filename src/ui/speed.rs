@@ -0,0 +1,66 @@
+use bevy::prelude::*;
+
+use crate::simulation::Simulation;
+
+#[derive(Component)]
+pub struct SpeedIndicator;
+
+pub fn init_speed_indicator(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.),
+                top: Val::Px(16.),
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|root| {
+            root.spawn(NodeBundle {
+                style: Style {
+                    padding: UiRect::axes(Val::Px(16.), Val::Px(8.)),
+                    ..default()
+                },
+                background_color: Color::WHITE.into(),
+                ..default()
+            })
+            .with_children(|panel| {
+                panel
+                    .spawn(TextBundle::from_section(
+                        "",
+                        TextStyle {
+                            font: asset_server.load("AsepriteFont.ttf"),
+                            font_size: 16.,
+                            color: Color::DARK_GRAY,
+                        },
+                    ))
+                    .insert(SpeedIndicator);
+            });
+        });
+}
+
+pub fn update_speed_indicator(
+    simulation: Res<Simulation>,
+    mut indicator: Query<&mut Text, With<SpeedIndicator>>,
+    mut shown: Local<Option<(bool, &'static str)>>,
+) {
+    let state = (simulation.paused, simulation.speed.label());
+
+    // the simulation changes every tick, only touch the text when something visible did
+    if *shown == Some(state) {
+        return;
+    }
+
+    let Ok(mut text) = indicator.get_single_mut() else {
+        return;
+    };
+
+    text.sections[0].value = match simulation.paused {
+        true => format!("PAUSED {}", simulation.speed.label()),
+        false => format!("SPEED {}", simulation.speed.label()),
+    };
+
+    *shown = Some(state);
+}
@@ -0,0 +1,242 @@
+use std::time::Duration;
+
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+
+use crate::statistics::{ItemFlow, ProductionStatistics, StatisticsWindow};
+use crate::ui::UiEvent;
+
+const REFRESH_INTERVAL: Duration = Duration::from_millis(500);
+const GRAPH_HEIGHT: f32 = 32.;
+
+#[derive(Component)]
+pub struct StatisticsPanel;
+
+#[derive(Component)]
+pub struct StatisticsContent;
+
+#[derive(Component)]
+pub struct StatisticsWindowButton(StatisticsWindow);
+
+#[derive(Resource, Default)]
+pub struct StatisticsView {
+    window: StatisticsWindow,
+}
+
+pub fn init_statistics_panel(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let text_style = TextStyle {
+        font: asset_server.load("AsepriteFont.ttf"),
+        font_size: 16.,
+        color: Color::DARK_GRAY,
+    };
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                flex_direction: FlexDirection::Column,
+                top: Val::Px(16.),
+                left: Val::Px(220.),
+                padding: UiRect::all(Val::Px(16.)),
+                ..default()
+            },
+            background_color: Color::WHITE.into(),
+            visibility: Visibility::Hidden,
+            ..default()
+        })
+        .insert((StatisticsPanel, Interaction::default()))
+        .with_children(|panel| {
+            panel
+                .spawn(NodeBundle {
+                    style: Style {
+                        align_items: AlignItems::Center,
+                        margin: UiRect::bottom(Val::Px(8.)),
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|header| {
+                    header.spawn(
+                        TextBundle::from_section(
+                            "PRODUCTION",
+                            TextStyle {
+                                font_size: 24.,
+                                ..text_style.clone()
+                            },
+                        )
+                        .with_style(Style {
+                            flex_grow: 1.,
+                            margin: UiRect::right(Val::Px(16.)),
+                            ..default()
+                        }),
+                    );
+
+                    for window in StatisticsWindow::ALL {
+                        header
+                            .spawn(ButtonBundle {
+                                style: Style {
+                                    padding: UiRect::axes(Val::Px(8.), Val::Px(4.)),
+                                    ..default()
+                                },
+                                ..default()
+                            })
+                            .insert(StatisticsWindowButton(window))
+                            .with_children(|button| {
+                                button.spawn(TextBundle::from_section(
+                                    window.label(),
+                                    text_style.clone(),
+                                ));
+                            });
+                    }
+                });
+
+            panel
+                .spawn(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Column,
+                        ..default()
+                    },
+                    ..default()
+                })
+                .insert(StatisticsContent);
+        });
+}
+
+pub fn toggle_statistics_panel(
+    mut ui_events: EventReader<UiEvent>,
+    mut panel: Query<&mut Visibility, With<StatisticsPanel>>,
+) {
+    for _ in ui_events
+        .iter()
+        .filter(|e| matches!(e, UiEvent::ToggleStatistics))
+    {
+        if let Ok(mut visibility) = panel.get_single_mut() {
+            *visibility = match *visibility {
+                Visibility::Hidden => Visibility::Inherited,
+                _ => Visibility::Hidden,
+            };
+        }
+    }
+}
+
+pub fn select_statistics_window(
+    actions: Query<(&StatisticsWindowButton, &Interaction), Changed<Interaction>>,
+    mut buttons: Query<(&StatisticsWindowButton, &mut BackgroundColor)>,
+    mut view: ResMut<StatisticsView>,
+) {
+    if let Some((StatisticsWindowButton(window), _)) = actions
+        .iter()
+        .find(|(_, interaction)| matches!(interaction, Interaction::Pressed))
+    {
+        view.window = *window;
+    }
+
+    if view.is_changed() {
+        for (StatisticsWindowButton(window), mut color) in buttons.iter_mut() {
+            *color = match *window == view.window {
+                true => Color::GRAY.into(),
+                false => Color::WHITE.into(),
+            };
+        }
+    }
+}
+
+// the panel is rebuilt every REFRESH_INTERVAL at most
+#[derive(SystemParam)]
+pub struct RefreshTimer<'w, 's> {
+    time: Res<'w, Time>,
+    last_refresh: Local<'s, Duration>,
+}
+
+impl RefreshTimer<'_, '_> {
+    fn is_due(&self) -> bool {
+        self.time.elapsed().saturating_sub(*self.last_refresh) >= REFRESH_INTERVAL
+    }
+
+    fn restart(&mut self) {
+        *self.last_refresh = self.time.elapsed();
+    }
+}
+
+pub fn update_statistics_panel(
+    mut commands: Commands,
+    view: Res<StatisticsView>,
+    statistics: Res<ProductionStatistics>,
+    panel: Query<Ref<Visibility>, With<StatisticsPanel>>,
+    content: Query<Entity, With<StatisticsContent>>,
+    asset_server: Res<AssetServer>,
+    mut refresh: RefreshTimer,
+) {
+    let (Ok(visibility), Ok(content)) = (panel.get_single(), content.get_single()) else {
+        return;
+    };
+
+    if *visibility == Visibility::Hidden {
+        return;
+    }
+
+    if !refresh.is_due() && !visibility.is_changed() && !view.is_changed() {
+        return;
+    }
+
+    refresh.restart();
+
+    let text_style = TextStyle {
+        font: asset_server.load("AsepriteFont.ttf"),
+        font_size: 16.,
+        color: Color::DARK_GRAY,
+    };
+
+    commands.entity(content).despawn_descendants();
+    commands.entity(content).with_children(|content| {
+        for item_type in statistics.item_types() {
+            for flow in [ItemFlow::Produced, ItemFlow::Consumed] {
+                let Some(history) = statistics.get(item_type, flow) else {
+                    continue;
+                };
+
+                content.spawn(TextBundle::from_section(
+                    format!(
+                        "{} {} {:.1}/min",
+                        item_type.as_str().to_uppercase(),
+                        flow.as_str(),
+                        history.rate(view.window),
+                    ),
+                    text_style.clone(),
+                ));
+
+                let samples = history.window(view.window);
+                let max = samples.samples().max().unwrap_or(0).max(1);
+                let bar_color = match flow {
+                    ItemFlow::Produced => Color::rgb_u8(86, 125, 70),
+                    ItemFlow::Consumed => Color::rgb_u8(179, 24, 0),
+                };
+
+                content
+                    .spawn(NodeBundle {
+                        style: Style {
+                            align_items: AlignItems::FlexEnd,
+                            height: Val::Px(GRAPH_HEIGHT),
+                            margin: UiRect::bottom(Val::Px(8.)),
+                            ..default()
+                        },
+                        background_color: Color::rgb(0.9, 0.9, 0.9).into(),
+                        ..default()
+                    })
+                    .with_children(|graph| {
+                        for sample in samples.samples() {
+                            graph.spawn(NodeBundle {
+                                style: Style {
+                                    width: Val::Px(4.),
+                                    height: Val::Percent(sample as f32 / max as f32 * 100.),
+                                    ..default()
+                                },
+                                background_color: bar_color.into(),
+                                ..default()
+                            });
+                        }
+                    });
+            }
+        }
+    });
+}
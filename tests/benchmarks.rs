@@ -0,0 +1,32 @@
+use bevactorio::{run_benchmark, BuildingRegistry, Scenario};
+
+fn registry() -> BuildingRegistry {
+    BuildingRegistry::load_dir("assets/buildings").unwrap()
+}
+
+// the scenarios are only worth measuring while they build what they claim to
+#[test]
+fn scenarios_build_their_factories() {
+    let serpentine = run_benchmark(Scenario::Serpentine, registry(), 120);
+    assert_eq!(serpentine.belts, 60 * 63);
+    assert_eq!(serpentine.mines, 31);
+    assert!(serpentine.items > 0);
+    assert_eq!(serpentine.systems.len(), 3);
+
+    let mines = run_benchmark(Scenario::Mines, registry(), 120);
+    assert_eq!(mines.belts, 512);
+    assert_eq!(mines.mines, 512);
+
+    let guide = run_benchmark(Scenario::BuildGuide, registry(), 10);
+    let names: Vec<_> = guide.systems.iter().map(|timing| timing.name).collect();
+    assert_eq!(names, ["update_build_guide", "update_demo_guide"]);
+}
+
+#[test]
+fn scenarios_parse_from_their_names() {
+    for scenario in Scenario::ALL {
+        assert!(matches!(scenario.as_str().parse::<Scenario>(), Ok(s) if s == scenario));
+    }
+
+    assert!("factorio".parse::<Scenario>().is_err());
+}
@@ -0,0 +1,155 @@
+use std::io::Write;
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use bevactorio::{
+    decode, encode, Blueprint, BlueprintDecodeError, BlueprintEntry, BuildingRegistry,
+    BuildingTileType, BuildingType, MapDirection, BLUEPRINT_VERSION, MAX_BLUEPRINT_BYTES,
+    TILEMAP_SIZE,
+};
+use bevy_ecs_tilemap::prelude::*;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+
+fn registry() -> BuildingRegistry {
+    BuildingRegistry::load_dir("assets/buildings").unwrap()
+}
+
+fn entry(building_type: BuildingType, x: u32, y: u32) -> BlueprintEntry {
+    let size = match building_type {
+        BuildingType::Mine => 2,
+        _ => 1,
+    };
+
+    BlueprintEntry {
+        x,
+        y,
+        width: size,
+        height: size,
+        building_type,
+        direction: MapDirection::Right,
+        tile_type: None,
+    }
+}
+
+fn blueprint(entries: Vec<BlueprintEntry>) -> Blueprint {
+    Blueprint {
+        name: "test".to_string(),
+        entries,
+    }
+}
+
+// what encode writes, for contents encode can't produce
+fn compress(contents: &[u8]) -> String {
+    let mut encoder = DeflateEncoder::new(Vec::new(), Compression::best());
+    encoder.write_all(contents).unwrap();
+    format!(
+        "{BLUEPRINT_VERSION}{}",
+        BASE64.encode(encoder.finish().unwrap())
+    )
+}
+
+#[test]
+fn blueprints_round_trip() {
+    let mut belt = entry(BuildingType::Belt, 2, 0);
+    belt.tile_type = Some(BuildingTileType::BeltRight);
+    let original = blueprint(vec![
+        entry(BuildingType::Mine, 0, 0),
+        belt,
+        entry(BuildingType::Chest, 3, 0),
+    ]);
+
+    let decoded = decode(&encode(&original).unwrap(), &registry()).unwrap();
+    assert!(decoded == original);
+}
+
+#[test]
+fn other_versions_are_rejected() {
+    let encoded = encode(&blueprint(vec![entry(BuildingType::Chest, 0, 0)])).unwrap();
+    let other_version = format!("0{}", &encoded[1..]);
+
+    assert!(matches!(
+        decode(&other_version, &registry()),
+        Err(BlueprintDecodeError::UnsupportedVersion('0'))
+    ));
+    assert!(matches!(
+        decode("  ", &registry()),
+        Err(BlueprintDecodeError::Empty)
+    ));
+}
+
+#[test]
+fn corrupt_strings_are_rejected() {
+    let registry = registry();
+
+    let bad_base64 = format!("{BLUEPRINT_VERSION}not base64!");
+    assert!(matches!(
+        decode(&bad_base64, &registry),
+        Err(BlueprintDecodeError::Corrupt(_))
+    ));
+
+    let bad_deflate = format!("{BLUEPRINT_VERSION}{}", BASE64.encode([0xff; 16]));
+    assert!(matches!(
+        decode(&bad_deflate, &registry),
+        Err(BlueprintDecodeError::Corrupt(_))
+    ));
+
+    // compresses well, inflates past the limit
+    let too_large = compress(&vec![b' '; MAX_BLUEPRINT_BYTES as usize + 1]);
+    assert!(matches!(
+        decode(&too_large, &registry),
+        Err(BlueprintDecodeError::Corrupt(reason)) if reason.contains("larger")
+    ));
+}
+
+#[test]
+fn unknown_buildings_are_reported() {
+    let unknown = compress(
+        b"(name:\"test\",entries:[(building:\"furnace\",x:0,y:0,width:1,height:1,\
+          direction:Up,tile_type:None)])",
+    );
+
+    assert!(matches!(
+        decode(&unknown, &registry()),
+        Err(BlueprintDecodeError::UnknownBuilding(name)) if name == "furnace"
+    ));
+}
+
+#[test]
+fn entries_outside_the_map_are_rejected() {
+    let registry = registry();
+
+    let outside = blueprint(vec![entry(BuildingType::Chest, TILEMAP_SIZE.x, 0)]);
+    assert!(matches!(
+        decode(&encode(&outside).unwrap(), &registry),
+        Err(BlueprintDecodeError::Corrupt(reason)) if reason.contains("outside the map")
+    ));
+
+    // the footprint starts on the map but doesn't end there
+    let sticking_out = blueprint(vec![entry(BuildingType::Mine, 0, TILEMAP_SIZE.y - 1)]);
+    assert!(matches!(
+        decode(&encode(&sticking_out).unwrap(), &registry),
+        Err(BlueprintDecodeError::Corrupt(reason)) if reason.contains("outside the map")
+    ));
+
+    let overflowing = blueprint(vec![entry(BuildingType::Chest, u32::MAX, 0)]);
+    assert!(matches!(
+        decode(&encode(&overflowing).unwrap(), &registry),
+        Err(BlueprintDecodeError::Corrupt(reason)) if reason.contains("outside the map")
+    ));
+}
+
+#[test]
+fn pasting_at_the_edge_leaves_out_what_sticks_out() {
+    let pasted = blueprint(vec![
+        entry(BuildingType::Chest, 0, 0),
+        entry(BuildingType::Mine, 1, 0),
+    ]);
+
+    let edge = TilePos::new(TILEMAP_SIZE.x - 2, 0);
+    let requests: Vec<_> = pasted.requests_at(edge).map(|r| r.tile_pos).collect();
+    assert_eq!(requests, [edge]);
+
+    let far_away = TilePos::new(u32::MAX, u32::MAX);
+    assert_eq!(pasted.requests_at(far_away).count(), 0);
+}
@@ -0,0 +1,27 @@
+use bevactorio::{
+    BuildRequestedEvent, BuildingRegistry, BuildingType, MapDirection, Simulation,
+    SimulationPlugin, StatisticsPlugin,
+};
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::*;
+
+// the simulation without rendering, paused so it only advances through run_ticks or steps
+pub fn headless_app() -> App {
+    let mut app = App::new();
+
+    app.add_plugins((MinimalPlugins, SimulationPlugin, StatisticsPlugin))
+        .insert_resource(BuildingRegistry::load_dir("assets/buildings").unwrap());
+
+    // startup creates the map
+    app.update();
+    app.world.resource_mut::<Simulation>().paused = true;
+    app
+}
+
+pub fn build(app: &mut App, building_type: BuildingType, x: u32, y: u32, direction: MapDirection) {
+    app.world.send_event(BuildRequestedEvent {
+        building_type,
+        direction,
+        tile_pos: TilePos::new(x, y),
+    });
+}
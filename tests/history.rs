@@ -0,0 +1,160 @@
+use bevactorio::{
+    ActionMap, Belt, BuildMode, Building, BuildingTileType, BuildingType, DemolishEvent,
+    HistoryEvent, HistoryPlugin, Inventory, ItemType, MapDirection, SpawnItemEvent,
+};
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::*;
+
+use self::common::{build, headless_app};
+
+mod common;
+
+// history is only kept in build mode, the mouse is pressed and released by hand
+fn history_app() -> App {
+    let mut app = headless_app();
+
+    app.add_state::<BuildMode>()
+        .insert_resource(NextState(Some(BuildMode::Enabled)))
+        .init_resource::<ActionMap>()
+        .init_resource::<Input<KeyCode>>()
+        .init_resource::<Input<MouseButton>>()
+        .add_plugins(HistoryPlugin);
+
+    app.update();
+    app
+}
+
+// one frame, without the input plugin the mouse has to be told the frame is over
+fn frame(app: &mut App) {
+    app.update();
+    app.world.resource_mut::<Input<MouseButton>>().clear();
+}
+
+fn mouse(app: &mut App, pressed: bool) {
+    let mut mouse = app.world.resource_mut::<Input<MouseButton>>();
+
+    match pressed {
+        true => mouse.press(MouseButton::Left),
+        false => mouse.release(MouseButton::Left),
+    }
+}
+
+// the whole step lands in one frame, restored chest contents included
+fn history(app: &mut App, event: HistoryEvent) {
+    app.world.send_event(event);
+    frame(app);
+}
+
+fn buildings(app: &mut App) -> Vec<(BuildingType, (u32, u32))> {
+    let mut buildings: Vec<_> = app
+        .world
+        .query::<(&BuildingType, &TilePos, With<Building>)>()
+        .iter(&app.world)
+        .map(|(building_type, origin, _)| (*building_type, (origin.x, origin.y)))
+        .collect();
+    buildings.sort_by_key(|(_, origin)| *origin);
+    buildings
+}
+
+fn belt_at(app: &mut App, x: u32, y: u32) -> Option<BuildingTileType> {
+    app.world
+        .query_filtered::<(&TilePos, &TileTextureIndex), With<Belt>>()
+        .iter(&app.world)
+        .find(|(tile_pos, _)| **tile_pos == TilePos::new(x, y))
+        .map(|(_, texture)| BuildingTileType::from(*texture))
+}
+
+#[test]
+fn a_drag_is_undone_at_once() {
+    let mut app = history_app();
+
+    mouse(&mut app, true);
+    for x in 1..=3 {
+        build(&mut app, BuildingType::Belt, x, 5, MapDirection::Left);
+        frame(&mut app);
+    }
+    mouse(&mut app, false);
+    frame(&mut app);
+
+    // a click of its own
+    mouse(&mut app, true);
+    build(&mut app, BuildingType::Chest, 0, 5, MapDirection::Left);
+    frame(&mut app);
+    mouse(&mut app, false);
+    frame(&mut app);
+
+    let belts: Vec<_> = (1..=3).map(|x| (BuildingType::Belt, (x, 5))).collect();
+    let everything = [vec![(BuildingType::Chest, (0, 5))], belts.clone()].concat();
+    assert_eq!(buildings(&mut app), everything);
+
+    history(&mut app, HistoryEvent::Undo);
+    assert_eq!(buildings(&mut app), belts);
+
+    history(&mut app, HistoryEvent::Undo);
+    assert_eq!(buildings(&mut app), []);
+
+    history(&mut app, HistoryEvent::Redo);
+    assert_eq!(buildings(&mut app), belts);
+
+    history(&mut app, HistoryEvent::Redo);
+    assert_eq!(buildings(&mut app), everything);
+}
+
+#[test]
+fn belt_redirects_are_undone() {
+    let mut app = history_app();
+
+    build(&mut app, BuildingType::Belt, 1, 5, MapDirection::Left);
+    frame(&mut app);
+    build(&mut app, BuildingType::Belt, 1, 5, MapDirection::Up);
+    frame(&mut app);
+    assert_eq!(belt_at(&mut app, 1, 5), Some(BuildingTileType::BeltUp));
+
+    history(&mut app, HistoryEvent::Undo);
+    assert_eq!(belt_at(&mut app, 1, 5), Some(BuildingTileType::BeltLeft));
+
+    history(&mut app, HistoryEvent::Redo);
+    assert_eq!(belt_at(&mut app, 1, 5), Some(BuildingTileType::BeltUp));
+
+    // undoing the redirect and then the belt itself
+    history(&mut app, HistoryEvent::Undo);
+    history(&mut app, HistoryEvent::Undo);
+    assert_eq!(belt_at(&mut app, 1, 5), None);
+}
+
+#[test]
+fn demolished_chests_get_their_contents_back() {
+    let mut app = history_app();
+
+    build(&mut app, BuildingType::Chest, 0, 5, MapDirection::Left);
+    frame(&mut app);
+    app.world.send_event(SpawnItemEvent {
+        item_type: ItemType::Coal,
+        tile_pos: TilePos::new(0, 5),
+        amount: 7,
+    });
+    frame(&mut app);
+
+    let contents = |app: &mut App| {
+        app.world
+            .query::<&Inventory>()
+            .get_single(&app.world)
+            .map(|inventory| inventory.slots.to_vec())
+            .ok()
+    };
+    let stored = contents(&mut app).unwrap();
+    assert_eq!(
+        stored[..2],
+        [Some((ItemType::Coal, 5)), Some((ItemType::Coal, 2))]
+    );
+
+    app.world.send_event(DemolishEvent {
+        tile_pos: TilePos::new(0, 5),
+    });
+    frame(&mut app);
+    assert_eq!(contents(&mut app), None);
+
+    // back in the frame the undo is handled in
+    history(&mut app, HistoryEvent::Undo);
+    assert_eq!(contents(&mut app), Some(stored));
+}
@@ -0,0 +1,302 @@
+use bevactorio::{
+    run_ticks, Belt, Building, BuildingType, DemolishEvent, Inventory, InventoryEvent, Item,
+    ItemConservation, ItemFlow, ItemType, ItemViolation, MapDirection, MapEvent,
+    ProductionStatistics, Simulation, SpawnItemEvent, StatisticsWindow,
+};
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::*;
+
+use self::common::{build, headless_app};
+
+mod common;
+
+// ten seconds of simulation, a mine produces once per second
+const TICKS: u32 = 600;
+
+// a mine at (5, 5) outputting left onto a belt line that ends in a chest at (0, 5)
+fn mine_to_chest() -> App {
+    let mut app = headless_app();
+
+    build(&mut app, BuildingType::Mine, 5, 5, MapDirection::Left);
+    for x in 1..=4 {
+        build(&mut app, BuildingType::Belt, x, 5, MapDirection::Left);
+    }
+    build(&mut app, BuildingType::Chest, 0, 5, MapDirection::Left);
+
+    // requests are fully built within the frame they are handled in
+    app.update();
+    app
+}
+
+fn chest_contents(app: &mut App) -> usize {
+    let mut chests = app.world.query::<(&BuildingType, &Inventory)>();
+
+    chests
+        .iter(&app.world)
+        .filter(|(building_type, _)| **building_type == BuildingType::Chest)
+        .flat_map(|(_, inventory)| inventory.slots.iter().flatten())
+        .map(|(item_type, amount)| {
+            assert_eq!(*item_type, ItemType::Coal);
+            *amount
+        })
+        .sum()
+}
+
+fn flow_total(app: &App, flow: ItemFlow) -> u32 {
+    app.world
+        .resource::<ProductionStatistics>()
+        .get(ItemType::Coal, flow)
+        .map_or(0, |history| {
+            history.window(StatisticsWindow::Minute).total()
+        })
+}
+
+// item entities and the items belts carry
+fn item_counts(app: &mut App) -> (usize, usize) {
+    let items = app.world.query::<&Item>().iter(&app.world).count();
+    let belt_items = app
+        .world
+        .query::<&Belt>()
+        .iter(&app.world)
+        .map(|belt| belt.items.len())
+        .sum();
+
+    (items, belt_items)
+}
+
+fn violations(app: &App) -> &[ItemViolation] {
+    app.world.resource::<ItemConservation>().violations()
+}
+
+fn advance(app: &mut App, ticks: u32) {
+    run_ticks(&mut app.world, ticks);
+    // lets the statistics pick up the item flow of those ticks
+    app.update();
+}
+
+#[test]
+fn builds_requested_buildings() {
+    let mut app = mine_to_chest();
+
+    let buildings = app.world.query::<&Building>().iter(&app.world).count();
+    let belts = app.world.query::<&Belt>().iter(&app.world).count();
+
+    assert_eq!(buildings, 6);
+    assert_eq!(belts, 4);
+}
+
+#[test]
+fn mine_fills_chest_over_belts() {
+    let mut app = mine_to_chest();
+
+    advance(&mut app, TICKS);
+
+    let stored = chest_contents(&mut app);
+    let produced = flow_total(&app, ItemFlow::Produced);
+    let consumed = flow_total(&app, ItemFlow::Consumed);
+
+    assert!(stored > 0, "no coal reached the chest");
+    assert_eq!(consumed as usize, stored);
+    assert!(produced >= consumed);
+    assert_eq!(app.world.resource::<Simulation>().tick(), TICKS as u64);
+}
+
+#[test]
+fn paused_simulation_does_not_move_items() {
+    let mut app = mine_to_chest();
+
+    for _ in 0..10 {
+        app.update();
+    }
+
+    assert_eq!(app.world.resource::<Simulation>().tick(), 0);
+    assert_eq!(flow_total(&app, ItemFlow::Produced), 0);
+}
+
+#[test]
+fn demolished_chest_stops_consumption() {
+    let mut app = mine_to_chest();
+
+    advance(&mut app, TICKS);
+
+    app.world.send_event(DemolishEvent {
+        tile_pos: TilePos::new(0, 5),
+    });
+    app.update();
+
+    let consumed = flow_total(&app, ItemFlow::Consumed);
+
+    advance(&mut app, TICKS);
+
+    assert_eq!(chest_contents(&mut app), 0);
+    assert_eq!(flow_total(&app, ItemFlow::Consumed), consumed);
+}
+
+#[test]
+fn clear_buildings_empties_map() {
+    let mut app = mine_to_chest();
+
+    app.world.send_event(MapEvent::ClearBuildings);
+    app.update();
+
+    let buildings = app.world.query::<&Building>().iter(&app.world).count();
+    assert_eq!(buildings, 0);
+}
+
+#[test]
+fn spawned_items_land_on_belts_and_in_chests() {
+    let mut app = headless_app();
+
+    for x in 1..=4 {
+        build(&mut app, BuildingType::Belt, x, 5, MapDirection::Left);
+    }
+    build(&mut app, BuildingType::Chest, 0, 5, MapDirection::Left);
+
+    // more than a belt holds, the rest is dropped
+    for (x, amount) in [(4, 5), (0, 2)] {
+        app.world.send_event(SpawnItemEvent {
+            item_type: ItemType::Coal,
+            tile_pos: TilePos::new(x, 5),
+            amount,
+        });
+    }
+    app.update();
+
+    let belt_items: usize = app
+        .world
+        .query::<&Belt>()
+        .iter(&app.world)
+        .map(|belt| belt.items.len())
+        .sum();
+
+    assert_eq!(belt_items, 3);
+    assert_eq!(chest_contents(&mut app), 2);
+
+    advance(&mut app, TICKS);
+
+    assert_eq!(chest_contents(&mut app), 5);
+    assert_eq!(flow_total(&app, ItemFlow::Produced), 0);
+    assert_eq!(flow_total(&app, ItemFlow::Consumed), 3);
+}
+
+#[test]
+fn taken_items_leave_the_rest_of_the_stack() {
+    let mut app = headless_app();
+
+    build(&mut app, BuildingType::Chest, 0, 5, MapDirection::Left);
+    app.world.send_event(SpawnItemEvent {
+        item_type: ItemType::Coal,
+        tile_pos: TilePos::new(0, 5),
+        amount: 4,
+    });
+    app.update();
+
+    let take = |amount| InventoryEvent::Take {
+        tile_pos: TilePos::new(0, 5),
+        slot: 0,
+        amount,
+    };
+
+    app.world.send_event(take(3));
+    app.update();
+    assert_eq!(chest_contents(&mut app), 1);
+
+    // never more than the stack holds
+    app.world.send_event(take(3));
+    app.update();
+    assert_eq!(chest_contents(&mut app), 0);
+}
+
+#[test]
+fn items_stay_on_exactly_one_belt() {
+    let mut app = mine_to_chest();
+
+    // the check runs at the start of a tick, one more sees the state after the last
+    advance(&mut app, TICKS + 1);
+
+    let (items, belt_items) = item_counts(&mut app);
+    assert!(items > 0);
+    assert_eq!(items, belt_items);
+    assert_eq!(violations(&app), []);
+}
+
+#[test]
+fn demolished_belts_take_their_items_along() {
+    let mut app = mine_to_chest();
+    advance(&mut app, TICKS);
+
+    for x in [2, 3] {
+        app.world.send_event(DemolishEvent {
+            tile_pos: TilePos::new(x, 5),
+        });
+    }
+    // replacing a belt throws away what it carried as well
+    build(&mut app, BuildingType::Belt, 4, 5, MapDirection::Up);
+    app.update();
+
+    let (items, belt_items) = item_counts(&mut app);
+    assert_eq!(items, belt_items);
+
+    advance(&mut app, 1);
+    assert_eq!(violations(&app), []);
+}
+
+#[test]
+fn clear_buildings_removes_items() {
+    let mut app = mine_to_chest();
+    advance(&mut app, TICKS);
+    assert_ne!(item_counts(&mut app), (0, 0));
+
+    app.world.send_event(MapEvent::ClearBuildings);
+    app.update();
+
+    assert_eq!(item_counts(&mut app), (0, 0));
+}
+
+#[test]
+fn items_without_a_belt_are_reported() {
+    let mut app = mine_to_chest();
+
+    let item = app
+        .world
+        .spawn(Item {
+            belt: Entity::PLACEHOLDER,
+            item_type: ItemType::Coal,
+        })
+        .id();
+    advance(&mut app, 1);
+
+    assert_eq!(
+        violations(&app),
+        [ItemViolation::Lost {
+            item,
+            item_type: ItemType::Coal,
+            tile_pos: None,
+        }]
+    );
+
+    app.world.despawn(item);
+    advance(&mut app, 1);
+    assert_eq!(violations(&app), []);
+}
+
+#[test]
+fn belts_into_buildings_without_an_input_are_dead_ends() {
+    let mut app = headless_app();
+
+    // the mine covers (2, 5) to (3, 6), it doesn't take items
+    build(&mut app, BuildingType::Mine, 2, 5, MapDirection::Up);
+    build(&mut app, BuildingType::Belt, 4, 5, MapDirection::Left);
+    app.world.send_event(SpawnItemEvent {
+        item_type: ItemType::Coal,
+        tile_pos: TilePos::new(4, 5),
+        amount: 1,
+    });
+    app.update();
+
+    advance(&mut app, 120);
+
+    let belt = app.world.query::<&Belt>().single(&app.world);
+    assert_eq!(belt.items.len(), 1);
+    assert!(belt.dead_end);
+}
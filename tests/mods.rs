@@ -0,0 +1,153 @@
+use bevactorio::{
+    run_ticks, Belt, Building, BuildingTileType, BuildingType, GameState, Inventory, ItemType,
+    MapDirection, ModBuilding, ModName, ModPlugin, ModRegistry, Mods,
+};
+use bevy::asset::AssetPlugin;
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::*;
+use rhai::EvalAltResult;
+
+use self::common::{build, headless_app};
+
+mod common;
+
+// callbacks only run in game, there is no mods folder to load from
+fn mod_app() -> App {
+    let mut app = headless_app();
+
+    app.add_plugins((AssetPlugin::default(), ModPlugin))
+        .add_state::<GameState>()
+        .insert_resource(NextState(Some(GameState::InGame)));
+
+    app.update();
+    app
+}
+
+fn load(app: &mut App, source: &str) -> Result<(), Box<EvalAltResult>> {
+    app.world.resource_mut::<Mods>().load("test", source)
+}
+
+#[test]
+fn endless_scripts_are_stopped() {
+    let mut app = mod_app();
+
+    let error = load(&mut app, "let n = 0; loop { n += 1; }").unwrap_err();
+    assert!(matches!(*error, EvalAltResult::ErrorTooManyOperations(_)));
+    assert!(!app.world.resource::<Mods>().is_loaded("test"));
+
+    // a callback that never returns doesn't hang the game either
+    load(&mut app, "fn on_building_placed(b, x, y, d) { loop {} }").unwrap();
+    build(&mut app, BuildingType::Chest, 0, 0, MapDirection::Up);
+    app.update();
+}
+
+#[test]
+fn failing_scripts_keep_their_previous_version() {
+    let mut app = mod_app();
+
+    load(&mut app, r#"add_button("old", "old");"#).unwrap();
+    let result = load(
+        &mut app,
+        r#"register_item("ore"); add_button("new", "new"); throw "broken";"#,
+    );
+    assert!(result.is_err());
+    app.update();
+
+    assert!(app.world.resource::<Mods>().is_loaded("test"));
+    assert!(app.world.resource::<ModRegistry>().is_empty());
+}
+
+#[test]
+fn registered_mines_produce_registered_items() {
+    let mut app = mod_app();
+
+    load(
+        &mut app,
+        r#"
+        register_item("ore");
+        register_building("ore_mine", "mine", "ore");
+        register_building("crate", "chest");
+        "#,
+    )
+    .unwrap();
+    app.update();
+
+    let registry = app.world.resource::<ModRegistry>().clone();
+    let ore = ItemType::Mod(ModName::new("ore"));
+    assert_eq!(
+        registry.buildings.get(&ModName::new("ore_mine")),
+        Some(&ModBuilding::Mine { item: ore })
+    );
+
+    // the registry comes back the same from a save, replay or the host
+    let text = ron::to_string(&registry).unwrap();
+    assert_eq!(ron::from_str::<ModRegistry>(&text).unwrap(), registry);
+
+    let ore_mine = BuildingType::Mod(ModName::new("ore_mine"));
+    let chest = BuildingType::Mod(ModName::new("crate"));
+    build(&mut app, ore_mine, 5, 5, MapDirection::Left);
+    for x in 1..=4 {
+        build(&mut app, BuildingType::Belt, x, 5, MapDirection::Left);
+    }
+    build(&mut app, chest, 0, 5, MapDirection::Left);
+    app.update();
+
+    run_ticks(&mut app.world, 600);
+
+    let contents: Vec<_> = app
+        .world
+        .query::<&Inventory>()
+        .iter(&app.world)
+        .flat_map(|inventory| inventory.slots.iter().flatten().copied())
+        .collect();
+    assert!(!contents.is_empty());
+    assert!(contents.iter().all(|(item_type, _)| *item_type == ore));
+}
+
+#[test]
+fn scripts_cant_import_or_eval() {
+    let mut app = mod_app();
+
+    let error = load(&mut app, r#"import "mods/other" as other;"#).unwrap_err();
+    assert!(
+        matches!(*error, EvalAltResult::ErrorModuleNotFound(..)),
+        "{error}"
+    );
+
+    let error = load(&mut app, r#"eval("40 + 2")"#).unwrap_err();
+    assert!(matches!(*error, EvalAltResult::ErrorParsing(..)), "{error}");
+}
+
+#[test]
+fn placed_buildings_reach_scripts_and_their_builds_come_back() {
+    let mut app = mod_app();
+
+    load(
+        &mut app,
+        r#"
+        fn on_building_placed(building, x, y, direction) {
+            if building == "mine" {
+                build("belt", x - 1, y, "left");
+            }
+        }
+        "#,
+    )
+    .unwrap();
+
+    build(&mut app, BuildingType::Mine, 5, 5, MapDirection::Left);
+    // the script answers after the simulation, its build is handled in the next frame
+    app.update();
+    app.update();
+
+    let belt = app
+        .world
+        .query_filtered::<(&TilePos, &TileTextureIndex), With<Belt>>()
+        .get_single(&app.world)
+        .map(|(tile_pos, texture)| (*tile_pos, BuildingTileType::from(*texture)))
+        .unwrap();
+    assert_eq!(belt, (TilePos::new(4, 5), BuildingTileType::BeltLeft));
+
+    // the belt was placed too, but the script only builds next to mines
+    let buildings = app.world.query::<&Building>().iter(&app.world).count();
+    assert_eq!(buildings, 2);
+}
@@ -0,0 +1,259 @@
+use std::thread;
+use std::time::Duration;
+
+use bevactorio::{
+    ActionMap, Belt, BuildMode, BuildingType, DemolishEvent, GameState, HistoryEvent,
+    HistoryPlugin, Inventory, InventoryEvent, Item, ItemType, Lockstep, MapDirection,
+    MultiplayerPlugin, Simulation, SpawnItemEvent,
+};
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::*;
+
+use self::common::{build, headless_app};
+
+mod common;
+
+const MAX_FRAMES: usize = 10_000;
+
+type Slots = Vec<Option<(ItemType, usize)>>;
+
+#[derive(Debug, PartialEq)]
+struct FactoryState {
+    tick: u64,
+    buildings: Vec<(BuildingType, (u32, u32), Option<Slots>)>,
+    belt_items: usize,
+    items: usize,
+}
+
+fn factory_state(app: &mut App) -> FactoryState {
+    let mut buildings = app
+        .world
+        .query::<(&BuildingType, &TilePos, Option<&Inventory>)>()
+        .iter(&app.world)
+        .map(|(building_type, origin, inventory)| {
+            let slots = inventory.map(|inventory| inventory.slots.to_vec());
+            (*building_type, (origin.x, origin.y), slots)
+        })
+        .collect::<Vec<_>>();
+    buildings.sort_by_key(|(_, origin, _)| *origin);
+
+    let belt_items = app
+        .world
+        .query::<&Belt>()
+        .iter(&app.world)
+        .map(|belt| belt.items.len())
+        .sum();
+    let items = app.world.query::<&Item>().iter(&app.world).count();
+
+    FactoryState {
+        tick: tick(app),
+        buildings,
+        belt_items,
+        items,
+    }
+}
+
+fn tick(app: &App) -> u64 {
+    app.world.resource::<Simulation>().tick()
+}
+
+fn lockstep(app: &App) -> &Lockstep {
+    app.world.resource::<Lockstep>()
+}
+
+fn peer(lockstep: Lockstep) -> App {
+    let mut app = headless_app();
+    app.add_state::<GameState>()
+        .add_plugins(MultiplayerPlugin)
+        .insert_resource(lockstep);
+    app
+}
+
+// both peers get a frame with at most one tick until `done`, a peer that reached `max_tick`
+// only keeps up with the messages
+fn run_until(host: &mut App, client: &mut App, max_tick: u64, done: impl Fn(&App, &App) -> bool) {
+    for _ in 0..MAX_FRAMES {
+        if done(host, client) {
+            return;
+        }
+
+        for app in [&mut *host, &mut *client] {
+            if tick(app) < max_tick {
+                app.world.resource_mut::<Simulation>().step();
+            }
+            app.update();
+        }
+
+        thread::sleep(Duration::from_millis(1));
+    }
+
+    panic!("peers stuck at ticks {} and {}", tick(host), tick(client));
+}
+
+fn advance(host: &mut App, client: &mut App, to: u64) {
+    run_until(host, client, to, |host, client| {
+        tick(host) == to && tick(client) == to
+    });
+}
+
+// the client joins while the host is in the main menu and follows it into the game
+fn start() -> (App, App) {
+    let host_lockstep = Lockstep::host(0).unwrap();
+    let port = host_lockstep.local_addr().unwrap().port();
+
+    let mut host = peer(host_lockstep);
+    let mut client = peer(Lockstep::join(("127.0.0.1", port)).unwrap());
+
+    run_until(&mut host, &mut client, 0, |host, client| {
+        lockstep(host).players() == 2 && lockstep(client).player() == 1
+    });
+
+    host.world
+        .resource_mut::<NextState<GameState>>()
+        .set(GameState::InGame);
+
+    run_until(&mut host, &mut client, 0, |_, client| {
+        *client.world.resource::<State<GameState>>().get() == GameState::InGame
+    });
+
+    (host, client)
+}
+
+fn build_mine_line(app: &mut App) {
+    build(app, BuildingType::Mine, 5, 5, MapDirection::Left);
+    for x in 1..=4 {
+        build(app, BuildingType::Belt, x, 5, MapDirection::Left);
+    }
+}
+
+#[test]
+fn peers_simulate_the_same_factory() {
+    let (mut host, mut client) = start();
+
+    build_mine_line(&mut host);
+    build(&mut client, BuildingType::Chest, 0, 5, MapDirection::Left);
+    advance(&mut host, &mut client, 300);
+
+    // inputs only change the factory once their turn comes, on both peers at once
+    client.world.send_event(InventoryEvent::ClearSlot {
+        tile_pos: TilePos::new(0, 5),
+        slot: 0,
+    });
+    advance(&mut host, &mut client, 301);
+    assert_eq!(factory_state(&mut host), factory_state(&mut client));
+
+    build(&mut host, BuildingType::Belt, 3, 5, MapDirection::Up);
+    advance(&mut host, &mut client, 600);
+
+    let state = factory_state(&mut host);
+    assert_eq!(state, factory_state(&mut client));
+    assert_eq!(state.buildings.len(), 6);
+    assert!(state.belt_items > 0);
+
+    assert!(lockstep(&host).desync().is_none());
+    assert!(lockstep(&client).desync().is_none());
+}
+
+#[test]
+fn undone_demolitions_come_back_on_every_peer() {
+    let (mut host, mut client) = start();
+
+    client
+        .add_state::<BuildMode>()
+        .insert_resource(NextState(Some(BuildMode::Enabled)))
+        .init_resource::<ActionMap>()
+        .init_resource::<Input<KeyCode>>()
+        .init_resource::<Input<MouseButton>>()
+        .add_plugins(HistoryPlugin);
+
+    build(&mut client, BuildingType::Chest, 0, 5, MapDirection::Left);
+    advance(&mut host, &mut client, 60);
+    client.world.send_event(SpawnItemEvent {
+        item_type: ItemType::Coal,
+        tile_pos: TilePos::new(0, 5),
+        amount: 7,
+    });
+    advance(&mut host, &mut client, 120);
+    let stored = factory_state(&mut host).buildings;
+
+    client.world.send_event(DemolishEvent {
+        tile_pos: TilePos::new(0, 5),
+    });
+    advance(&mut host, &mut client, 180);
+    assert!(factory_state(&mut host).buildings.is_empty());
+
+    // the host's belt isn't part of the client's history
+    build(&mut host, BuildingType::Belt, 5, 5, MapDirection::Left);
+    advance(&mut host, &mut client, 210);
+
+    client.world.send_event(HistoryEvent::Undo);
+    advance(&mut host, &mut client, 240);
+
+    let state = factory_state(&mut host);
+    assert_eq!(state, factory_state(&mut client));
+    assert_eq!(state.buildings[..1], stored);
+    assert_eq!(state.buildings[1].0, BuildingType::Belt);
+
+    client.world.send_event(HistoryEvent::Redo);
+    advance(&mut host, &mut client, 270);
+
+    let state = factory_state(&mut host);
+    assert_eq!(state, factory_state(&mut client));
+    assert_eq!(state.buildings.len(), 1);
+    assert_eq!(state.buildings[0].0, BuildingType::Belt);
+
+    client.world.send_event(HistoryEvent::Undo);
+    advance(&mut host, &mut client, 300);
+
+    let state = factory_state(&mut host);
+    assert_eq!(state, factory_state(&mut client));
+    assert_eq!(state.buildings[..1], stored);
+
+    assert!(lockstep(&host).desync().is_none());
+    assert!(lockstep(&client).desync().is_none());
+}
+
+#[test]
+fn diverging_factories_stop_with_a_report() {
+    let (mut host, mut client) = start();
+
+    build_mine_line(&mut host);
+    build(&mut host, BuildingType::Chest, 0, 5, MapDirection::Left);
+    advance(&mut host, &mut client, 120);
+
+    // a change that didn't go through a turn
+    client
+        .world
+        .query::<&mut Inventory>()
+        .single_mut(&mut client.world)
+        .insert(1, ItemType::Coal);
+
+    run_until(&mut host, &mut client, u64::MAX, |host, client| {
+        let stopped = |app: &App| {
+            lockstep(app)
+                .desync()
+                .is_some_and(|report| report.stop_tick == tick(app))
+        };
+        stopped(host) && stopped(client)
+    });
+
+    let report = lockstep(&host).desync().unwrap().clone();
+    assert!(report.tick >= 120);
+    assert_eq!(report.checksums.len(), 2);
+    assert_ne!(report.checksums[0].1, report.checksums[1].1);
+
+    // nobody gets past the last turn handed out before the desync
+    for _ in 0..30 {
+        for app in [&mut host, &mut client] {
+            app.world.resource_mut::<Simulation>().step();
+            app.update();
+        }
+    }
+
+    assert_eq!(tick(&host), report.stop_tick);
+    assert_eq!(tick(&client), report.stop_tick);
+
+    let dump = std::fs::read_to_string("desyncs/player-1.txt").unwrap();
+    assert!(dump.starts_with(&report.to_string()));
+    assert!(dump.contains("chest at 0, 5"));
+}
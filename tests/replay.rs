@@ -0,0 +1,192 @@
+use bevactorio::{
+    run_ticks, ActionMap, Belt, BuildMode, BuildTool, BuildingType, DemolishEvent, HistoryEvent,
+    HistoryPlugin, Inventory, Item, ItemType, MapDirection, Replay, ReplayPlayback, ReplayPlugin,
+    ReplayRecorder, Simulation, SpawnItemEvent, Tool,
+};
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::*;
+
+use self::common::{build, headless_app};
+
+mod common;
+
+const END_TICK: u64 = 1200;
+
+type Slots = Vec<Option<(ItemType, usize)>>;
+
+#[derive(Debug, PartialEq)]
+struct FactoryState {
+    tick: u64,
+    buildings: Vec<(BuildingType, (u32, u32), Option<Slots>)>,
+    belt_items: usize,
+    items: usize,
+    tool: bool,
+}
+
+fn replay_app() -> App {
+    let mut app = headless_app();
+    app.add_plugins(ReplayPlugin).init_resource::<Tool>();
+    app
+}
+
+fn factory_state(app: &mut App) -> FactoryState {
+    let mut buildings = app
+        .world
+        .query::<(&BuildingType, &TilePos, Option<&Inventory>)>()
+        .iter(&app.world)
+        .map(|(building_type, origin, inventory)| {
+            let slots = inventory.map(|inventory| inventory.slots.to_vec());
+            (*building_type, (origin.x, origin.y), slots)
+        })
+        .collect::<Vec<_>>();
+    buildings.sort_by_key(|(_, origin, _)| *origin);
+
+    let belt_items = app
+        .world
+        .query::<&Belt>()
+        .iter(&app.world)
+        .map(|belt| belt.items.len())
+        .sum();
+    let items = app.world.query::<&Item>().iter(&app.world).count();
+
+    let chest_tool = Tool::Build(BuildTool {
+        building: BuildingType::Chest,
+        direction: MapDirection::Left,
+    });
+
+    FactoryState {
+        tick: app.world.resource::<Simulation>().tick(),
+        buildings,
+        belt_items,
+        items,
+        tool: *app.world.resource::<Tool>() == chest_tool,
+    }
+}
+
+fn advance(app: &mut App, ticks: u32) {
+    run_ticks(&mut app.world, ticks);
+    app.update();
+}
+
+// builds a mine feeding a chest, breaks the belt line for a while and redirects a belt
+fn record_session() -> (Replay, FactoryState) {
+    let mut app = replay_app();
+    app.insert_resource(ReplayRecorder(Replay::new(0, None)));
+
+    build(&mut app, BuildingType::Mine, 5, 5, MapDirection::Left);
+    for x in 1..=4 {
+        build(&mut app, BuildingType::Belt, x, 5, MapDirection::Left);
+    }
+    app.update();
+    advance(&mut app, 120);
+
+    *app.world.resource_mut::<Tool>() = Tool::Build(BuildTool {
+        building: BuildingType::Chest,
+        direction: MapDirection::Left,
+    });
+    build(&mut app, BuildingType::Chest, 0, 5, MapDirection::Left);
+    app.update();
+    advance(&mut app, 300);
+
+    app.world.send_event(DemolishEvent {
+        tile_pos: TilePos::new(2, 5),
+    });
+    app.update();
+    advance(&mut app, 60);
+
+    build(&mut app, BuildingType::Belt, 2, 5, MapDirection::Left);
+    build(&mut app, BuildingType::Belt, 3, 5, MapDirection::Up);
+    app.update();
+    // a second frame at the same tick
+    build(&mut app, BuildingType::Belt, 3, 5, MapDirection::Left);
+    app.update();
+
+    let remaining = END_TICK - app.world.resource::<Simulation>().tick();
+    advance(&mut app, remaining as u32);
+
+    let replay = app.world.remove_resource::<ReplayRecorder>().unwrap().0;
+    (replay, factory_state(&mut app))
+}
+
+fn play_back(replay: Replay) -> FactoryState {
+    let mut app = replay_app();
+    app.insert_resource(ReplayPlayback::new(replay));
+
+    // one tick per frame, playback holds the simulation whenever the next inputs are due
+    while app.world.contains_resource::<ReplayPlayback>() {
+        app.world.resource_mut::<Simulation>().step();
+        app.update();
+    }
+
+    let remaining = END_TICK - app.world.resource::<Simulation>().tick();
+    advance(&mut app, remaining as u32);
+
+    factory_state(&mut app)
+}
+
+#[test]
+fn playback_reproduces_recorded_session() {
+    let (replay, recorded) = record_session();
+
+    assert_eq!(replay.frames.len(), 5);
+    assert!(recorded.tool);
+    assert!(recorded.belt_items > 0);
+
+    assert_eq!(play_back(replay), recorded);
+}
+
+#[test]
+fn replay_survives_a_round_trip_through_a_file() {
+    let (replay, recorded) = record_session();
+
+    let path = std::env::temp_dir().join("bevactorio-replay-test.ron");
+    replay.write(&path).unwrap();
+    let replay = Replay::read(&path).unwrap();
+
+    assert_eq!(replay.last_tick(), 480);
+    assert_eq!(play_back(replay), recorded);
+}
+
+#[test]
+fn undone_demolitions_play_back_with_their_contents() {
+    let mut app = replay_app();
+    app.add_state::<BuildMode>()
+        .insert_resource(NextState(Some(BuildMode::Enabled)))
+        .init_resource::<ActionMap>()
+        .init_resource::<Input<KeyCode>>()
+        .init_resource::<Input<MouseButton>>()
+        .add_plugins(HistoryPlugin)
+        .insert_resource(ReplayRecorder(Replay::new(0, None)));
+    app.update();
+
+    build(&mut app, BuildingType::Chest, 0, 5, MapDirection::Left);
+    app.update();
+    app.world.send_event(SpawnItemEvent {
+        item_type: ItemType::Coal,
+        tile_pos: TilePos::new(0, 5),
+        amount: 7,
+    });
+    app.update();
+    advance(&mut app, 60);
+
+    app.world.send_event(DemolishEvent {
+        tile_pos: TilePos::new(0, 5),
+    });
+    app.update();
+    advance(&mut app, 60);
+
+    app.world.send_event(HistoryEvent::Undo);
+    app.update();
+
+    let remaining = END_TICK - app.world.resource::<Simulation>().tick();
+    advance(&mut app, remaining as u32);
+
+    let replay = app.world.remove_resource::<ReplayRecorder>().unwrap().0;
+    let recorded = factory_state(&mut app);
+
+    let restored = [Some((ItemType::Coal, 5)), Some((ItemType::Coal, 2))];
+    let (_, _, slots) = &recorded.buildings[0];
+    assert_eq!(slots.as_ref().unwrap()[..2], restored);
+
+    assert_eq!(play_back(replay), recorded);
+}
@@ -0,0 +1,169 @@
+use std::path::Path;
+use std::{env, fs};
+
+use bevactorio::{
+    belt_dir_between, capture_belt_items, run_ticks, BuildingType, Inventory, MapDirection,
+    MapSnapshot, TileArea,
+};
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::*;
+
+use self::common::{build, headless_app};
+
+mod common;
+
+const SNAPSHOTS_PATH: &str = "tests/snapshots";
+
+// `UPDATE_SNAPSHOTS=1 cargo test` writes the golden files instead of comparing with them
+fn assert_golden(name: &str, actual: &str) {
+    let path = Path::new(SNAPSHOTS_PATH).join(name).with_extension("txt");
+
+    if env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        fs::write(&path, actual).unwrap();
+        return;
+    }
+
+    let expected = fs::read_to_string(&path).unwrap_or_else(|e| {
+        panic!("{}: {e}, UPDATE_SNAPSHOTS=1 creates it", path.display());
+    });
+
+    assert!(
+        expected == actual,
+        "{} differs\nexpected:\n{expected}\nactual:\n{actual}",
+        path.display()
+    );
+}
+
+fn build_snapshot(app: &mut App, snapshot: &MapSnapshot, origin: TilePos) {
+    for request in snapshot.build_requests(origin).unwrap() {
+        app.world.send_event(request);
+    }
+    app.update();
+}
+
+#[test]
+fn snapshots_survive_a_round_trip_through_the_map() {
+    let text = "
+        .MI.C.
+        vNE<<^
+        >>>^.^
+    ";
+    let snapshot: MapSnapshot = text.parse().unwrap();
+    assert_eq!((snapshot.width(), snapshot.height()), (6, 3));
+
+    let mut app = headless_app();
+    let origin = TilePos::new(10, 20);
+    build_snapshot(&mut app, &snapshot, origin);
+
+    let captured = MapSnapshot::capture(&mut app.world, snapshot.area(origin));
+    assert_eq!(captured, snapshot);
+    assert_eq!(captured.to_string(), ".MI.C.\nvNE<<^\n>>>^.^\n");
+}
+
+#[test]
+fn broken_snapshots_are_rejected() {
+    assert!("..\n...".parse::<MapSnapshot>().is_err());
+    assert!(".x.".parse::<MapSnapshot>().is_err());
+
+    let origin = TilePos::new(0, 0);
+    for text in ["MI\nN.", "MI\n.E", ".MI\nNE.", "*"] {
+        let snapshot: MapSnapshot = text.parse().unwrap();
+        assert!(snapshot.build_requests(origin).is_err(), "{text}");
+    }
+}
+
+// dragging belts like the mouse does, each belt points at the one placed after it
+#[test]
+fn dragged_belts_point_along_the_path() {
+    let mut app = headless_app();
+
+    let path = [
+        (0, 0),
+        (1, 0),
+        (2, 0),
+        (2, 1),
+        (2, 2),
+        (1, 2),
+        (0, 2),
+        (0, 3),
+        (0, 4),
+        // not a neighbour, the belt before keeps its direction
+        (3, 4),
+        (3, 3),
+        (4, 3),
+        (4, 4),
+    ];
+
+    let mut last: Option<TilePos> = None;
+
+    for (x, y) in path {
+        let tile_pos = TilePos::new(x, y);
+
+        let direction = match last.and_then(|last_pos| {
+            belt_dir_between(tile_pos, last_pos)
+                .and_then(|tile_type| tile_type.belt_direction())
+                .map(|direction| (last_pos, direction))
+        }) {
+            Some((last_pos, direction)) => {
+                build(
+                    &mut app,
+                    BuildingType::Belt,
+                    last_pos.x,
+                    last_pos.y,
+                    direction,
+                );
+                direction
+            }
+            None => MapDirection::Down,
+        };
+
+        build(&mut app, BuildingType::Belt, x, y, direction);
+        app.update();
+        last = Some(tile_pos);
+    }
+
+    let area = TileArea::from_corners(TilePos::new(0, 0), TilePos::new(4, 4));
+    assert_golden(
+        "belt_drag",
+        &MapSnapshot::capture(&mut app.world, area).to_string(),
+    );
+}
+
+// straight belts hand items over at their start, belts turning a corner halfway along,
+// belts running into each other not at all
+#[test]
+fn items_cross_belt_junctions() {
+    let factory: MapSnapshot = "
+        .....MI
+        C<<<<NE
+        .....MI
+        ^<<<<NE
+        .....MI
+        ><<<<NE
+    "
+    .parse()
+    .unwrap();
+
+    let mut app = headless_app();
+    let origin = TilePos::new(0, 0);
+    build_snapshot(&mut app, &factory, origin);
+
+    run_ticks(&mut app.world, 1200);
+    app.update();
+
+    let chest = app
+        .world
+        .query::<&Inventory>()
+        .single(&app.world)
+        .slots
+        .iter()
+        .flatten()
+        .map(|(_, amount)| amount)
+        .sum::<usize>();
+
+    let items = capture_belt_items(&mut app.world, factory.area(origin));
+    assert_golden(
+        "belt_junctions",
+        &format!("{factory}\n{items}\nchest {chest}\n"),
+    );
+}
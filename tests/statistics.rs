@@ -0,0 +1,108 @@
+use std::time::Duration;
+
+use bevactorio::{ItemFlow, ItemType, ProductionStatistics, StatisticsWindow, SAMPLES};
+
+fn secs(secs: f64) -> Duration {
+    Duration::from_secs_f64(secs)
+}
+
+fn total(statistics: &ProductionStatistics, window: StatisticsWindow) -> u32 {
+    statistics
+        .get(ItemType::Coal, ItemFlow::Produced)
+        .unwrap()
+        .window(window)
+        .total()
+}
+
+fn samples(statistics: &ProductionStatistics, window: StatisticsWindow) -> Vec<u32> {
+    statistics
+        .get(ItemType::Coal, ItemFlow::Produced)
+        .unwrap()
+        .window(window)
+        .samples()
+        .collect()
+}
+
+fn produce(statistics: &mut ProductionStatistics, now: Duration, amount: u32) {
+    statistics.record(now, ItemType::Coal, ItemFlow::Produced, amount);
+}
+
+// the minute window has one second buckets
+#[test]
+fn buckets_roll_over_after_a_window() {
+    let mut statistics = ProductionStatistics::default();
+
+    produce(&mut statistics, secs(0.5), 3);
+    produce(&mut statistics, secs(0.9), 1);
+    produce(&mut statistics, secs(1.5), 2);
+
+    let recent = samples(&statistics, StatisticsWindow::Minute);
+    assert_eq!(recent.len(), SAMPLES);
+    assert_eq!(recent[SAMPLES - 2..], [4, 2]);
+    assert_eq!(total(&statistics, StatisticsWindow::Minute), 6);
+
+    // the first bucket is reused for second 60, the one after it for second 61
+    statistics.advance(secs(60.5));
+    assert_eq!(total(&statistics, StatisticsWindow::Minute), 2);
+    assert_eq!(
+        samples(&statistics, StatisticsWindow::Minute)[SAMPLES - 1],
+        0
+    );
+
+    statistics.advance(secs(61.5));
+    assert_eq!(total(&statistics, StatisticsWindow::Minute), 0);
+
+    // the longer windows still remember all of it
+    assert_eq!(total(&statistics, StatisticsWindow::TenMinutes), 6);
+    assert_eq!(total(&statistics, StatisticsWindow::Hour), 6);
+}
+
+#[test]
+fn windows_total_what_they_cover() {
+    let mut statistics = ProductionStatistics::default();
+
+    // one item every ten seconds for 70 minutes
+    for step in 0..420 {
+        produce(&mut statistics, secs(step as f64 * 10.), 1);
+    }
+
+    // the window ends with the bucket of the last item at 4190s, the minute window covers
+    // 4131s to 4190s, ten minutes 3600s to 4199s and the hour 600s to 4199s
+    assert_eq!(total(&statistics, StatisticsWindow::Minute), 6);
+    assert_eq!(total(&statistics, StatisticsWindow::TenMinutes), 60);
+    assert_eq!(total(&statistics, StatisticsWindow::Hour), 360);
+
+    let history = statistics.get(ItemType::Coal, ItemFlow::Produced).unwrap();
+    for window in StatisticsWindow::ALL {
+        assert_eq!(history.rate(window), 6., "{window:?}");
+    }
+}
+
+#[test]
+fn young_windows_average_over_the_time_so_far() {
+    let mut statistics = ProductionStatistics::default();
+
+    // one item a second for the first half minute
+    for second in 1..=30 {
+        produce(&mut statistics, secs(second as f64), 1);
+    }
+
+    let history = statistics.get(ItemType::Coal, ItemFlow::Produced).unwrap();
+    for window in StatisticsWindow::ALL {
+        assert_eq!(history.rate(window), 60., "{window:?}");
+    }
+}
+
+#[test]
+fn long_pauses_clear_every_window() {
+    let mut statistics = ProductionStatistics::default();
+
+    produce(&mut statistics, secs(10.), 5);
+    statistics.advance(secs(2. * 60. * 60.));
+
+    for window in StatisticsWindow::ALL {
+        assert_eq!(total(&statistics, window), 0, "{window:?}");
+    }
+
+    assert!(statistics.item_types() == [ItemType::Coal]);
+}
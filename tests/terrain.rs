@@ -0,0 +1,48 @@
+use bevactorio::{Building, BuildingType, MapDirection, Terrain, TerrainType, TILEMAP_SIZE};
+use bevy_ecs_tilemap::prelude::*;
+
+use self::common::{build, headless_app};
+
+mod common;
+
+fn rock_tiles(terrain: &Terrain) -> Vec<TilePos> {
+    (0..TILEMAP_SIZE.y)
+        .flat_map(|y| (0..TILEMAP_SIZE.x).map(move |x| TilePos::new(x, y)))
+        .filter(|tile_pos| terrain.get(*tile_pos) == TerrainType::Rock)
+        .collect()
+}
+
+#[test]
+fn the_seed_decides_the_map() {
+    assert_eq!(Terrain::generate(7), Terrain::generate(7));
+    assert_ne!(Terrain::generate(7), Terrain::generate(8));
+
+    // the start area is always free
+    let centre = TilePos::new(TILEMAP_SIZE.x / 2, TILEMAP_SIZE.y / 2);
+    for seed in 0..20 {
+        let terrain = Terrain::generate(seed);
+        assert!(!rock_tiles(&terrain).is_empty());
+        assert!(terrain.is_buildable(centre));
+    }
+}
+
+#[test]
+fn nothing_is_built_on_rock() {
+    let mut app = headless_app();
+    let terrain = Terrain::generate(7);
+    let rock = rock_tiles(&terrain)[0];
+    app.insert_resource(terrain);
+
+    build(
+        &mut app,
+        BuildingType::Chest,
+        rock.x,
+        rock.y,
+        MapDirection::Up,
+    );
+    build(&mut app, BuildingType::Chest, 32, 32, MapDirection::Up);
+    app.update();
+
+    let buildings = app.world.query::<&Building>().iter(&app.world).count();
+    assert_eq!(buildings, 1);
+}